@@ -0,0 +1,149 @@
+//! Search latency and indexing throughput on synthetic corpora.
+//!
+//! Uses [`MockEmbedder`], so no model is downloaded and runs are repeatable. Run with
+//! `cargo bench`, or `cargo bench -- hybrid_search/in_rust` for a single group.
+//!
+//! SurrealDB 1.5 has no vector index on the `files` table, so both search groups are full
+//! scans: `server_side_scan` scores every record with the `vector::` functions inside the
+//! database, `in_rust` fetches every record and scores it in Rust.
+
+use criterion::{ criterion_group, criterion_main, BenchmarkId, Criterion, Throughput };
+use file_embeddings::{ FileEmbeddingConfig, FileEmbeddingSystem, MockEmbedder };
+use std::fs;
+use std::path::PathBuf;
+use tempfile::TempDir;
+use tokio::runtime::Runtime;
+
+const CORPUS_SIZES: [usize; 3] = [100, 1_000, 10_000];
+
+const QUERY: &str = "memory safety in concurrent systems";
+
+const VOCABULARY: [&str; 24] = [
+    "rust", "memory", "safety", "garden", "tomato", "invoice", "tax", "return",
+    "concurrent", "systems", "thread", "recipe", "soup", "budget", "meeting", "notes",
+    "compiler", "error", "handling", "travel", "train", "schedule", "database", "index",
+];
+
+/// A deterministic pseudo-random sentence for document `i`.
+fn synthetic_document(i: usize) -> String {
+    let mut state = (i as u64).wrapping_add(1).wrapping_mul(6364136223846793005);
+    (0..40)
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            VOCABULARY[((state >> 33) as usize) % VOCABULARY.len()]
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+async fn mock_system(db_dir: &TempDir, config: FileEmbeddingConfig) -> FileEmbeddingSystem {
+    FileEmbeddingSystem::with_embedder(
+        db_dir.path().join("db").to_str().unwrap(),
+        config,
+        Box::new(MockEmbedder::new(384))
+    ).await.expect("Failed to create system")
+}
+
+/// A system holding `size` synthetic documents.
+async fn indexed_system(
+    db_dir: &TempDir,
+    size: usize,
+    server_side_scoring: bool
+) -> FileEmbeddingSystem {
+    let config = FileEmbeddingConfig {
+        server_side_scoring,
+        flush_after_indexing: false,
+        ..Default::default()
+    };
+    let system = mock_system(db_dir, config).await;
+    for i in 0..size {
+        system
+            .index_content(&format!("doc_{}.txt", i), &synthetic_document(i), Some("txt")).await
+            .expect("Failed to index document");
+    }
+    system
+}
+
+fn bench_hybrid_search(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("hybrid_search");
+    group.sample_size(20);
+
+    for (name, server_side_scoring) in [("server_side_scan", true), ("in_rust", false)] {
+        for size in CORPUS_SIZES {
+            let db_dir = TempDir::new().unwrap();
+            let system = runtime.block_on(indexed_system(&db_dir, size, server_side_scoring));
+            group.throughput(Throughput::Elements(size as u64));
+            group.bench_with_input(BenchmarkId::new(name, size), &system, |b, system| {
+                b.to_async(&runtime).iter(|| async {
+                    system.hybrid_search(QUERY, 10).await.expect("Search failed")
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+/// Scoring alone: a fixed vector through `search_by_embedding`, so no query is embedded.
+fn bench_search_by_embedding(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut group = c.benchmark_group("search_by_embedding");
+    group.sample_size(20);
+
+    let mut state: u64 = 42;
+    let embedding: Vec<f32> = (0..384)
+        .map(|_| {
+            state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+            ((state >> 33) as f32) / (u32::MAX as f32) - 0.25
+        })
+        .collect();
+
+    for (name, server_side_scoring) in [("server_side_scan", true), ("in_rust", false)] {
+        for size in CORPUS_SIZES {
+            let db_dir = TempDir::new().unwrap();
+            let system = runtime.block_on(indexed_system(&db_dir, size, server_side_scoring));
+            group.throughput(Throughput::Elements(size as u64));
+            group.bench_with_input(BenchmarkId::new(name, size), &system, |b, system| {
+                b.to_async(&runtime).iter(|| async {
+                    system.search_by_embedding(&embedding, 10).await.expect("Search failed")
+                });
+            });
+        }
+    }
+    group.finish();
+}
+
+fn bench_index_file(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let docs = TempDir::new().unwrap();
+    let paths: Vec<PathBuf> = (0..100)
+        .map(|i| {
+            let path = docs.path().join(format!("doc_{}.txt", i));
+            fs::write(&path, synthetic_document(i)).unwrap();
+            path
+        })
+        .collect();
+    let db_dir = TempDir::new().unwrap();
+    let config = FileEmbeddingConfig {
+        flush_after_indexing: false,
+        ..Default::default()
+    };
+    let system = runtime.block_on(mock_system(&db_dir, config));
+
+    let mut group = c.benchmark_group("index_file");
+    group.throughput(Throughput::Elements(1));
+    // Cycles through the files, so after the first pass every call replaces a record
+    let mut next = 0;
+    group.bench_function("txt", |b| {
+        b.to_async(&runtime).iter(|| {
+            let path = paths[next % paths.len()].clone();
+            next += 1;
+            let system = &system;
+            async move { system.index_file(path).await.expect("Indexing failed") }
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_hybrid_search, bench_search_by_embedding, bench_index_file);
+criterion_main!(benches);
@@ -0,0 +1,144 @@
+//! Document embeddings pooled from per-sentence embeddings.
+//!
+//! A single embedding of a long, mixed document tends to land between its topics. Pooling
+//! the embeddings of its sentences keeps each topic's signal, and the sentence vectors
+//! double as a way to pick the sentence that best matches a query for the preview.
+
+use crate::preview;
+use crate::similarity::{ self, cosine_similarity };
+
+/// How a document's `content_embedding` is computed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Aggregation {
+    /// Embed the document as one text.
+    #[default]
+    WholeDoc,
+    /// Average the embeddings of its sentences.
+    MeanSentences,
+    /// Take the largest value of each dimension across its sentences' embeddings.
+    MaxSentences,
+}
+
+impl Aggregation {
+    pub fn embeds_sentences(&self) -> bool {
+        *self != Aggregation::WholeDoc
+    }
+
+    /// Pools sentence embeddings into one normalized document embedding, or returns `None`
+    /// for `WholeDoc` or when there are no sentences.
+    pub fn pool(&self, embeddings: &[Vec<f32>]) -> Option<Vec<f32>> {
+        let first = embeddings.first()?;
+        let pooled = match self {
+            Aggregation::WholeDoc => {
+                return None;
+            }
+            Aggregation::MeanSentences => {
+                let mut sum = vec![0.0; first.len()];
+                for embedding in embeddings {
+                    sum.iter_mut()
+                        .zip(embedding)
+                        .for_each(|(total, x)| *total += x);
+                }
+                sum.iter()
+                    .map(|total| total / (embeddings.len() as f32))
+                    .collect::<Vec<f32>>()
+            }
+            Aggregation::MaxSentences => {
+                let mut max = first.clone();
+                for embedding in &embeddings[1..] {
+                    max.iter_mut()
+                        .zip(embedding)
+                        .for_each(|(best, x)| *best = best.max(*x));
+                }
+                max
+            }
+        };
+        Some(similarity::normalize(&pooled))
+    }
+}
+
+/// Splits `text` into trimmed sentences, ending one after `.`, `!` or `?` followed by
+/// whitespace, and at blank lines.
+pub fn split_sentences(text: &str) -> Vec<&str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    let mut chars = text.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let next = chars.peek().map(|&(_, next)| next);
+        let boundary = match c {
+            '.' | '!' | '?' => next.is_some_and(char::is_whitespace),
+            '\n' => next == Some('\n'),
+            _ => false,
+        };
+        if boundary {
+            let end = i + c.len_utf8();
+            pieces.push(&text[start..end]);
+            start = end;
+        }
+    }
+    pieces.push(&text[start..]);
+    pieces
+        .into_iter()
+        .map(str::trim)
+        .filter(|sentence| !sentence.is_empty())
+        .collect()
+}
+
+/// The sentence of `content` whose stored embedding is closest to `query_embedding`, or
+/// `None` if `embeddings` don't line up with its sentences.
+pub fn best_sentence<'a>(
+    content: &'a str,
+    embeddings: &[Vec<f32>],
+    query_embedding: &[f32]
+) -> Option<&'a str> {
+    let sentences = split_sentences(content);
+    if sentences.len() != embeddings.len() {
+        return None;
+    }
+    let scores: Vec<f32> = embeddings
+        .iter()
+        .map(|embedding| cosine_similarity(embedding, query_embedding))
+        .collect();
+    preview::best_window(&scores).map(|i| sentences[i])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_sentences() {
+        let text = "Rust is fast. Is it safe? Yes!\n\nA new paragraph\nwith a wrapped line v1.2 here";
+        assert_eq!(split_sentences(text), vec![
+            "Rust is fast.",
+            "Is it safe?",
+            "Yes!",
+            "A new paragraph\nwith a wrapped line v1.2 here",
+        ]);
+        assert!(split_sentences("  \n\n ").is_empty());
+    }
+
+    #[test]
+    fn test_pooling() {
+        let embeddings = vec![vec![1.0, 0.0, 0.0], vec![0.0, 1.0, 0.0], vec![0.0, 0.5, 0.0]];
+
+        let mean = Aggregation::MeanSentences.pool(&embeddings).unwrap();
+        let expected = similarity::normalize(&[1.0, 1.5, 0.0]);
+        assert!(mean.iter().zip(&expected).all(|(a, b)| (a - b).abs() < 1e-6), "{:?}", mean);
+
+        let max = Aggregation::MaxSentences.pool(&embeddings).unwrap();
+        let expected = similarity::normalize(&[1.0, 1.0, 0.0]);
+        assert!(max.iter().zip(&expected).all(|(a, b)| (a - b).abs() < 1e-6), "{:?}", max);
+
+        assert_eq!(Aggregation::WholeDoc.pool(&embeddings), None);
+        assert_eq!(Aggregation::MeanSentences.pool(&[]), None);
+    }
+
+    #[test]
+    fn test_best_sentence() {
+        let content = "About Rust. About gardens.";
+        let embeddings = vec![vec![1.0, 0.0], vec![0.0, 1.0]];
+        assert_eq!(best_sentence(content, &embeddings, &[0.1, 0.9]), Some("About gardens."));
+        assert_eq!(best_sentence(content, &embeddings[..1], &[0.1, 0.9]), None);
+    }
+}
@@ -0,0 +1,61 @@
+use crate::similarity::cosine_similarity;
+
+/// Groups embeddings so every pair within a cluster has cosine similarity above `threshold`.
+///
+/// Greedy complete-linkage: each item joins the first cluster whose members it is similar
+/// to *all* of, or starts a new one. This is O(n²) in the corpus size, which is fine for
+/// finding duplicates in a personal index but not for millions of files.
+///
+/// Paths within a cluster are sorted, and clusters are ordered largest first.
+pub fn cluster_embeddings(items: &[(String, Vec<f32>)], threshold: f32) -> Vec<Vec<String>> {
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    for (i, (_, embedding)) in items.iter().enumerate() {
+        let home = clusters.iter_mut().find(|members| {
+            members
+                .iter()
+                .all(|&j| cosine_similarity(embedding, &items[j].1) > threshold)
+        });
+        match home {
+            Some(members) => members.push(i),
+            None => clusters.push(vec![i]),
+        }
+    }
+
+    let mut clusters: Vec<Vec<String>> = clusters
+        .into_iter()
+        .map(|members| {
+            let mut paths: Vec<String> = members
+                .into_iter()
+                .map(|i| items[i].0.clone())
+                .collect();
+            paths.sort();
+            paths
+        })
+        .collect();
+    clusters.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+    clusters
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_near_duplicates_cluster_together() {
+        let items = vec![
+            ("b.txt".to_string(), vec![0.98, 0.2, 0.0]),
+            ("outlier.txt".to_string(), vec![0.0, 0.0, 1.0]),
+            ("a.txt".to_string(), vec![1.0, 0.0, 0.0]),
+            ("c.txt".to_string(), vec![0.97, 0.0, 0.24]),
+        ];
+
+        let clusters = cluster_embeddings(&items, 0.9);
+        assert_eq!(clusters, vec![
+            vec!["a.txt".to_string(), "b.txt".to_string(), "c.txt".to_string()],
+            vec!["outlier.txt".to_string()],
+        ]);
+
+        // Above every pairwise similarity, each file is its own cluster
+        assert_eq!(cluster_embeddings(&items, 0.999).len(), 4);
+    }
+}
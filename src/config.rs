@@ -0,0 +1,257 @@
+use std::collections::HashMap;
+
+use fastembed::EmbeddingModel;
+
+use crate::aggregate::Aggregation;
+use crate::ensemble::EnsembleModel;
+use crate::error::FileEmbeddingError;
+use crate::extract::TruncationStrategy;
+use crate::preprocess::Preprocessors;
+use crate::preview::PreviewStrategy;
+use crate::progress::ProgressCallback;
+use crate::quantize::EmbeddingPrecision;
+use crate::retry::RetryConfig;
+use crate::search::{ Metric, TieBreaker };
+use crate::stopwords;
+use crate::storage::StorageConfig;
+
+/// What `index_directory` does when a file fails to index.
+///
+/// Unsupported files are skipped either way; they are not failures.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum IndexErrorPolicy {
+    /// Log the error and move on to the next file.
+    #[default]
+    Continue,
+    /// Return the first error, leaving the rest of the directory unindexed.
+    StopOnFirst,
+}
+
+/// What `index_file` does with a path that is already indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OnDuplicate {
+    /// Fail with [`FileEmbeddingError::DuplicatePath`](crate::FileEmbeddingError::DuplicatePath).
+    Error,
+    /// Leave the stored record alone without reading the file.
+    Skip,
+    /// Re-embed the file and replace the stored record.
+    #[default]
+    Update,
+}
+
+/// Most the index may hold before
+/// [`FileEmbeddingSystem::evict`](crate::FileEmbeddingSystem::evict) drops the records
+/// matched least recently, for a bounded cache of relevant files. Unset limits are unbounded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Capacity {
+    /// Most records kept.
+    pub max_files: Option<usize>,
+    /// Most `size_bytes` kept, summed over records.
+    pub max_bytes: Option<u64>,
+}
+
+impl Capacity {
+    pub fn is_bounded(&self) -> bool {
+        self.max_files.is_some() || self.max_bytes.is_some()
+    }
+
+    /// Whether `files` records totalling `bytes` exceed the capacity.
+    pub fn is_exceeded(&self, files: usize, bytes: u64) -> bool {
+        self.max_files.is_some_and(|max| files > max) || self.max_bytes.is_some_and(|max| bytes > max)
+    }
+}
+
+/// Tunables for a [`FileEmbeddingSystem`](crate::FileEmbeddingSystem).
+///
+/// Start from [`FileEmbeddingConfig::default`] and override the fields you need.
+#[derive(Debug, Clone)]
+pub struct FileEmbeddingConfig {
+    /// Primary model, whose vectors are stored in `content_embedding`.
+    pub embedding_model: EmbeddingModel,
+    /// Additional models whose scores are fused with the primary model's.
+    pub ensemble: Vec<EnsembleModel>,
+    /// Weight of the primary model's score when an ensemble is configured.
+    pub primary_weight: f32,
+    /// Texts passed to the model per inference call, or `None` for fastembed's default (256).
+    ///
+    /// Only matters when many texts are embedded at once (batched searches, best-match
+    /// previews, pipelined indexing). Smaller batches lower peak memory, larger ones improve
+    /// throughput.
+    pub embed_batch_size: Option<usize>,
+    /// Files read and extracted at once by `index_directory`.
+    ///
+    /// At 1, each file is read, embedded and stored before the next is opened. Above 1,
+    /// extraction runs ahead on the blocking thread pool and feeds the embedding stage
+    /// through a bounded channel, so disk IO overlaps inference.
+    pub extract_concurrency: usize,
+    /// Extracted files the embedding stage takes off the channel at a time when
+    /// `extract_concurrency` is above 1. Their documents are embedded in one model call.
+    pub pipeline_batch_size: usize,
+    /// Only read this many characters of each file, or `None` to read files whole.
+    ///
+    /// Text files are read incrementally and stop at the limit, so huge logs don't have to
+    /// fit in memory. The model only sees its first few hundred tokens anyway; the rest
+    /// matters only for `BestMatch` previews. PDFs are still extracted whole, then cut.
+    pub max_embed_chars: Option<usize>,
+    /// Which part of a document longer than the model's input is embedded.
+    ///
+    /// `Tail` and `Middle` can only keep what was read, so combine them with a
+    /// `max_embed_chars` large enough to reach the end of the documents that matter.
+    pub embed_truncation: TruncationStrategy,
+    /// Roughly how many characters fit in the model's input, used by `Tail` and `Middle`
+    /// truncation. The default suits 512-token models on English prose.
+    pub embed_input_chars: usize,
+    /// Whether `content_embedding` embeds the whole document or pools its sentences.
+    ///
+    /// Sentence pooling costs one embedding per sentence and stores each sentence vector;
+    /// previews then show the sentence closest to the query. `embed_truncation` and
+    /// `include_path_in_embedding` only apply to whole-document embeddings.
+    pub aggregation: Aggregation,
+    /// Embed the file's name and parent directories along with its content, so a sparse
+    /// `finance/tax_return_2023.pdf` is still found by "tax return". Previews are unaffected.
+    pub include_path_in_embedding: bool,
+    /// Store the words of each file's parent directories as `path_tokens`, leaving the
+    /// embedding alone. They can be filtered on with [`SearchFilter::path_token`] and
+    /// boost results by `path_token_boost`.
+    ///
+    /// [`SearchFilter::path_token`]: crate::SearchFilter
+    pub index_path_tokens: bool,
+    /// Added to a result's score, scaled by the fraction of query words found in its
+    /// `path_tokens`, so a query naming a folder favours the files in it. Scores can then
+    /// exceed 1.
+    pub path_token_boost: f32,
+    /// Embed JSON, YAML and TOML files as flattened `key.path: value` lines instead of raw
+    /// text, and store their top-level keys; see [`crate::structured`].
+    pub flatten_structured: bool,
+    /// Content transforms applied before embedding, keyed by extension.
+    pub preprocessors: Preprocessors,
+    /// Language of the indexed files, e.g. `"en"`. When set, its stopwords are ignored in
+    /// filename matching, suggestions and highlighting; see [`crate::stopwords`].
+    pub language: Option<String>,
+    /// Maximum number of characters in a file's `content_preview`.
+    pub preview_chars: usize,
+    /// `preview_chars` overrides keyed by lowercase extension without the dot, e.g. a
+    /// shorter `"pdf"` preview to show less extraction noise.
+    pub preview_chars_by_extension: HashMap<String, usize>,
+    /// How the preview is chosen; see [`PreviewStrategy`].
+    pub preview_strategy: PreviewStrategy,
+    /// Collapse whitespace and strip control characters (e.g. form feeds) in previews of
+    /// PDF and Word documents, whose extracted text is full of layout spacing.
+    pub clean_preview: bool,
+    /// Metric used by searches that don't pick one explicitly.
+    pub metric: Metric,
+    /// How results with equal scores are ordered.
+    pub tie_breaker: TieBreaker,
+    /// Retry policy for transient database errors.
+    pub retry: RetryConfig,
+    /// Score searches inside SurrealDB so only the top results are transferred.
+    ///
+    /// Quantized storage and ensembles always score in Rust. Turn this off for SurrealDB
+    /// versions without the `vector::` functions.
+    pub server_side_scoring: bool,
+    /// Call [`FileEmbeddingSystem::flush`](crate::FileEmbeddingSystem::flush) at the end of
+    /// every `index_directory` run.
+    pub flush_after_indexing: bool,
+    /// Storage format for embeddings; see [`crate::quantize`] for the size/accuracy tradeoff.
+    pub embedding_precision: EmbeddingPrecision,
+    /// Also index files with a missing or unrecognised extension if their first bytes
+    /// look like UTF-8 text, storing `text/plain` as their MIME type.
+    pub index_extensionless_text: bool,
+    /// Skip files whose extracted text, trimmed, has fewer characters than this, such as a
+    /// one-word todo: their embeddings carry too little to be worth matching. 0 indexes
+    /// everything.
+    pub min_content_chars: usize,
+    /// Called after each file `index_directory` processes, with elapsed time and an ETA.
+    pub progress: Option<ProgressCallback>,
+    /// How `index_file` and `index_directory` treat files that are already indexed.
+    pub on_duplicate: OnDuplicate,
+    /// Whether `index_directory` tolerates per-file failures.
+    pub index_error_policy: IndexErrorPolicy,
+    /// How the database is opened; see [`crate::storage`] for RocksDB tuning.
+    pub storage: StorageConfig,
+}
+
+impl Default for FileEmbeddingConfig {
+    fn default() -> Self {
+        Self {
+            embedding_model: EmbeddingModel::AllMiniLML6V2,
+            ensemble: Vec::new(),
+            primary_weight: 1.0,
+            embed_batch_size: None,
+            extract_concurrency: 1,
+            pipeline_batch_size: 16,
+            max_embed_chars: None,
+            embed_truncation: TruncationStrategy::Head,
+            embed_input_chars: 2000,
+            aggregation: Aggregation::WholeDoc,
+            include_path_in_embedding: false,
+            index_path_tokens: false,
+            path_token_boost: 0.1,
+            flatten_structured: true,
+            preprocessors: Preprocessors::default(),
+            language: None,
+            preview_chars: 1000,
+            preview_chars_by_extension: HashMap::new(),
+            preview_strategy: PreviewStrategy::Head,
+            clean_preview: true,
+            metric: Metric::Cosine,
+            tie_breaker: TieBreaker::Path,
+            retry: RetryConfig::default(),
+            server_side_scoring: true,
+            flush_after_indexing: true,
+            embedding_precision: EmbeddingPrecision::F32,
+            index_extensionless_text: false,
+            min_content_chars: 10,
+            progress: None,
+            on_duplicate: OnDuplicate::Update,
+            index_error_policy: IndexErrorPolicy::Continue,
+            storage: StorageConfig::default(),
+        }
+    }
+}
+
+impl FileEmbeddingConfig {
+    /// Checks the settings that have no sensible meaning, such as a batch size of 0, failing
+    /// with [`FileEmbeddingError::InvalidConfig`].
+    ///
+    /// Opening a system calls this before touching the database.
+    pub fn validate(&self) -> Result<(), FileEmbeddingError> {
+        let invalid = |message: String| Err(FileEmbeddingError::InvalidConfig(message));
+        if self.embed_batch_size == Some(0) {
+            return invalid(String::from("embed_batch_size must be at least 1"));
+        }
+        if self.extract_concurrency == 0 {
+            return invalid(String::from("extract_concurrency must be at least 1"));
+        }
+        if self.pipeline_batch_size == 0 {
+            return invalid(String::from("pipeline_batch_size must be at least 1"));
+        }
+        if self.embed_input_chars == 0 {
+            return invalid(String::from("embed_input_chars must be at least 1"));
+        }
+        if let Some(language) = &self.language {
+            if stopwords::for_language(language).is_none() {
+                return invalid(format!("no stopword list for language: {}", language));
+            }
+        }
+        for (i, member) in self.ensemble.iter().enumerate() {
+            if self.ensemble[..i].iter().any(|other| other.name == member.name) {
+                return invalid(format!("duplicate ensemble model name: {}", member.name));
+            }
+            if !member.weight.is_finite() || member.weight < 0.0 {
+                return invalid(
+                    format!("invalid weight {} for ensemble model {}", member.weight, member.name)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Preview length for files with `extension`, falling back to `preview_chars`.
+    pub fn preview_chars_for(&self, extension: Option<&str>) -> usize {
+        extension
+            .and_then(|ext| self.preview_chars_by_extension.get(ext))
+            .copied()
+            .unwrap_or(self.preview_chars)
+    }
+}
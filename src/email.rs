@@ -0,0 +1,169 @@
+//! Extraction of email messages from `.eml` files and `.mbox` archives.
+//!
+//! The message body is what gets embedded; the main headers are stored alongside it in
+//! [`FileRecord::email`](crate::FileRecord::email) and can be filtered on with
+//! [`SearchFilter::sender`](crate::SearchFilter::sender) and
+//! [`SearchFilter::subject`](crate::SearchFilter::subject).
+
+use mailparse::{ MailHeaderMap, ParsedMail };
+use serde::{ Deserialize, Serialize };
+
+/// Headers stored with an indexed email, as written in the message.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+pub struct EmailHeaders {
+    pub subject: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub date: Option<String>,
+}
+
+/// A parsed message: its headers and the text of its body.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmailMessage {
+    pub headers: EmailHeaders,
+    pub body: String,
+}
+
+/// Parses one RFC 5322 message.
+///
+/// The body is the first `text/plain` part, or the first `text/html` part with its tags
+/// stripped when there is no plain text. Attachments are ignored.
+pub fn parse_message(raw: &[u8]) -> Result<EmailMessage, String> {
+    let mail = mailparse::parse_mail(raw).map_err(|e| e.to_string())?;
+    let header = |name: &str| mail.headers.get_first_value(name);
+    let headers = EmailHeaders {
+        subject: header("Subject"),
+        from: header("From"),
+        to: header("To"),
+        date: header("Date"),
+    };
+
+    let body = match find_part(&mail, "text/plain") {
+        Some(part) => part.get_body().map_err(|e| e.to_string())?,
+        None =>
+            match find_part(&mail, "text/html") {
+                Some(part) => strip_tags(&part.get_body().map_err(|e| e.to_string())?),
+                None => String::new(),
+            }
+    };
+
+    Ok(EmailMessage { headers, body })
+}
+
+/// Depth-first search for the first non-multipart part of type `mimetype`.
+fn find_part<'a>(mail: &'a ParsedMail<'a>, mimetype: &str) -> Option<&'a ParsedMail<'a>> {
+    if mail.subparts.is_empty() {
+        return (mail.ctype.mimetype == mimetype).then_some(mail);
+    }
+    mail.subparts.iter().find_map(|part| find_part(part, mimetype))
+}
+
+/// Drops everything between `<` and `>`, leaving the text of simple HTML mail.
+fn strip_tags(html: &str) -> String {
+    let mut text = String::with_capacity(html.len());
+    let mut in_tag = false;
+    for c in html.chars() {
+        match c {
+            '<' => {
+                in_tag = true;
+            }
+            '>' if in_tag => {
+                in_tag = false;
+                text.push(' ');
+            }
+            _ if !in_tag => text.push(c),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Splits an mbox archive into its raw messages.
+///
+/// A message starts after each `From ` separator line at the start of the archive or after
+/// a blank line; mbox writers quote body lines like that as `>From `, which is left as is.
+pub fn split_mbox(raw: &[u8]) -> Vec<&[u8]> {
+    let mut messages = Vec::new();
+    let mut start: Option<usize> = None;
+    let mut offset = 0;
+    let mut previous_blank = true;
+
+    for line in raw.split_inclusive(|&b| b == b'\n') {
+        if previous_blank && line.starts_with(b"From ") {
+            if let Some(start) = start {
+                messages.push(&raw[start..offset]);
+            }
+            start = Some(offset + line.len());
+        }
+        previous_blank = line.iter().all(u8::is_ascii_whitespace);
+        offset += line.len();
+    }
+    if let Some(start) = start {
+        messages.push(&raw[start..]);
+    }
+
+    messages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: &str =
+        "From: Alice <alice@example.com>\r\n\
+         To: bob@example.com\r\n\
+         Subject: Quarterly budget\r\n\
+         Date: Mon, 3 Jun 2024 10:00:00 +0000\r\n\
+         \r\n\
+         The budget review moves to Friday.\r\n";
+
+    #[test]
+    fn test_parse_plain_message() -> Result<(), String> {
+        let message = parse_message(PLAIN.as_bytes())?;
+        assert_eq!(message.headers, EmailHeaders {
+            subject: Some("Quarterly budget".to_string()),
+            from: Some("Alice <alice@example.com>".to_string()),
+            to: Some("bob@example.com".to_string()),
+            date: Some("Mon, 3 Jun 2024 10:00:00 +0000".to_string()),
+        });
+        assert_eq!(message.body.trim(), "The budget review moves to Friday.");
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_multipart_prefers_plain_text() -> Result<(), String> {
+        let raw =
+            "Subject: Hello\r\n\
+             Content-Type: multipart/alternative; boundary=\"b\"\r\n\
+             \r\n\
+             --b\r\n\
+             Content-Type: text/html\r\n\
+             \r\n\
+             <p>Hello <b>HTML</b></p>\r\n\
+             --b\r\n\
+             Content-Type: text/plain\r\n\
+             \r\n\
+             Hello plain\r\n\
+             --b--\r\n";
+        assert_eq!(parse_message(raw.as_bytes())?.body.trim(), "Hello plain");
+
+        let html_only = raw.replace("Content-Type: text/plain", "Content-Type: image/png");
+        assert_eq!(parse_message(html_only.as_bytes())?.body, "Hello HTML");
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_mbox() {
+        let mbox = format!(
+            "From alice@example.com Mon Jun  3 10:00:00 2024\n{}\n\
+             From bob@example.com Tue Jun  4 10:00:00 2024\nSubject: Second\n\n>From here on.\n",
+            PLAIN
+        );
+        let messages = split_mbox(mbox.as_bytes());
+        assert_eq!(messages.len(), 2);
+        assert!(messages[0].starts_with(b"From: Alice"));
+        assert_eq!(messages[1], b"Subject: Second\n\n>From here on.\n");
+
+        assert!(split_mbox(b"").is_empty());
+    }
+}
@@ -0,0 +1,150 @@
+use anyhow::Result;
+use fastembed::{ EmbeddingModel, InitOptions, TextEmbedding };
+
+use crate::error::FileEmbeddingError;
+use crate::similarity;
+
+/// Turns text into embedding vectors.
+///
+/// [`FastEmbedder`] is what [`FileEmbeddingSystem::with_config`](crate::FileEmbeddingSystem::with_config)
+/// uses; other implementations can be passed to
+/// [`FileEmbeddingSystem::with_embedder`](crate::FileEmbeddingSystem::with_embedder).
+pub trait Embedder: Send + Sync {
+    /// Embeds each text, returning one vector per text in the same order.
+    fn embed(&self, texts: &[&str], batch_size: Option<usize>) -> Result<Vec<Vec<f32>>>;
+
+    /// Length of the vectors [`Embedder::embed`] returns.
+    fn dimension(&self) -> usize;
+}
+
+/// Calls [`Embedder::embed`], failing if it didn't return exactly one vector per text, so
+/// callers can index into the result.
+pub(crate) fn embed_checked(
+    embedder: &dyn Embedder,
+    texts: &[&str],
+    batch_size: Option<usize>
+) -> Result<Vec<Vec<f32>>, FileEmbeddingError> {
+    let embeddings = embedder
+        .embed(texts, batch_size)
+        .map_err(|e| FileEmbeddingError::Embedding(e.to_string()))?;
+    if embeddings.len() != texts.len() {
+        return Err(
+            FileEmbeddingError::Embedding(
+                format!("embedder returned {} vectors for {} inputs", embeddings.len(), texts.len())
+            )
+        );
+    }
+    Ok(embeddings)
+}
+
+/// A local fastembed model.
+pub struct FastEmbedder {
+    model: TextEmbedding,
+    dimension: usize,
+}
+
+impl FastEmbedder {
+    /// Loads `model`, downloading it on first use.
+    pub fn new(model: EmbeddingModel) -> Result<Self> {
+        let dimension = TextEmbedding::get_model_info(&model)?.dim;
+        let model = TextEmbedding::try_new(
+            InitOptions::new(model).with_show_download_progress(true)
+        )?;
+        Ok(Self { model, dimension })
+    }
+}
+
+impl Embedder for FastEmbedder {
+    fn embed(&self, texts: &[&str], batch_size: Option<usize>) -> Result<Vec<Vec<f32>>> {
+        self.model.embed(texts.to_vec(), batch_size)
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+}
+
+/// A deterministic bag-of-words embedder for tests and benchmarks that need no model.
+///
+/// Each word is hashed into one of `dimension` buckets and the counts are normalized, so
+/// texts sharing words score higher than unrelated ones, with no semantics beyond that.
+#[derive(Debug, Clone)]
+pub struct MockEmbedder {
+    dimension: usize,
+    output_dimension: usize,
+}
+
+impl MockEmbedder {
+    pub fn new(dimension: usize) -> Self {
+        Self {
+            dimension,
+            output_dimension: dimension,
+        }
+    }
+
+    /// Keeps reporting the original dimension but returns vectors of `output_dimension`,
+    /// like a misconfigured model would.
+    pub fn with_output_dimension(mut self, output_dimension: usize) -> Self {
+        self.output_dimension = output_dimension;
+        self
+    }
+
+    fn embed_one(&self, text: &str) -> Vec<f32> {
+        let mut counts = vec![0.0; self.output_dimension];
+        if self.output_dimension == 0 {
+            return counts;
+        }
+        for word in text.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()) {
+            // FNV-1a, so vectors are stable across runs and platforms
+            let hash = word
+                .to_lowercase()
+                .bytes()
+                .fold(0xcbf29ce484222325u64, |hash, byte| {
+                    (hash ^ (byte as u64)).wrapping_mul(0x100000001b3)
+                });
+            counts[(hash % (self.output_dimension as u64)) as usize] += 1.0;
+        }
+        similarity::normalize(&counts)
+    }
+}
+
+impl Embedder for MockEmbedder {
+    fn embed(&self, texts: &[&str], _batch_size: Option<usize>) -> Result<Vec<Vec<f32>>> {
+        Ok(
+            texts
+                .iter()
+                .map(|text| self.embed_one(text))
+                .collect()
+        )
+    }
+
+    fn dimension(&self) -> usize {
+        self.dimension
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::similarity::cosine_similarity;
+
+    #[test]
+    fn test_mock_embedder() -> Result<()> {
+        let embedder = MockEmbedder::new(64);
+        let vectors = embedder.embed(
+            &["rust programming", "Rust programming language", "tomato garden"],
+            None
+        )?;
+
+        assert!(vectors.iter().all(|v| v.len() == 64));
+        assert_eq!(embedder.embed(&["rust programming"], None)?[0], vectors[0]);
+        assert!(
+            cosine_similarity(&vectors[0], &vectors[1]) > cosine_similarity(&vectors[0], &vectors[2])
+        );
+
+        let misconfigured = MockEmbedder::new(64).with_output_dimension(32);
+        assert_eq!(misconfigured.dimension(), 64);
+        assert_eq!(misconfigured.embed(&["rust"], None)?[0].len(), 32);
+        Ok(())
+    }
+}
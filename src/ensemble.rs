@@ -0,0 +1,61 @@
+use fastembed::EmbeddingModel;
+
+/// An additional embedding model whose scores are fused with the primary model's.
+///
+/// Each model's vectors are stored under `name` in [`FileRecord::embeddings`](crate::FileRecord)
+/// and only ever compared with query vectors from the same model, so models with different
+/// dimensions can be mixed freely.
+#[derive(Debug, Clone)]
+pub struct EnsembleModel {
+    /// Key the model's vectors are stored under, e.g. `"code"`.
+    pub name: String,
+    pub model: EmbeddingModel,
+    /// Relative weight of this model's score in the fused result.
+    pub weight: f32,
+}
+
+/// Weighted mean of `(weight, score)` pairs, skipping models with no score for a record.
+///
+/// Records indexed before a model was added have no vector for it, so their fused score
+/// is renormalized over the models they do have.
+pub fn fuse_scores(scores: impl IntoIterator<Item = (f32, Option<f32>)>) -> f32 {
+    let (weighted, total_weight) = scores
+        .into_iter()
+        .filter_map(|(weight, score)| score.map(|score| (weight, score)))
+        .fold((0.0, 0.0), |(sum, total), (weight, score)| (sum + weight * score, total + weight));
+
+    if total_weight > 0.0 {
+        weighted / total_weight
+    } else {
+        0.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fused_ranking_differs_from_each_model() {
+        // Model A prefers doc1, model B prefers doc2, but both like doc3
+        let docs = [("doc1", 1.0, 0.0), ("doc2", 0.0, 1.0), ("doc3", 0.8, 0.8)];
+
+        let top = |score: &dyn Fn(f32, f32) -> f32| {
+            docs.iter()
+                .max_by(|a, b| score(a.1, a.2).total_cmp(&score(b.1, b.2)))
+                .map(|doc| doc.0)
+                .unwrap()
+        };
+
+        assert_eq!(top(&|a, _| a), "doc1");
+        assert_eq!(top(&|_, b| b), "doc2");
+        assert_eq!(top(&|a, b| fuse_scores([(1.0, Some(a)), (1.0, Some(b))])), "doc3");
+    }
+
+    #[test]
+    fn test_fuse_scores_skips_missing() {
+        assert_eq!(fuse_scores([(1.0, Some(0.5)), (3.0, None)]), 0.5);
+        assert_eq!(fuse_scores([(1.0, Some(0.0)), (3.0, Some(1.0))]), 0.75);
+        assert_eq!(fuse_scores([(1.0, None)]), 0.0);
+    }
+}
@@ -1,3 +1,4 @@
+use std::path::PathBuf;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -5,13 +6,59 @@ pub enum FileEmbeddingError {
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     #[error("Database error: {0}")]
-    Database(#[from] surrealdb::Error),
+    Database(Box<surrealdb::Error>),
     #[error("Embedding error: {0}")]
     Embedding(String),
     #[error("Unsupported file type: {0}")]
     UnsupportedFileType(String),
     #[error("PDF extraction error: {0}")]
     PdfExtraction(String),
+    #[error("Email parse error: {0}")]
+    EmailParse(String),
     #[error("WalkDir error: {0}")]
     WalkDir(#[from] walkdir::Error),
+    #[error("Watch error: {0}")]
+    Watch(#[from] notify::Error),
+    #[error("Invalid filter: {0}")]
+    InvalidFilter(String),
+    #[error("Invalid query: {0}")]
+    InvalidQuery(String),
+    #[error("Invalid config: {0}")]
+    InvalidConfig(String),
+    #[error("Path is not valid UTF-8: {}", .0.display())]
+    NonUtf8Path(PathBuf),
+    #[error("{0} is already indexed")]
+    DuplicatePath(String),
+    #[error("Embedding for {path} has {actual} dimensions, but the index expects {expected}")]
+    DimensionMismatch {
+        path: String,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("{0} has too little content to index")]
+    ContentTooShort(String),
+    #[error("Invalid embedding: {0}")]
+    InvalidEmbedding(String),
+    #[error("Dimension reduction error: {0}")]
+    Reduction(String),
+    #[error("Index is open read-only")]
+    ReadOnly,
+    #[error("No index at {}; create one with `FileEmbeddingSystem::initialize`", .0.display())]
+    SchemaMissing(PathBuf),
+    #[error(
+        "Database at {} is corrupt or unreadable ({source}). Restore it from a backup, delete \
+         the directory to start over, or set `storage.reset_if_corrupt` to move it aside \
+         automatically",
+        path.display()
+    )]
+    DatabaseCorrupt {
+        path: PathBuf,
+        source: Box<surrealdb::Error>,
+    },
+}
+
+impl From<surrealdb::Error> for FileEmbeddingError {
+    fn from(error: surrealdb::Error) -> Self {
+        FileEmbeddingError::Database(Box::new(error))
+    }
 }
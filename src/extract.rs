@@ -0,0 +1,207 @@
+//! Reading file content for embedding.
+
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{ self, BufReader, Read };
+use std::path::Path;
+
+/// Byte order mark some Windows editors write at the start of UTF-8 files.
+const BOM: char = '\u{FEFF}';
+
+/// Reads a UTF-8 text file, stopping after `max_chars` characters if given.
+///
+/// At most `4 * max_chars` bytes are read (the longest UTF-8 encoding), so a multi-GB log
+/// costs no more memory than a small one. Without a limit this is `fs::read_to_string`.
+/// A leading byte order mark is dropped either way.
+pub fn read_text(path: &Path, max_chars: Option<usize>) -> io::Result<String> {
+    let Some(max_chars) = max_chars else {
+        let mut text = std::fs::read_to_string(path)?;
+        if text.starts_with(BOM) {
+            text.drain(..BOM.len_utf8());
+        }
+        return Ok(text);
+    };
+
+    let mut bytes = Vec::new();
+    BufReader::new(File::open(path)?)
+        .take(max_chars.saturating_mul(4) as u64)
+        .read_to_end(&mut bytes)?;
+
+    let valid = match std::str::from_utf8(&bytes) {
+        Ok(text) => text,
+        // The byte limit can cut a character in half; anything else is really invalid
+        Err(e) if e.error_len().is_none() => {
+            std::str::from_utf8(&bytes[..e.valid_up_to()]).expect("prefix is valid UTF-8")
+        }
+        Err(e) => {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, e));
+        }
+    };
+
+    Ok(truncate_chars(strip_bom(valid), max_chars).to_string())
+}
+
+/// [`read_text`] on the blocking thread pool, so reads can overlap other work.
+pub async fn read_text_blocking(path: &Path, max_chars: Option<usize>) -> io::Result<String> {
+    let path = path.to_path_buf();
+    tokio::task
+        ::spawn_blocking(move || read_text(&path, max_chars)).await
+        .map_err(io::Error::other)?
+}
+
+/// Runs `extract` on a blocking thread, turning a panic into `Err` with its message.
+///
+/// Extractors for complex formats such as `pdf_extract` can panic on malformed input; this
+/// keeps one bad file from taking down a whole indexing run.
+pub async fn catch_panics<T, F>(extract: F) -> Result<T, String>
+    where T: Send + 'static, F: FnOnce() -> T + Send + 'static
+{
+    tokio::task::spawn_blocking(extract).await.map_err(|e| {
+        if !e.is_panic() {
+            return e.to_string();
+        }
+        let payload = e.into_panic();
+        let message = payload
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| payload.downcast_ref::<String>().cloned())
+            .unwrap_or_default();
+        format!("panic during extraction: {}", message)
+    })
+}
+
+/// `text` without a leading byte order mark.
+pub fn strip_bom(text: &str) -> &str {
+    text.strip_prefix(BOM).unwrap_or(text)
+}
+
+/// The first `max_chars` characters of `text`.
+pub fn truncate_chars(text: &str, max_chars: usize) -> &str {
+    match text.char_indices().nth(max_chars) {
+        Some((end, _)) => &text[..end],
+        None => text,
+    }
+}
+
+/// The last `max_chars` characters of `text`.
+pub fn last_chars(text: &str, max_chars: usize) -> &str {
+    if max_chars == 0 {
+        return &text[text.len()..];
+    }
+    match text.char_indices().nth_back(max_chars - 1) {
+        Some((start, _)) => &text[start..],
+        None => text,
+    }
+}
+
+/// Which part of a document too long for the model is embedded.
+///
+/// Only the single embedding of a whole document is affected; `BestMatch` chunk
+/// embeddings always cover every chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TruncationStrategy {
+    /// Pass the text through whole and let the model's tokenizer keep the start.
+    #[default]
+    Head,
+    /// Keep the end, for documents whose conclusion or summary comes last.
+    Tail,
+    /// Keep the start and the end, half the budget each, and drop the middle.
+    Middle,
+}
+
+impl TruncationStrategy {
+    /// The part of `text` to embed when the model holds about `max_chars` characters.
+    pub fn apply<'a>(&self, text: &'a str, max_chars: usize) -> Cow<'a, str> {
+        match self {
+            TruncationStrategy::Head => Cow::Borrowed(text),
+            TruncationStrategy::Tail => Cow::Borrowed(last_chars(text, max_chars)),
+            TruncationStrategy::Middle => {
+                if text.chars().count() <= max_chars {
+                    return Cow::Borrowed(text);
+                }
+                let head = truncate_chars(text, max_chars / 2);
+                let tail = last_chars(text, max_chars - max_chars / 2);
+                Cow::Owned(format!("{}\n\n{}", head, tail))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_read_text_stops_early() -> io::Result<()> {
+        let mut file = NamedTempFile::new()?;
+        let line = "héllo wörld\n";
+        for _ in 0..100_000 {
+            file.write_all(line.as_bytes())?;
+        }
+        // Invalid UTF-8 far past the limit: reading it would fail, so it must not be read
+        file.write_all(&[0xff, 0xfe, 0xfd])?;
+        file.flush()?;
+
+        let text = read_text(file.path(), Some(30))?;
+        assert_eq!(text.chars().count(), 30);
+        assert!(text.starts_with("héllo wörld\nhéllo"));
+
+        assert!(read_text(file.path(), None).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_read_text_strips_bom() -> io::Result<()> {
+        let mut file = NamedTempFile::new()?;
+        file.write_all("\u{FEFF}Hello, BOM".as_bytes())?;
+        file.flush()?;
+
+        assert_eq!(read_text(file.path(), None)?, "Hello, BOM");
+        assert_eq!(read_text(file.path(), Some(5))?, "Hello");
+        assert_eq!(strip_bom("no BOM \u{FEFF}"), "no BOM \u{FEFF}");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_catch_panics() {
+        assert_eq!(catch_panics(|| 42).await, Ok(42));
+
+        let result: Result<(), String> = catch_panics(|| panic!("bad xref table")).await;
+        assert_eq!(result, Err("panic during extraction: bad xref table".to_string()));
+    }
+
+    #[test]
+    fn test_truncate_chars() {
+        assert_eq!(truncate_chars("héllo", 2), "hé");
+        assert_eq!(truncate_chars("héllo", 10), "héllo");
+        assert_eq!(truncate_chars("", 3), "");
+    }
+
+    #[test]
+    fn test_last_chars() {
+        assert_eq!(last_chars("héllo", 4), "éllo");
+        assert_eq!(last_chars("héllo", 10), "héllo");
+        assert_eq!(last_chars("héllo", 0), "");
+    }
+
+    #[test]
+    fn test_truncation_strategies() {
+        let text = format!("Introduction. {} Conclusion: ship it.", "filler ".repeat(1000));
+
+        let head = TruncationStrategy::Head.apply(&text, 40);
+        assert_eq!(head, text.as_str(), "Head should leave truncation to the tokenizer");
+
+        let tail = TruncationStrategy::Tail.apply(&text, 20);
+        assert_eq!(tail, "Conclusion: ship it.");
+        assert_eq!(tail.chars().count(), 20);
+
+        let middle = TruncationStrategy::Middle.apply(&text, 40);
+        assert_eq!(middle, "Introduction. filler\n\nConclusion: ship it.");
+        assert!(!middle.contains("filler filler"), "Middle should be dropped: {}", middle);
+
+        assert_eq!(TruncationStrategy::Middle.apply("short", 40), "short");
+        assert_eq!(TruncationStrategy::Tail.apply("short", 40), "short");
+    }
+}
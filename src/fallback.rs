@@ -0,0 +1,112 @@
+use std::collections::HashMap;
+
+use crate::models::SearchResult;
+
+/// Maximum edit distance for a word to count as a near miss of a query term.
+const MAX_EDIT_DISTANCE: usize = 2;
+/// Maximum number of suggested terms returned.
+const MAX_SUGGESTIONS: usize = 5;
+
+/// Results from [`FileEmbeddingSystem::search_with_fallback`](crate::FileEmbeddingSystem::search_with_fallback).
+#[derive(Debug, Clone)]
+pub struct FallbackResults {
+    /// Ranked results; flagged `low_confidence` when they came from the fallback.
+    pub results: Vec<SearchResult>,
+    /// Indexed words close to query terms that don't appear in the corpus.
+    pub suggestions: Vec<String>,
+    /// Whether nothing cleared the threshold and the fallback was used.
+    pub used_fallback: bool,
+}
+
+/// Lowercased alphanumeric words of at least three characters.
+pub fn words(text: &str) -> impl Iterator<Item = String> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() >= 3)
+        .map(|word| word.to_lowercase())
+}
+
+/// Levenshtein distance between two strings, counted in characters.
+pub fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Whether any word in a file name is a near miss of (or contains) a query term.
+pub fn name_matches(name: &str, terms: &[String]) -> bool {
+    words(name).any(|word| {
+        terms.iter().any(|term| {
+            word.contains(term.as_str()) || edit_distance(&word, term) <= MAX_EDIT_DISTANCE
+        })
+    })
+}
+
+/// Suggests indexed words close to query terms that don't appear in the corpus.
+///
+/// Candidates are ranked by edit distance, then by how often they occur.
+pub fn suggest_terms<'a>(terms: &[String], texts: impl Iterator<Item = &'a str>) -> Vec<String> {
+    let mut vocabulary: HashMap<String, usize> = HashMap::new();
+    for text in texts {
+        for word in words(text) {
+            *vocabulary.entry(word).or_default() += 1;
+        }
+    }
+
+    let mut candidates: Vec<(usize, usize, String)> = Vec::new();
+    for term in terms {
+        if vocabulary.contains_key(term) {
+            continue;
+        }
+        for (word, count) in &vocabulary {
+            let distance = edit_distance(term, word);
+            if distance <= MAX_EDIT_DISTANCE {
+                candidates.push((distance, *count, word.clone()));
+            }
+        }
+    }
+
+    candidates.sort_by(|a, b| a.0.cmp(&b.0).then(b.1.cmp(&a.1)).then(a.2.cmp(&b.2)));
+    let mut suggestions: Vec<String> = Vec::new();
+    for (_, _, word) in candidates {
+        if !suggestions.contains(&word) {
+            suggestions.push(word);
+        }
+    }
+    suggestions.truncate(MAX_SUGGESTIONS);
+    suggestions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        assert_eq!(edit_distance("rust", "rust"), 0);
+        assert_eq!(edit_distance("progrmming", "programming"), 1);
+        assert_eq!(edit_distance("rsut", "rust"), 2);
+        assert_eq!(edit_distance("", "abc"), 3);
+    }
+
+    #[test]
+    fn test_near_miss_suggestions() {
+        let texts = ["Rust programming language guide", "Programming in Rust is fun"];
+        let terms: Vec<String> = words("progrmming rsut").collect();
+
+        let suggestions = suggest_terms(&terms, texts.into_iter());
+        assert_eq!(suggestions, vec!["programming", "rust"]);
+
+        assert!(name_matches("tax_return_2023.pdf", &["retrun".to_string()]));
+        assert!(!name_matches("holiday.jpg", &["retrun".to_string()]));
+    }
+}
@@ -0,0 +1,346 @@
+use surrealdb::method::Query;
+use surrealdb::Connection;
+
+use crate::error::FileEmbeddingError;
+
+/// Restricts an operation to the indexed files matching every set field.
+///
+/// Unset fields (`None` or an empty `extensions` list) match all records.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SearchFilter {
+    /// File extensions to match, without the leading dot.
+    pub extensions: Vec<String>,
+    /// Only match files with exactly this name, e.g. `README.md`.
+    pub name: Option<String>,
+    /// Minimum file size in bytes (inclusive).
+    pub min_size: Option<u64>,
+    /// Maximum file size in bytes (inclusive).
+    pub max_size: Option<u64>,
+    /// Only match files whose stored path starts with this, e.g. `/home/me/notes/`.
+    ///
+    /// This is a plain string comparison, so include the trailing separator to avoid also
+    /// matching `/home/me/notes-old/`.
+    pub path_prefix: Option<String>,
+    /// Only match files indexed into this collection.
+    pub collection: Option<String>,
+    /// Only match emails whose `From` header contains this, case-insensitively.
+    pub sender: Option<String>,
+    /// Only match emails whose subject contains this, case-insensitively.
+    pub subject: Option<String>,
+    /// Only match JSON, YAML and TOML documents with this top-level key.
+    pub top_level_key: Option<String>,
+    /// Only match files with this word, ignoring case, in one of their parent directories'
+    /// names; requires `index_path_tokens`.
+    pub path_token: Option<String>,
+    /// Match `name` and `extensions` exactly instead of ignoring case.
+    pub case_sensitive: bool,
+}
+
+impl SearchFilter {
+    /// Starts building a filter fluently:
+    ///
+    /// ```
+    /// # use file_embeddings::SearchFilter;
+    /// let filter = SearchFilter::new()
+    ///     .extensions(["rs", "md"])
+    ///     .min_size(1024)
+    ///     .path_prefix("/notes/")
+    ///     .build()?;
+    /// # Ok::<(), file_embeddings::FileEmbeddingError>(())
+    /// ```
+    // Reads better at call sites than `SearchFilterBuilder::default()`
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> SearchFilterBuilder {
+        SearchFilterBuilder::default()
+    }
+
+    /// Returns true when the filter has no conditions and would match every record.
+    pub fn is_empty(&self) -> bool {
+        self.extensions.is_empty() &&
+            self.name.is_none() &&
+            self.min_size.is_none() &&
+            self.max_size.is_none() &&
+            self.path_prefix.is_none() &&
+            self.collection.is_none() &&
+            self.sender.is_none() &&
+            self.subject.is_none() &&
+            self.top_level_key.is_none() &&
+            self.path_token.is_none()
+    }
+
+    fn normalized_extensions(&self) -> Vec<String> {
+        self.raw_extensions()
+            .iter()
+            .map(|ext| ext.to_lowercase())
+            .collect()
+    }
+
+    fn raw_extensions(&self) -> Vec<String> {
+        self.extensions
+            .iter()
+            .map(|ext| ext.trim_start_matches('.').to_string())
+            .collect()
+    }
+
+    /// Builds the SurrealQL condition for this filter, or `None` if it is empty.
+    ///
+    /// The condition references parameters that must be supplied with [`SearchFilter::bind`].
+    pub(crate) fn where_clause(&self) -> Option<String> {
+        let mut conditions = Vec::new();
+        if !self.extensions.is_empty() {
+            // Like `name_lower` below, the indexed lowercase field narrows the exact match
+            conditions.push(
+                if self.case_sensitive {
+                    "extension IN $filter_extensions AND raw_extension IN $filter_raw_extensions"
+                } else {
+                    "extension IN $filter_extensions"
+                }
+            );
+        }
+        if self.name.is_some() {
+            // `name_lower` is indexed; the exact comparison only narrows what it finds
+            conditions.push(
+                if self.case_sensitive {
+                    "name_lower = $filter_name_lower AND name = $filter_name"
+                } else {
+                    "name_lower = $filter_name_lower"
+                }
+            );
+        }
+        if self.min_size.is_some() {
+            conditions.push("size_bytes >= $filter_min_size");
+        }
+        if self.max_size.is_some() {
+            conditions.push("size_bytes <= $filter_max_size");
+        }
+        if self.path_prefix.is_some() {
+            conditions.push("string::startsWith(path, $filter_path_prefix)");
+        }
+        if self.collection.is_some() {
+            conditions.push("collection = $filter_collection");
+        }
+        if self.sender.is_some() {
+            conditions.push(
+                "string::contains(string::lowercase(email.from ?? ''), $filter_sender)"
+            );
+        }
+        if self.subject.is_some() {
+            conditions.push(
+                "string::contains(string::lowercase(email.subject ?? ''), $filter_subject)"
+            );
+        }
+        if self.top_level_key.is_some() {
+            conditions.push("$filter_top_level_key IN top_level_keys");
+        }
+        if self.path_token.is_some() {
+            conditions.push("$filter_path_token IN path_tokens");
+        }
+
+        if conditions.is_empty() {
+            None
+        } else {
+            Some(conditions.join(" AND "))
+        }
+    }
+
+    /// Binds the parameters referenced by [`SearchFilter::where_clause`].
+    pub(crate) fn bind<'r, C: Connection>(&self, query: Query<'r, C>) -> Query<'r, C> {
+        query
+            .bind(("filter_extensions", self.normalized_extensions()))
+            .bind(("filter_raw_extensions", self.raw_extensions()))
+            .bind(("filter_name", self.name.clone()))
+            .bind(("filter_name_lower", self.name.as_deref().map(str::to_lowercase)))
+            .bind(("filter_min_size", self.min_size))
+            .bind(("filter_max_size", self.max_size))
+            .bind(("filter_path_prefix", self.path_prefix.clone()))
+            .bind(("filter_collection", self.collection.clone()))
+            .bind(("filter_sender", self.sender.as_deref().map(str::to_lowercase)))
+            .bind(("filter_subject", self.subject.as_deref().map(str::to_lowercase)))
+            .bind(("filter_top_level_key", self.top_level_key.clone()))
+            .bind(("filter_path_token", self.path_token.as_deref().map(str::to_lowercase)))
+    }
+}
+
+/// Fluent constructor for [`SearchFilter`], from [`SearchFilter::new`].
+#[derive(Debug, Clone, Default)]
+pub struct SearchFilterBuilder {
+    filter: SearchFilter,
+}
+
+impl SearchFilterBuilder {
+    /// Adds extensions to match; any of them matches.
+    pub fn extensions<I, S>(mut self, extensions: I) -> Self
+        where I: IntoIterator<Item = S>, S: Into<String>
+    {
+        self.filter.extensions.extend(extensions.into_iter().map(Into::into));
+        self
+    }
+
+    pub fn name(mut self, name: &str) -> Self {
+        self.filter.name = Some(name.to_string());
+        self
+    }
+
+    pub fn case_sensitive(mut self, case_sensitive: bool) -> Self {
+        self.filter.case_sensitive = case_sensitive;
+        self
+    }
+
+    pub fn min_size(mut self, bytes: u64) -> Self {
+        self.filter.min_size = Some(bytes);
+        self
+    }
+
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.filter.max_size = Some(bytes);
+        self
+    }
+
+    pub fn path_prefix(mut self, prefix: &str) -> Self {
+        self.filter.path_prefix = Some(prefix.to_string());
+        self
+    }
+
+    pub fn collection(mut self, collection: &str) -> Self {
+        self.filter.collection = Some(collection.to_string());
+        self
+    }
+
+    pub fn sender(mut self, sender: &str) -> Self {
+        self.filter.sender = Some(sender.to_string());
+        self
+    }
+
+    pub fn subject(mut self, subject: &str) -> Self {
+        self.filter.subject = Some(subject.to_string());
+        self
+    }
+
+    pub fn top_level_key(mut self, key: &str) -> Self {
+        self.filter.top_level_key = Some(key.to_string());
+        self
+    }
+
+    pub fn path_token(mut self, token: &str) -> Self {
+        self.filter.path_token = Some(token.to_string());
+        self
+    }
+
+    /// Returns the filter, or [`FileEmbeddingError::InvalidFilter`] if it can never match:
+    /// a size range with `min_size > max_size`, or an empty extension.
+    pub fn build(self) -> Result<SearchFilter, FileEmbeddingError> {
+        let filter = self.filter;
+        if let (Some(min), Some(max)) = (filter.min_size, filter.max_size) {
+            if min > max {
+                return Err(
+                    FileEmbeddingError::InvalidFilter(
+                        format!("min_size {} is greater than max_size {}", min, max)
+                    )
+                );
+            }
+        }
+        if filter.normalized_extensions().iter().any(String::is_empty) {
+            return Err(FileEmbeddingError::InvalidFilter(String::from("empty extension")));
+        }
+        Ok(filter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_where_clause() {
+        assert_eq!(SearchFilter::default().where_clause(), None);
+
+        let filter = SearchFilter {
+            extensions: vec![".LOG".to_string()],
+            name: None,
+            min_size: Some(10 * 1024 * 1024),
+            max_size: None,
+            path_prefix: None,
+            collection: None,
+            sender: None,
+            subject: None,
+            top_level_key: None,
+            path_token: None,
+            case_sensitive: false,
+        };
+        assert_eq!(
+            filter.where_clause().as_deref(),
+            Some("extension IN $filter_extensions AND size_bytes >= $filter_min_size")
+        );
+        assert_eq!(filter.normalized_extensions(), vec!["log"]);
+
+        let filter = SearchFilter {
+            path_prefix: Some("/home/me/notes/".to_string()),
+            ..Default::default()
+        };
+        assert!(!filter.is_empty());
+        assert_eq!(
+            filter.where_clause().as_deref(),
+            Some("string::startsWith(path, $filter_path_prefix)")
+        );
+
+        let filter = SearchFilter {
+            collection: Some("work".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(filter.where_clause().as_deref(), Some("collection = $filter_collection"));
+    }
+
+    #[test]
+    fn test_case_sensitivity() {
+        let filter = SearchFilter::new().name("README.md").extensions(["MD"]);
+        let insensitive = filter.clone().build().unwrap();
+        assert_eq!(
+            insensitive.where_clause().as_deref(),
+            Some("extension IN $filter_extensions AND name_lower = $filter_name_lower")
+        );
+        assert_eq!(insensitive.normalized_extensions(), vec!["md"]);
+
+        let sensitive = filter.case_sensitive(true).build().unwrap();
+        assert_eq!(
+            sensitive.where_clause().as_deref(),
+            Some(
+                "extension IN $filter_extensions AND raw_extension IN $filter_raw_extensions AND \
+                 name_lower = $filter_name_lower AND name = $filter_name"
+            )
+        );
+        assert_eq!(sensitive.normalized_extensions(), vec!["md"]);
+        assert_eq!(sensitive.raw_extensions(), vec!["MD"]);
+        assert!(SearchFilter::new().case_sensitive(true).build().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_builder() -> Result<(), FileEmbeddingError> {
+        let built = SearchFilter::new()
+            .extensions(["rs", "md"])
+            .min_size(1024)
+            .max_size(4096)
+            .path_prefix("/notes/")
+            .collection("work")
+            .build()?;
+        let manual = SearchFilter {
+            extensions: vec!["rs".to_string(), "md".to_string()],
+            min_size: Some(1024),
+            max_size: Some(4096),
+            path_prefix: Some("/notes/".to_string()),
+            collection: Some("work".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(built, manual);
+        assert_eq!(SearchFilter::new().build()?, SearchFilter::default());
+
+        assert!(
+            matches!(
+                SearchFilter::new().min_size(10).max_size(5).build(),
+                Err(FileEmbeddingError::InvalidFilter(_))
+            )
+        );
+        assert!(SearchFilter::new().min_size(5).max_size(5).build().is_ok());
+        assert!(SearchFilter::new().extensions([".", "rs"]).build().is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,107 @@
+use crate::fallback;
+
+/// Markers wrapped around query terms found in a result's snippet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Highlight {
+    pub start: String,
+    pub end: String,
+}
+
+impl Default for Highlight {
+    /// Markdown bold: `**term**`.
+    fn default() -> Self {
+        Self::new("**", "**")
+    }
+}
+
+impl Highlight {
+    pub fn new(start: &str, end: &str) -> Self {
+        Self {
+            start: start.to_string(),
+            end: end.to_string(),
+        }
+    }
+
+    /// Bold yellow ANSI escapes, for terminal output.
+    pub fn ansi() -> Self {
+        Self::new("\x1b[1;33m", "\x1b[0m")
+    }
+
+    /// Wraps every word of `text` that matches a term of `query`, case-insensitively.
+    ///
+    /// Query terms are the words [`fallback::words`] extracts (three characters or more), so
+    /// stopword-sized words like "is" are never highlighted. Everything else in `text` is
+    /// copied through unchanged.
+    pub fn apply(&self, text: &str, query: &str) -> String {
+        self.apply_excluding(text, query, &[])
+    }
+
+    /// Like [`Highlight::apply`], but query terms in `stopwords` are left unmarked.
+    pub fn apply_excluding(&self, text: &str, query: &str, stopwords: &[&str]) -> String {
+        let terms: Vec<String> = fallback
+            ::words(query)
+            .filter(|term| !stopwords.contains(&term.as_str()))
+            .collect();
+        let mut out = String::with_capacity(text.len());
+        let mut word_start: Option<usize> = None;
+
+        let flush = |out: &mut String, word: &str| {
+            if terms.iter().any(|term| *term == word.to_lowercase()) {
+                out.push_str(&self.start);
+                out.push_str(word);
+                out.push_str(&self.end);
+            } else {
+                out.push_str(word);
+            }
+        };
+
+        for (i, c) in text.char_indices() {
+            match (c.is_alphanumeric(), word_start) {
+                (true, None) => {
+                    word_start = Some(i);
+                }
+                (false, Some(start)) => {
+                    flush(&mut out, &text[start..i]);
+                    word_start = None;
+                    out.push(c);
+                }
+                (false, None) => out.push(c),
+                (true, Some(_)) => {}
+            }
+        }
+        if let Some(start) = word_start {
+            flush(&mut out, &text[start..]);
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_highlight_terms() {
+        let text = "Rust is a systems language; rusty tools aren't RUST.";
+        assert_eq!(
+            Highlight::default().apply(text, "rust systems"),
+            "**Rust** is a **systems** language; rusty tools aren't **RUST**."
+        );
+        assert_eq!(
+            Highlight::new("<em>", "</em>").apply(text, "language"),
+            "Rust is a systems <em>language</em>; rusty tools aren't RUST."
+        );
+
+        assert_eq!(Highlight::default().apply(text, "python"), text);
+        assert_eq!(Highlight::default().apply(text, "is"), text, "Short terms are skipped");
+        assert_eq!(
+            Highlight::ansi().apply("über cafés", "cafés"),
+            "über \x1b[1;33mcafés\x1b[0m"
+        );
+        assert_eq!(
+            Highlight::default().apply_excluding(text, "the rust tools", &["the"]),
+            "**Rust** is a systems language; rusty **tools** aren't **RUST**."
+        );
+    }
+}
@@ -0,0 +1,5332 @@
+use anyhow::Result;
+use std::path::{ Path, PathBuf };
+use surrealdb::Surreal;
+use surrealdb::engine::local::RocksDb;
+use walkdir::WalkDir;
+use std::fs;
+pub mod aggregate;
+pub mod cluster;
+pub mod config;
+pub mod email;
+pub mod embedder;
+pub mod ensemble;
+pub mod error;
+pub mod extract;
+pub mod fallback;
+pub mod filter;
+pub mod highlight;
+pub mod models;
+pub mod paths;
+pub mod preprocess;
+pub mod preview;
+pub mod progress;
+pub mod quantize;
+pub mod reduce;
+pub mod query;
+pub mod retry;
+pub mod search;
+pub mod similarity;
+pub mod sniff;
+pub mod stopwords;
+pub mod storage;
+pub mod structured;
+pub mod verify;
+pub mod watch;
+pub use aggregate::Aggregation;
+pub use config::{ Capacity, FileEmbeddingConfig, IndexErrorPolicy, OnDuplicate };
+pub use models::{
+    FileRecord,
+    IndexOutcome,
+    IndexPlan,
+    IndexReport,
+    PendingReport,
+    PlannedFile,
+    SearchHit,
+    SearchResult,
+    SkipReason,
+};
+pub use email::EmailHeaders;
+pub use embedder::{ Embedder, FastEmbedder, MockEmbedder };
+pub use ensemble::EnsembleModel;
+pub use error::FileEmbeddingError;
+pub use extract::TruncationStrategy;
+pub use fallback::FallbackResults;
+pub use filter::{ SearchFilter, SearchFilterBuilder };
+pub use highlight::Highlight;
+pub use preprocess::Preprocessors;
+pub use preview::PreviewStrategy;
+pub use progress::{ IndexProgress, ProgressCallback };
+pub use quantize::EmbeddingPrecision;
+pub use reduce::Projection;
+pub use query::{ parse_query, ParsedQuery };
+pub use retry::RetryConfig;
+pub use search::{
+    group_by_extension,
+    round_score,
+    AutoThreshold,
+    Metric,
+    SearchOptions,
+    TieBreaker,
+};
+pub use storage::{ CompactionReport, StorageConfig };
+pub use verify::{ IndexIssue, RepairOptions, RepairReport, VerifyReport };
+pub use watch::WatchConfig;
+use embedder::embed_checked;
+use serde::Deserialize;
+use similarity::{ cosine_similarity, finite_or_zero };
+use std::collections::{ HashMap, HashSet };
+use futures::StreamExt;
+use std::future::Future;
+use std::ops::ControlFlow;
+use std::sync::{ Arc, PoisonError, RwLock };
+use surrealdb::opt::RecordId;
+
+/// Rejects embeddings with NaN or infinite components before they are stored.
+fn validate_embedding(path: &str, embedding: &[f32]) -> Result<(), FileEmbeddingError> {
+    match embedding.iter().position(|x| !x.is_finite()) {
+        Some(i) =>
+            Err(
+                FileEmbeddingError::InvalidEmbedding(
+                    format!("{} has non-finite value {} at dimension {}", path, embedding[i], i)
+                )
+            ),
+        None => Ok(()),
+    }
+}
+
+/// Rejects embeddings whose length differs from the model's, which would otherwise be stored
+/// and silently score 0 against every query.
+fn check_dimension(
+    path: &str,
+    expected: usize,
+    embedding: &[f32]
+) -> Result<(), FileEmbeddingError> {
+    if embedding.len() != expected {
+        return Err(FileEmbeddingError::DimensionMismatch {
+            path: path.to_string(),
+            expected,
+            actual: embedding.len(),
+        });
+    }
+    Ok(())
+}
+
+fn is_mbox(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("mbox"))
+}
+
+const SUPPORTED_TEXT_EXTENSIONS: &[&str] = &[
+    "txt",
+    "md",
+    "rs",
+    "py",
+    "js",
+    "json",
+    "yaml",
+    "yml",
+    "toml",
+    "css",
+    "html",
+    "htm",
+    "xml",
+    "csv",
+    "log",
+    "pdf",
+    "doc",
+    "docx",
+    "eml",
+];
+
+pub struct FileEmbeddingSystem {
+    db: Surreal<surrealdb::engine::local::Db>,
+    db_path: PathBuf,
+    embedder: Arc<dyn Embedder>,
+    ensemble: Vec<LoadedModel>,
+    config: FileEmbeddingConfig,
+    /// Learned by [`FileEmbeddingSystem::fit_reduction`] and applied to every primary
+    /// vector stored or searched with afterwards.
+    reduction: RwLock<Option<Arc<Projection>>>,
+    /// Set by [`FileEmbeddingSystem::set_capacity`].
+    capacity: RwLock<Capacity>,
+}
+
+/// Record in the `_meta` table holding the fitted [`Projection`].
+const REDUCTION_META_ID: (&str, &str) = ("_meta", "reduction");
+
+/// Most embeddings `fit_reduction` fits the projection on.
+const REDUCTION_SAMPLE_SIZE: usize = 2000;
+
+/// What the extraction stage of `index_directory` hands to the embedding stage.
+enum Extracted {
+    /// Already indexed, and `on_duplicate` is `Skip`.
+    Skipped,
+    /// An mbox archive, split and embedded message by message in the embedding stage.
+    Mbox,
+    Record(Box<FileRecord>, String),
+}
+
+/// A file's content as prepared for embedding.
+struct EmbedText<'a> {
+    /// Cleaned content, used for previews, sentences and preview chunks.
+    display: std::borrow::Cow<'a, str>,
+    /// What the whole-document vector is computed from.
+    input: String,
+}
+
+/// An [`EnsembleModel`] with its weights loaded.
+struct LoadedModel {
+    name: String,
+    model: FastEmbedder,
+    weight: f32,
+}
+
+/// A query embedded with the primary model and each ensemble model, in config order.
+struct QueryVectors {
+    text: String,
+    primary: Vec<f32>,
+    ensemble: Vec<Vec<f32>>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+struct Record {
+    #[allow(dead_code)]
+    id: RecordId,
+}
+
+impl FileEmbeddingSystem {
+    /// Creates a fresh, empty index at `db_path`, dropping any files indexed there before.
+    ///
+    /// The same as [`FileEmbeddingSystem::initialize`]; use [`FileEmbeddingSystem::open`]
+    /// to keep an existing index.
+    pub async fn new(db_path: &str) -> Result<Self> {
+        Self::with_config(db_path, FileEmbeddingConfig::default()).await
+    }
+
+    /// Creates the schema at `db_path` from scratch, dropping any files indexed there before.
+    pub async fn initialize(db_path: &str) -> Result<Self> {
+        Self::new(db_path).await
+    }
+
+    /// Opens the index created at `db_path` by [`FileEmbeddingSystem::initialize`], keeping
+    /// its records and schema as they are.
+    ///
+    /// Fails with [`FileEmbeddingError::SchemaMissing`] if no index was ever created there.
+    pub async fn open(db_path: &str) -> Result<Self> {
+        let mut config = FileEmbeddingConfig::default();
+        config.storage.open_existing = true;
+        Self::with_config(db_path, config).await
+    }
+
+    async fn connect(
+        db_path: &str,
+        config: &FileEmbeddingConfig
+    ) -> Result<Surreal<surrealdb::engine::local::Db>, surrealdb::Error> {
+        Surreal::new::<RocksDb>((db_path, config.storage.surreal_config())).await
+    }
+
+    /// Opens an existing index for searching only.
+    ///
+    /// The schema is left untouched and every method that writes returns
+    /// [`FileEmbeddingError::ReadOnly`].
+    pub async fn open_read_only(db_path: &str) -> Result<Self> {
+        let mut config = FileEmbeddingConfig::default();
+        config.storage.read_only = true;
+        Self::with_config(db_path, config).await
+    }
+
+    /// Names of the tables defined in the selected database.
+    async fn defined_tables(
+        db: &Surreal<surrealdb::engine::local::Db>
+    ) -> Result<Vec<String>, FileEmbeddingError> {
+        let info: Option<serde_json::Value> = db.query("INFO FOR DB").await?.take(0)?;
+        Ok(
+            info
+                .as_ref()
+                .and_then(|info| info.get("tables"))
+                .and_then(|tables| tables.as_object())
+                .map(|tables| tables.keys().cloned().collect())
+                .unwrap_or_default()
+        )
+    }
+
+    fn ensure_writable(&self) -> Result<(), FileEmbeddingError> {
+        if self.config.storage.read_only {
+            return Err(FileEmbeddingError::ReadOnly);
+        }
+        Ok(())
+    }
+
+    pub async fn with_config(db_path: &str, config: FileEmbeddingConfig) -> Result<Self> {
+        // Fail before spending time on loading the model
+        config.validate()?;
+        let embedder = FastEmbedder::new(config.embedding_model.clone())?;
+        Self::open_validated(db_path, config, Arc::new(embedder)).await
+    }
+
+    /// Like [`FileEmbeddingSystem::with_config`], but embeds with `embedder` instead of
+    /// loading `config.embedding_model`.
+    pub async fn with_embedder(
+        db_path: &str,
+        config: FileEmbeddingConfig,
+        embedder: Box<dyn Embedder>
+    ) -> Result<Self> {
+        config.validate()?;
+        Self::open_validated(db_path, config, Arc::from(embedder)).await
+    }
+
+    /// Like [`FileEmbeddingSystem::with_embedder`], but shares an already loaded model, so
+    /// several indexes open in one process hold a single copy of it in memory.
+    ///
+    /// The systems may embed concurrently: [`Embedder`] requires `Send + Sync`, and a
+    /// [`FastEmbedder`] runs each call as an independent ONNX Runtime inference, which is
+    /// safe to do in parallel on one session. Ensemble models are still loaded per system.
+    pub async fn with_shared_model(
+        db_path: &str,
+        config: FileEmbeddingConfig,
+        embedder: Arc<dyn Embedder>
+    ) -> Result<Self> {
+        config.validate()?;
+        Self::open_validated(db_path, config, embedder).await
+    }
+
+    /// The constructors' shared body, for a `config` that has already been validated.
+    async fn open_validated(
+        db_path: &str,
+        config: FileEmbeddingConfig,
+        embedder: Arc<dyn Embedder>
+    ) -> Result<Self> {
+        // Initialize SurrealDB with RocksDB
+        let db = match Self::connect(db_path, &config).await {
+            Ok(db) => db,
+            Err(e) if storage::is_corruption(&e) => {
+                if !config.storage.reset_if_corrupt || config.storage.read_only {
+                    return Err(
+                        FileEmbeddingError::DatabaseCorrupt {
+                            path: PathBuf::from(db_path),
+                            source: Box::new(e),
+                        }.into()
+                    );
+                }
+                let backup = storage::move_aside(Path::new(db_path))?;
+                log::error!(
+                    "Database at {} is corrupt ({}); moved it to {} and starting fresh",
+                    db_path,
+                    e,
+                    backup.display()
+                );
+                Self::connect(db_path, &config).await?
+            }
+            Err(e) => {
+                return Err(e.into());
+            }
+        };
+        let define_schema = !config.storage.read_only && !config.storage.open_existing;
+        db.use_ns("files").use_db("embeddings").await?;
+        let tables = Self::defined_tables(&db).await?;
+        if config.storage.open_existing && !tables.iter().any(|table| table == "files") {
+            return Err(FileEmbeddingError::SchemaMissing(PathBuf::from(db_path)).into());
+        }
+        if define_schema {
+            // Start from an empty index, including any fitted reduction
+            for table in tables.iter().filter(|table| ["files", REDUCTION_META_ID.0].contains(&table.as_str())) {
+                db.query(format!("REMOVE TABLE {}", table)).await?.check()?;
+            }
+            db.query(
+                "
+                DEFINE TABLE files SCHEMAFUL;
+                DEFINE FIELD path ON files TYPE string;
+                DEFINE FIELD name ON files TYPE string;
+                DEFINE FIELD name_lower ON files VALUE string::lowercase(name);
+                DEFINE FIELD extension ON files TYPE option<string>;
+                DEFINE FIELD raw_extension ON files TYPE option<string>;
+                DEFINE FIELD mime_type ON files TYPE option<string>;
+                DEFINE FIELD size_bytes ON files TYPE number;
+                DEFINE FIELD content_embedding ON files TYPE array<float>;
+                DEFINE FIELD content_preview ON files TYPE string;
+                DEFINE FIELD content ON files TYPE option<string>;
+                DEFINE FIELD is_virtual ON files TYPE bool DEFAULT false;
+                DEFINE FIELD quantized_embedding ON files TYPE option<bytes>;
+                DEFINE FIELD quantization_scale ON files TYPE option<float>;
+                DEFINE FIELD embeddings ON files FLEXIBLE TYPE object DEFAULT {};
+                DEFINE FIELD indexed_at ON files TYPE option<datetime>;
+                DEFINE FIELD embedded ON files TYPE bool DEFAULT false;
+                DEFINE FIELD chunk_embeddings ON files TYPE array<array<float>> DEFAULT [];
+                DEFINE FIELD sentence_embeddings ON files TYPE array<array<float>> DEFAULT [];
+                DEFINE FIELD collection ON files TYPE option<string>;
+                DEFINE FIELD parent_path ON files TYPE option<string>;
+                DEFINE FIELD top_level_keys ON files TYPE array<string> DEFAULT [];
+                DEFINE FIELD path_tokens ON files TYPE array<string> DEFAULT [];
+                DEFINE FIELD last_matched_at ON files TYPE option<datetime>;
+                DEFINE FIELD match_count ON files TYPE number DEFAULT 0;
+                DEFINE FIELD email ON files TYPE option<object>;
+                DEFINE FIELD email.subject ON files TYPE option<string>;
+                DEFINE FIELD email.from ON files TYPE option<string>;
+                DEFINE FIELD email.to ON files TYPE option<string>;
+                DEFINE FIELD email.date ON files TYPE option<string>;
+                
+                DEFINE INDEX idx_path ON files FIELDS path UNIQUE;
+                DEFINE INDEX idx_name ON files FIELDS name_lower;
+                DEFINE INDEX idx_extension ON files FIELDS extension;
+                DEFINE INDEX idx_indexed_at ON files FIELDS indexed_at;
+                DEFINE INDEX idx_embedded ON files FIELDS embedded;
+                DEFINE INDEX idx_collection ON files FIELDS collection;
+                DEFINE INDEX idx_parent_path ON files FIELDS parent_path;
+            "
+            ).await?
+                .check()?;
+        }
+
+        let mut ensemble: Vec<LoadedModel> = Vec::new();
+        for member in &config.ensemble {
+            log::debug!("Loading ensemble model {} ({:?})", member.name, member.model);
+            ensemble.push(LoadedModel {
+                name: member.name.clone(),
+                model: FastEmbedder::new(member.model.clone())?,
+                weight: member.weight,
+            });
+        }
+
+        let mut reduction: Option<Projection> = db.select(REDUCTION_META_ID).await?;
+        if let Some(projection) = reduction.take_if(|p| p.input_dim() != embedder.dimension()) {
+            log::warn!(
+                "Ignoring stored {}->{} dimension reduction, which doesn't fit the {}-dimensional model",
+                projection.input_dim(),
+                projection.output_dim(),
+                embedder.dimension()
+            );
+        }
+
+        Ok(Self {
+            db,
+            db_path: PathBuf::from(db_path),
+            embedder,
+            ensemble,
+            config,
+            reduction: RwLock::new(reduction.map(Arc::new)),
+            capacity: RwLock::new(Capacity::default()),
+        })
+    }
+
+    async fn extract_text_content(&self, path: &PathBuf) -> Result<String, FileEmbeddingError> {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .unwrap_or("")
+            .to_lowercase();
+
+        match extension.as_str() {
+            "pdf" => {
+                let bytes = fs::read(path)?;
+                let text = extract
+                    ::catch_panics(move || pdf_extract::extract_text_from_mem(&bytes)).await
+                    .map_err(FileEmbeddingError::PdfExtraction)?
+                    .map_err(|e| FileEmbeddingError::PdfExtraction(e.to_string()))?;
+                Ok(match self.config.max_embed_chars {
+                    Some(max_chars) => extract::truncate_chars(&text, max_chars).to_string(),
+                    None => text,
+                })
+            }
+            _ if SUPPORTED_TEXT_EXTENSIONS.contains(&extension.as_str()) => {
+                extract
+                    ::read_text_blocking(path, self.config.max_embed_chars).await
+                    .map_err(FileEmbeddingError::Io)
+            }
+            _ => Err(FileEmbeddingError::UnsupportedFileType(extension)),
+        }
+    }
+
+    /// Indexes one file, handling an already-indexed path per `config.on_duplicate`.
+    pub async fn index_file(&self, path: PathBuf) -> Result<IndexOutcome, FileEmbeddingError> {
+        let outcome = self.index_file_as(path, None).await?;
+        self.evict().await?;
+        Ok(outcome)
+    }
+
+    /// Indexes `path`, tagging its record with `collection`.
+    async fn index_file_as(
+        &self,
+        path: PathBuf,
+        collection: Option<&str>
+    ) -> Result<IndexOutcome, FileEmbeddingError> {
+        self.ensure_writable()?;
+        if self.check_duplicate(&path).await? {
+            log::debug!("Skipping already indexed file: {}", path.display());
+            return Ok(IndexOutcome::Skipped);
+        }
+
+        if is_mbox(&path) {
+            return self.index_mbox(&path, collection).await;
+        }
+        let (file_record, content) = self.extract_record(&path).await?;
+        self.store_extracted(&path, file_record, &content, collection).await
+    }
+
+    /// Embeds a record returned by `extract_record` and stores it in `collection`.
+    async fn store_extracted(
+        &self,
+        path: &Path,
+        mut file_record: FileRecord,
+        content: &str,
+        collection: Option<&str>
+    ) -> Result<IndexOutcome, FileEmbeddingError> {
+        self.embed_into(&mut file_record, content)?;
+        self.store_embedded(path, file_record, collection).await
+    }
+
+    /// Stores a record `embed_into` has filled in, in `collection`.
+    async fn store_embedded(
+        &self,
+        path: &Path,
+        mut file_record: FileRecord,
+        collection: Option<&str>
+    ) -> Result<IndexOutcome, FileEmbeddingError> {
+        file_record.collection = collection.map(str::to_string);
+
+        let outcome = self.upsert(&file_record).await?;
+        log::debug!(
+            "Successfully indexed: {} ({:?}, embedding size: {})",
+            path.display(),
+            outcome,
+            file_record.content_embedding.len()
+        );
+
+        Ok(outcome)
+    }
+
+    /// Applies `config.on_duplicate` to `path`: returns whether to skip it, or fails with
+    /// `DuplicatePath`.
+    async fn check_duplicate(&self, path: &Path) -> Result<bool, FileEmbeddingError> {
+        let path_str = path
+            .to_str()
+            .ok_or_else(|| FileEmbeddingError::NonUtf8Path(path.to_path_buf()))?;
+        let policy = self.config.on_duplicate;
+        if policy == OnDuplicate::Update || !self.is_indexed(path_str).await? {
+            return Ok(false);
+        }
+        if policy == OnDuplicate::Skip {
+            return Ok(true);
+        }
+        Err(FileEmbeddingError::DuplicatePath(path_str.to_string()))
+    }
+
+    /// Whether a record, or the messages of an mbox archive, are stored for `path`.
+    async fn is_indexed(&self, path: &str) -> Result<bool, FileEmbeddingError> {
+        let existing: Vec<Record> = self.with_retry(|| async {
+            self.db
+                .query("SELECT id FROM files WHERE path = $path OR parent_path = $path LIMIT 1")
+                .bind(("path", path)).await?
+                .take(0)
+        }).await?;
+        Ok(!existing.is_empty())
+    }
+
+    /// Replaces the record stored for `file_record.path`, or creates one.
+    async fn upsert(&self, file_record: &FileRecord) -> Result<IndexOutcome, FileEmbeddingError> {
+        let updated: Vec<Record> = self.with_retry(|| async {
+            self.db
+                .query("UPDATE files CONTENT $record WHERE path = $path")
+                .bind(("path", file_record.path.as_str()))
+                .bind(("record", file_record)).await?
+                .take(0)
+        }).await?;
+        if !updated.is_empty() {
+            return Ok(IndexOutcome::Updated);
+        }
+
+        let _: Vec<Record> = self.with_retry(|| async {
+            self.db.create("files").content(file_record.clone()).await
+        }).await?;
+        Ok(IndexOutcome::Created)
+    }
+
+    /// Indexes every message of an mbox archive as its own record, replacing the messages
+    /// stored for it before.
+    async fn index_mbox(
+        &self,
+        path: &Path,
+        collection: Option<&str>
+    ) -> Result<IndexOutcome, FileEmbeddingError> {
+        log::debug!("Attempting to index mailbox: {}", path.display());
+        let path_str = path
+            .to_str()
+            .ok_or_else(|| FileEmbeddingError::NonUtf8Path(path.to_path_buf()))?;
+        let raw = fs::read(path)?;
+        let existed = self.is_indexed(path_str).await?;
+
+        let mut records = Vec::new();
+        for (i, message) in email::split_mbox(&raw).into_iter().enumerate() {
+            let parsed = email::parse_message(message).map_err(FileEmbeddingError::EmailParse)?;
+            let mut file_record = FileRecord {
+                path: format!("{}#{}", path_str, i + 1),
+                name: parsed.headers.subject
+                    .clone()
+                    .unwrap_or_else(|| format!("{} #{}", path.display(), i + 1)),
+                extension: Some("mbox".to_string()),
+                raw_extension: path.extension().and_then(|ext| ext.to_str()).map(str::to_string),
+                mime_type: Some("message/rfc822".to_string()),
+                size_bytes: message.len() as u64,
+                parent_path: Some(path_str.to_string()),
+                collection: collection.map(str::to_string),
+                ..Default::default()
+            };
+            self.embed_into(&mut file_record, &self.email_body(parsed.body))?;
+            file_record.email = Some(parsed.headers);
+            records.push(file_record);
+        }
+
+        let mut sql = String::from("BEGIN TRANSACTION; DELETE files WHERE parent_path = $path;");
+        if !records.is_empty() {
+            sql.push_str(" INSERT INTO files $records;");
+        }
+        sql.push_str(" COMMIT TRANSACTION;");
+        self.with_retry(|| async {
+            self.db
+                .query(sql.as_str())
+                .bind(("path", path_str))
+                .bind(("records", records.clone())).await?
+                .check()
+        }).await?;
+        log::debug!("Successfully indexed {} messages from {}", records.len(), path.display());
+
+        Ok(if existed { IndexOutcome::Updated } else { IndexOutcome::Created })
+    }
+
+    /// An email body cut to `max_embed_chars`, like any other extracted text.
+    fn email_body(&self, body: String) -> String {
+        match self.config.max_embed_chars {
+            Some(max_chars) => extract::truncate_chars(&body, max_chars).to_string(),
+            None => body,
+        }
+    }
+
+    /// Reads the text to embed for `file_record`, filling in the headers of `.eml` files.
+    async fn load_content(
+        &self,
+        file_record: &mut FileRecord,
+        path: &Path,
+        sniffed_text: bool
+    ) -> Result<String, FileEmbeddingError> {
+        if file_record.extension.as_deref() == Some("eml") {
+            let message = email
+                ::parse_message(&fs::read(path)?)
+                .map_err(FileEmbeddingError::EmailParse)?;
+            file_record.email = Some(message.headers);
+            return Ok(self.email_body(message.body));
+        }
+        let content = self.read_content(path, sniffed_text).await?;
+        let extension = file_record.extension.as_deref().unwrap_or("");
+        if self.config.flatten_structured && structured::STRUCTURED_EXTENSIONS.contains(&extension) {
+            if let Some(flattened) = structured::flatten(extension, &content) {
+                file_record.top_level_keys = flattened.top_level_keys;
+                return Ok(flattened.text);
+            }
+        }
+        Ok(content)
+    }
+
+    /// Reads and embeds `path` into a record ready to be stored.
+    async fn prepare_record(&self, path: &Path) -> Result<FileRecord, FileEmbeddingError> {
+        let (mut file_record, content) = self.extract_record(path).await?;
+        self.embed_into(&mut file_record, &content)?;
+        Ok(file_record)
+    }
+
+    /// Rejects content shorter than `config.min_content_chars`, once surrounding
+    /// whitespace is trimmed.
+    fn check_content_length(
+        &self,
+        file_record: &FileRecord,
+        content: &str
+    ) -> Result<(), FileEmbeddingError> {
+        let min_chars = self.config.min_content_chars;
+        if content.trim().chars().take(min_chars).count() < min_chars {
+            log::debug!("Skipping file with too little content: {}", file_record.path);
+            return Err(FileEmbeddingError::ContentTooShort(file_record.path.clone()));
+        }
+        Ok(())
+    }
+
+    /// Reads `path` into a record without embeddings, along with the text to embed.
+    async fn extract_record(&self, path: &Path) -> Result<(FileRecord, String), FileEmbeddingError> {
+        log::debug!("Attempting to index: {}", path.display());
+        let (mut file_record, sniffed_text) = self.describe_file(path)?;
+
+        match self.load_content(&mut file_record, path, sniffed_text).await {
+            Ok(content) => {
+                log::debug!("Successfully extracted content from: {}", path.display());
+                self.check_content_length(&file_record, &content)?;
+                Ok((file_record, content))
+            }
+            Err(e) => {
+                log::debug!("Error extracting content from {}: {:?}", path.display(), e);
+                Err(e)
+            }
+        }
+    }
+
+    /// Checks that `path` is an indexable file and builds its record without content.
+    ///
+    /// Also returns whether the file is only indexable because it sniffed as text.
+    fn describe_file(&self, path: &Path) -> Result<(FileRecord, bool), FileEmbeddingError> {
+        let metadata = fs::metadata(path)?;
+        let raw_extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_string);
+        let extension = raw_extension.as_deref().map(str::to_lowercase);
+
+        // Stored paths must round-trip back to a real PathBuf for deletes and pruning
+        let path_str = path
+            .to_str()
+            .ok_or_else(|| FileEmbeddingError::NonUtf8Path(path.to_path_buf()))?;
+
+        let recognized = Self::is_supported_extension(extension.as_deref());
+        let sniffed_text =
+            !recognized &&
+            metadata.is_file() &&
+            self.config.index_extensionless_text &&
+            sniff::looks_like_text(path)?;
+
+        if !metadata.is_file() || !(recognized || sniffed_text) {
+            log::debug!("Skipping unsupported file: {}", path.display());
+            return Err(
+                FileEmbeddingError::UnsupportedFileType(
+                    extension.unwrap_or_else(|| String::from("unknown")).clone()
+                )
+            );
+        }
+
+        let file_record = FileRecord {
+            path: path_str.to_string(),
+            name: path.file_name().unwrap_or_default().to_string_lossy().to_string(),
+            extension,
+            raw_extension,
+            mime_type: if sniffed_text {
+                Some(sniff::TEXT_MIME_TYPE.to_string())
+            } else {
+                mime_guess
+                    ::from_path(path)
+                    .first()
+                    .map(|m| m.to_string())
+            },
+            size_bytes: metadata.len(),
+            ..Default::default()
+        };
+
+        Ok((file_record, sniffed_text))
+    }
+
+    fn is_supported_extension(extension: Option<&str>) -> bool {
+        extension.is_some_and(|ext| SUPPORTED_TEXT_EXTENSIONS.contains(&ext))
+    }
+
+    /// Reads a file's text, bypassing the per-extension extractors for sniffed text files.
+    async fn read_content(
+        &self,
+        path: &Path,
+        sniffed_text: bool
+    ) -> Result<String, FileEmbeddingError> {
+        if sniffed_text {
+            extract
+                ::read_text_blocking(path, self.config.max_embed_chars).await
+                .map_err(FileEmbeddingError::Io)
+        } else {
+            self.extract_text_content(&path.to_path_buf()).await
+        }
+    }
+
+    /// Records every indexable file under `dir_path` without reading or embedding it.
+    ///
+    /// This gives an immediate inventory of a large tree; the records are excluded from
+    /// searches until [`FileEmbeddingSystem::embed_pending`] embeds them. Already indexed
+    /// paths are handled per `config.on_duplicate`, and with `Update` only files whose size
+    /// or type changed are recorded again. Returns how many files were recorded.
+    pub async fn index_metadata_only(&self, dir_path: PathBuf) -> Result<usize, FileEmbeddingError> {
+        self.ensure_writable()?;
+        let mut recorded = 0;
+        for entry in WalkDir::new(dir_path) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            match self.record_metadata(entry.path()).await {
+                Ok(true) => {
+                    recorded += 1;
+                }
+                Ok(false) => {}
+                Err(e) => log::warn!("Error recording {}: {:?}", entry.path().display(), e),
+            }
+        }
+        log::debug!("Recorded {} files for later embedding", recorded);
+        Ok(recorded)
+    }
+
+    /// Stores an unembedded record for `path` unless it is skipped as a duplicate or its
+    /// stored record still matches, returning whether it was stored.
+    async fn record_metadata(&self, path: &Path) -> Result<bool, FileEmbeddingError> {
+        #[derive(Deserialize)]
+        struct StoredMetadata {
+            size_bytes: u64,
+            mime_type: Option<String>,
+        }
+
+        let (file_record, _) = self.describe_file(path)?;
+        if self.check_duplicate(path).await? {
+            return Ok(false);
+        }
+
+        let stored: Vec<StoredMetadata> = self.with_retry(|| async {
+            self.db
+                .query("SELECT size_bytes, mime_type FROM files WHERE path = $path")
+                .bind(("path", file_record.path.as_str())).await?
+                .take(0)
+        }).await?;
+        let unchanged = stored
+            .iter()
+            .any(|s| s.size_bytes == file_record.size_bytes && s.mime_type == file_record.mime_type);
+        if unchanged {
+            return Ok(false);
+        }
+
+        self.upsert(&file_record).await?;
+        Ok(true)
+    }
+
+    /// Embeds up to `limit` records created by [`FileEmbeddingSystem::index_metadata_only`],
+    /// reporting what was embedded and dropped.
+    ///
+    /// Call repeatedly (e.g. from a background task) until nothing is embedded or dropped.
+    /// Files that are gone or have too little content are dropped from the index, as
+    /// `index_file` would have rejected them. Files that fail to read for any other reason,
+    /// e.g. a locked file, stay pending and are retried by the next call.
+    pub async fn embed_pending(&self, limit: usize) -> Result<PendingReport, FileEmbeddingError> {
+        self.ensure_writable()?;
+        let mut report = PendingReport::default();
+        loop {
+            let done = report.embedded.len() + report.dropped.len();
+            if done >= limit {
+                break;
+            }
+            // Rows are read in path order and failed ones stay pending, so skip past those
+            let pending: Vec<FileRecord> = self.with_retry(|| async {
+                self.db
+                    .query(
+                        "SELECT * FROM files WHERE embedded = false ORDER BY path \
+                         LIMIT $limit START $start"
+                    )
+                    .bind(("limit", limit - done))
+                    .bind(("start", report.failed.len())).await?
+                    .take(0)
+            }).await?;
+            if pending.is_empty() {
+                break;
+            }
+
+            for mut file_record in pending {
+                let path = PathBuf::from(&file_record.path);
+                let sniffed_text = !Self::is_supported_extension(file_record.extension.as_deref());
+                let loaded = self
+                    .load_content(&mut file_record, &path, sniffed_text).await
+                    .and_then(|content| {
+                        self.check_content_length(&file_record, &content)?;
+                        Ok(content)
+                    });
+                let content = match loaded {
+                    Ok(content) => content,
+                    Err(e) => {
+                        let gone = match &e {
+                            FileEmbeddingError::ContentTooShort(_) => true,
+                            FileEmbeddingError::Io(e) => e.kind() == std::io::ErrorKind::NotFound,
+                            _ => false,
+                        };
+                        if gone {
+                            log::debug!("Dropping {} from the index: {:?}", path.display(), e);
+                            self.delete_file(&file_record.path).await?;
+                            report.dropped.push((file_record.path, e.to_string()));
+                        } else {
+                            log::warn!("Leaving {} pending: {:?}", path.display(), e);
+                            report.failed.push((file_record.path, e.to_string()));
+                        }
+                        continue;
+                    }
+                };
+                self.embed_into(&mut file_record, &content)?;
+
+                let _: Vec<Record> = self.with_retry(|| async {
+                    self.db
+                        .query("UPDATE files CONTENT $record WHERE path = $path")
+                        .bind(("path", file_record.path.as_str()))
+                        .bind(("record", &file_record)).await?
+                        .take(0)
+                }).await?;
+                report.embedded.push(file_record.path);
+            }
+        }
+
+        log::debug!(
+            "Embedded {} pending files, dropped {}, {} still pending after errors",
+            report.embedded.len(),
+            report.dropped.len(),
+            report.failed.len()
+        );
+        Ok(report)
+    }
+
+    /// Indexes a string that doesn't live on disk, keyed by `virtual_path`.
+    ///
+    /// Indexing the same `virtual_path` again replaces the stored record.
+    pub async fn index_content(
+        &self,
+        virtual_path: &str,
+        content: &str,
+        extension: Option<&str>
+    ) -> Result<IndexOutcome, FileEmbeddingError> {
+        self.ensure_writable()?;
+        log::debug!("Attempting to index content: {}", virtual_path);
+
+        let raw_extension = extension.map(|ext| ext.trim_start_matches('.').to_string());
+        let extension = raw_extension.as_deref().map(str::to_lowercase);
+        let mut file_record = FileRecord {
+            path: virtual_path.to_string(),
+            name: virtual_path.rsplit(['/', '\\']).next().unwrap_or(virtual_path).to_string(),
+            mime_type: extension
+                .as_deref()
+                .and_then(|ext| mime_guess::from_ext(ext).first())
+                .map(|m| m.to_string()),
+            extension,
+            raw_extension,
+            size_bytes: content.len() as u64,
+            is_virtual: true,
+            ..Default::default()
+        };
+        self.embed_into(&mut file_record, content)?;
+
+        let outcome = self.upsert(&file_record).await?;
+        log::debug!("Successfully indexed: {} ({:?})", virtual_path, outcome);
+        self.evict().await?;
+        Ok(outcome)
+    }
+
+    /// Runs a database operation under the configured [`RetryConfig`].
+    async fn with_retry<T, F, Fut>(&self, operation: F) -> Result<T, surrealdb::Error>
+        where F: FnMut() -> Fut, Fut: Future<Output = Result<T, surrealdb::Error>>
+    {
+        retry::retry(&self.config.retry, operation).await
+    }
+
+    /// Embeds `content` and fills in the embedding and preview fields of `record`.
+    fn embed_into(&self, record: &mut FileRecord, content: &str) -> Result<(), FileEmbeddingError> {
+        self.embed_batch(&mut [(record, content)])?.remove(0)
+    }
+
+    /// Like [`Self::embed_into`] for several records, embedding their whole documents with
+    /// one call per model.
+    ///
+    /// Fails as a whole only if a model call does; the result for each record carries its
+    /// own validation error.
+    fn embed_batch(
+        &self,
+        items: &mut [(&mut FileRecord, &str)]
+    ) -> Result<Vec<Result<(), FileEmbeddingError>>, FileEmbeddingError> {
+        if items.is_empty() {
+            return Ok(Vec::new());
+        }
+        let texts: Vec<EmbedText> = items
+            .iter()
+            .map(|(record, content)| self.prepare_embedding(record, content))
+            .collect();
+        let inputs: Vec<&str> = texts
+            .iter()
+            .map(|text| text.input.as_str())
+            .collect();
+        // Sentence pooling embeds the whole document only as a fallback, record by record
+        let whole_docs: Vec<Option<Vec<f32>>> = if self.config.aggregation.embeds_sentences() {
+            vec![None; inputs.len()]
+        } else {
+            embed_checked(&*self.embedder, &inputs, self.config.embed_batch_size)?
+                .into_iter()
+                .map(Some)
+                .collect()
+        };
+        let mut per_model = Vec::with_capacity(self.ensemble.len());
+        for member in &self.ensemble {
+            let embeddings = embed_checked(&member.model, &inputs, self.config.embed_batch_size)?;
+            per_model.push(embeddings.into_iter());
+        }
+
+        Ok(
+            items
+                .iter_mut()
+                .zip(texts)
+                .zip(whole_docs)
+                .map(|(((record, _), text), whole_doc)| {
+                    let ensemble = per_model
+                        .iter_mut()
+                        .map(|embeddings| embeddings.next().unwrap_or_default())
+                        .collect();
+                    self.finish_embedding(record, text, whole_doc, ensemble)
+                })
+                .collect()
+        )
+    }
+
+    /// Applies preprocessors, truncation and the path header to `content`.
+    fn prepare_embedding<'a>(&self, record: &FileRecord, content: &'a str) -> EmbedText<'a> {
+        let content = match self.config.preprocessors.apply(record.extension.as_deref(), content) {
+            Some(preprocessed) => std::borrow::Cow::Owned(preprocessed),
+            None => std::borrow::Cow::Borrowed(content),
+        };
+        let truncated = self.config.embed_truncation.apply(&content, self.config.embed_input_chars);
+        let input = if self.config.include_path_in_embedding {
+            paths::with_path_header(&record.path, &truncated)
+        } else {
+            truncated.into_owned()
+        };
+        let binary_derived = record.extension
+            .as_deref()
+            .is_some_and(|ext| preview::BINARY_DERIVED_EXTENSIONS.contains(&ext));
+        let display = if self.config.clean_preview && binary_derived {
+            std::borrow::Cow::Owned(preview::clean(&content))
+        } else {
+            content
+        };
+        EmbedText { display, input }
+    }
+
+    /// Fills in `record` from its prepared text and the vectors `embed_batch` computed for
+    /// it, embedding sentences and preview chunks itself.
+    fn finish_embedding(
+        &self,
+        record: &mut FileRecord,
+        text: EmbedText,
+        whole_doc: Option<Vec<f32>>,
+        ensemble: Vec<Vec<f32>>
+    ) -> Result<(), FileEmbeddingError> {
+        let EmbedText { display: display_content, input: embedding_input } = text;
+        let sentences = if self.config.aggregation.embeds_sentences() {
+            aggregate::split_sentences(&display_content)
+        } else {
+            Vec::new()
+        };
+        record.sentence_embeddings = Vec::new();
+        let primary = if !sentences.is_empty() {
+            let sentence_embeddings = embed_checked(
+                &*self.embedder,
+                &sentences,
+                self.config.embed_batch_size
+            )?;
+            for embedding in &sentence_embeddings {
+                check_dimension(&record.path, self.embedder.dimension(), embedding)?;
+            }
+            let pooled = self.config.aggregation.pool(&sentence_embeddings).unwrap_or_default();
+            record.sentence_embeddings = sentence_embeddings;
+            pooled
+        } else if let Some(whole_doc) = whole_doc {
+            whole_doc
+        } else {
+            let input = embedding_input.as_str();
+            embed_checked(&*self.embedder, &[input], self.config.embed_batch_size)?.remove(0)
+        };
+
+        log::debug!("Generated embedding with size: {}", primary.len());
+        check_dimension(&record.path, self.embedder.dimension(), &primary)?;
+        validate_embedding(&record.path, &primary)?;
+
+        record.content_embedding = primary;
+        for (member, embedding) in self.ensemble.iter().zip(ensemble) {
+            check_dimension(&record.path, member.model.dimension(), &embedding)?;
+            validate_embedding(&record.path, &embedding)?;
+            record.embeddings.insert(member.name.clone(), embedding);
+        }
+        record.path_tokens = self.path_tokens(&record.path);
+        record.indexed_at = Some(surrealdb::sql::Datetime::default());
+        record.embedded = true;
+        let preview_chars = self.config.preview_chars_for(record.extension.as_deref());
+        record.content_preview = self.config.preview_strategy.stored_preview(
+            &display_content,
+            preview_chars
+        );
+        record.chunk_embeddings = Vec::new();
+        if self.config.preview_strategy == PreviewStrategy::BestMatch {
+            let chunks = preview::windows(&display_content, preview_chars);
+            if chunks.len() > 1 {
+                let texts: Vec<&str> = chunks.iter().map(String::as_str).collect();
+                record.chunk_embeddings = embed_checked(
+                    &*self.embedder,
+                    &texts,
+                    self.config.embed_batch_size
+                )?;
+            }
+        }
+        // Query-time previews re-split the content to line up with the stored vectors
+        let keep_content =
+            self.config.preview_strategy == PreviewStrategy::BestMatch ||
+            !record.sentence_embeddings.is_empty();
+        record.content = keep_content.then(|| display_content.into_owned());
+
+        // Debug: Print sample of embedding before storage
+        log::debug!(
+            "First few values of embedding for {}: {:?}",
+            record.name,
+            record.content_embedding.iter().take(5).collect::<Vec<_>>()
+        );
+
+        self.encode_embeddings(record);
+        Ok(())
+    }
+
+    fn path_tokens(&self, path: &str) -> Vec<String> {
+        if self.config.index_path_tokens { paths::directory_tokens(path) } else { Vec::new() }
+    }
+
+    /// Converts a record's full-precision model vectors into their stored form: reduced, if
+    /// a projection has been fitted, then quantized to the configured precision.
+    fn encode_embeddings(&self, record: &mut FileRecord) {
+        if let Some(projection) = self.reduction() {
+            record.content_embedding = projection.project(&record.content_embedding);
+            for embedding in record.chunk_embeddings.iter_mut().chain(&mut record.sentence_embeddings) {
+                *embedding = projection.project(embedding);
+            }
+        }
+
+        let quantized = self.config.embedding_precision.quantize(&record.content_embedding);
+        if let Some((bytes, scale)) = quantized {
+            record.quantized_embedding = Some(bytes);
+            record.quantization_scale = scale;
+            record.content_embedding = Vec::new();
+        } else {
+            record.quantized_embedding = None;
+            record.quantization_scale = None;
+        }
+    }
+
+    fn reduction(&self) -> Option<Arc<Projection>> {
+        self.reduction
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    /// Bounds the index to `capacity`, evicting right away if it is already over.
+    ///
+    /// While bounded, every search records when it returned each file, and indexing is
+    /// followed by [`FileEmbeddingSystem::evict`]. `Capacity::default()` removes the bound.
+    pub async fn set_capacity(&self, capacity: Capacity) -> Result<Vec<String>, FileEmbeddingError> {
+        *self.capacity.write().unwrap_or_else(PoisonError::into_inner) = capacity;
+        self.evict().await
+    }
+
+    fn capacity(&self) -> Capacity {
+        *self.capacity.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Deletes the records matched least recently until the index fits its capacity,
+    /// returning their paths.
+    ///
+    /// Records never returned by a search count as matched when they were indexed, so a
+    /// freshly indexed file isn't evicted before older ones.
+    pub async fn evict(&self) -> Result<Vec<String>, FileEmbeddingError> {
+        let capacity = self.capacity();
+        if !capacity.is_bounded() || self.config.storage.read_only {
+            return Ok(Vec::new());
+        }
+        #[derive(Deserialize)]
+        struct SizeRow {
+            path: String,
+            size_bytes: u64,
+        }
+
+        let rows: Vec<SizeRow> = self.with_retry(|| async {
+            self.db
+                .query(
+                    "SELECT path, size_bytes, last_matched_at ?? indexed_at AS recency FROM files \
+                     ORDER BY recency ASC, path ASC"
+                ).await?
+                .take(0)
+        }).await?;
+        let mut files = rows.len();
+        let mut bytes: u64 = rows
+            .iter()
+            .map(|row| row.size_bytes)
+            .sum();
+        let mut evicted = Vec::new();
+        for row in rows {
+            if !capacity.is_exceeded(files, bytes) {
+                break;
+            }
+            files -= 1;
+            bytes -= row.size_bytes;
+            evicted.push(row.path);
+        }
+        if evicted.is_empty() {
+            return Ok(evicted);
+        }
+
+        self.with_retry(|| async {
+            self.db
+                .query("DELETE files WHERE path IN $paths OR parent_path IN $paths")
+                .bind(("paths", evicted.clone())).await?
+                .check()
+        }).await?;
+        log::debug!("Evicted {} records to stay within {:?}", evicted.len(), capacity);
+        Ok(evicted)
+    }
+
+    /// Marks `results` as just matched, for eviction; only done while a capacity is set.
+    async fn record_matches(&self, results: &[SearchResult]) -> Result<(), FileEmbeddingError> {
+        if results.is_empty() || !self.capacity().is_bounded() || self.config.storage.read_only {
+            return Ok(());
+        }
+        let paths: Vec<String> = results
+            .iter()
+            .map(|result| result.file.path.clone())
+            .collect();
+        self.with_retry(|| async {
+            self.db
+                .query(
+                    "UPDATE files SET last_matched_at = time::now(), match_count += 1 \
+                     WHERE path IN $paths"
+                )
+                .bind(("paths", paths.clone())).await?
+                .check()
+        }).await?;
+        Ok(())
+    }
+
+    /// Dimension of stored primary vectors: the model's, or the reduced one.
+    fn stored_dimension(&self) -> usize {
+        self.reduction().map_or_else(|| self.embedder.dimension(), |p| p.output_dim())
+    }
+
+    /// Fits a PCA projection to `target_dim` dimensions on up to 2000 stored embeddings and
+    /// rewrites every record in the reduced space, returning how many were rewritten.
+    ///
+    /// The projection is saved in the `_meta` table and applied to everything stored or
+    /// searched afterwards, including after reopening. The full vectors are not kept, so
+    /// undoing a reduction means re-indexing. See [`crate::reduce`] for the tradeoff.
+    pub async fn fit_reduction(&self, target_dim: usize) -> Result<usize, FileEmbeddingError> {
+        self.ensure_writable()?;
+        if self.reduction().is_some() {
+            return Err(
+                FileEmbeddingError::Reduction(
+                    String::from("the index is already reduced; re-index it to fit a new projection")
+                )
+            );
+        }
+
+        let mut records: Vec<FileRecord> = self.with_retry(|| async {
+            self.db.query("SELECT * FROM files WHERE embedded = true").await?.take(0)
+        }).await?;
+        records.iter_mut().for_each(FileRecord::dequantize_embedding);
+        // Vectors of the wrong size are left for verify_index to flag
+        let dimension = self.embedder.dimension();
+        records.retain(|record| record.content_embedding.len() == dimension);
+        let samples: Vec<Vec<f32>> = records
+            .iter()
+            .take(REDUCTION_SAMPLE_SIZE)
+            .map(|record| record.content_embedding.clone())
+            .collect();
+        let projection = Projection::fit(&samples, target_dim).ok_or_else(|| {
+            FileEmbeddingError::Reduction(
+                format!(
+                    "cannot fit {} dimensions on {} embeddings of dimension {}",
+                    target_dim,
+                    samples.len(),
+                    dimension
+                )
+            )
+        })?;
+
+        let _: Option<Projection> = self.with_retry(|| async {
+            self.db.update(REDUCTION_META_ID).content(projection.clone()).await
+        }).await?;
+        *self.reduction.write().unwrap_or_else(PoisonError::into_inner) = Some(Arc::new(projection));
+
+        for record in records.iter_mut() {
+            self.encode_embeddings(record);
+            self.upsert(record).await?;
+        }
+        log::debug!("Reduced {} records to {} dimensions", records.len(), target_dim);
+        Ok(records.len())
+    }
+
+    pub async fn index_directory(
+        &self,
+        dir_path: PathBuf
+    ) -> Result<IndexReport, FileEmbeddingError> {
+        self.index_tree(dir_path, None, None).await
+    }
+
+    /// Like [`FileEmbeddingSystem::index_directory`], but only indexes files modified after
+    /// `since`, e.g. the [`IndexReport::started_at`] of the previous run.
+    ///
+    /// Older files are skipped on their metadata alone, without being opened, so a scheduled
+    /// re-index of a large, mostly static tree is cheap.
+    pub async fn index_directory_since(
+        &self,
+        dir_path: PathBuf,
+        since: std::time::SystemTime
+    ) -> Result<IndexReport, FileEmbeddingError> {
+        self.index_tree(dir_path, None, Some(since)).await
+    }
+
+    /// Like [`FileEmbeddingSystem::index_directory`], but tags every file with `collection`
+    /// so searches can be limited to it with [`SearchFilter::collection`].
+    ///
+    /// One database can hold several collections, e.g. `"work"` and `"personal"`.
+    pub async fn index_directory_as(
+        &self,
+        dir_path: PathBuf,
+        collection: &str
+    ) -> Result<IndexReport, FileEmbeddingError> {
+        self.index_tree(dir_path, Some(collection), None).await
+    }
+
+    async fn index_tree(
+        &self,
+        dir_path: PathBuf,
+        collection: Option<&str>,
+        since: Option<std::time::SystemTime>
+    ) -> Result<IndexReport, FileEmbeddingError> {
+        self.ensure_writable()?;
+        let mut report = IndexReport {
+            collection: collection.map(str::to_string),
+            started_at: Some(std::time::SystemTime::now()),
+            ..Default::default()
+        };
+        // Walk first so progress events know the total
+        let mut paths = Vec::new();
+        for entry in WalkDir::new(dir_path) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            if let Some(since) = since {
+                if entry.metadata()?.modified()? <= since {
+                    report.unchanged += 1;
+                    continue;
+                }
+            }
+            paths.push(entry.into_path());
+        }
+
+        let started = std::time::Instant::now();
+        let total = paths.len();
+        if self.config.extract_concurrency > 1 {
+            self.index_pipelined(paths, &mut report, started).await?;
+        } else {
+            for (i, path) in paths.into_iter().enumerate() {
+                self.index_into_report(path.clone(), &mut report).await?;
+                self.report_progress(i + 1, total, path, started);
+            }
+        }
+        self.evict().await?;
+        if self.config.flush_after_indexing {
+            self.flush().await?;
+        }
+        Ok(report)
+    }
+
+    /// Indexes `paths` with extraction running up to `config.extract_concurrency` files
+    /// ahead of embedding. Files are stored and reported in the order given, as in the
+    /// serial path.
+    async fn index_pipelined(
+        &self,
+        paths: Vec<PathBuf>,
+        report: &mut IndexReport,
+        started: std::time::Instant
+    ) -> Result<(), FileEmbeddingError> {
+        let total = paths.len();
+        let batch_size = self.config.pipeline_batch_size;
+        let (sender, mut receiver) = tokio::sync::mpsc::channel(
+            batch_size.max(self.config.extract_concurrency)
+        );
+
+        let extract = async move {
+            let mut extracted = futures::stream
+                ::iter(paths)
+                .map(|path| async move {
+                    let extracted = self.extract_for_pipeline(&path).await;
+                    (path, extracted)
+                })
+                .buffered(self.config.extract_concurrency);
+            while let Some(item) = extracted.next().await {
+                if sender.send(item).await.is_err() {
+                    // The embedding stage stopped on an error
+                    break;
+                }
+            }
+        };
+
+        let collection = report.collection.clone();
+        // Owning the receiver means returning an error drops it, which stops extraction
+        let embed = async move {
+            let mut done = 0;
+            let mut batch = Vec::with_capacity(batch_size);
+            while receiver.recv_many(&mut batch, batch_size).await > 0 {
+                let mut embedded = self.embed_extracted(&mut batch).into_iter();
+                for (path, extracted) in batch.drain(..) {
+                    let outcome = match extracted {
+                        Ok(Extracted::Skipped) => Ok(IndexOutcome::Skipped),
+                        Ok(Extracted::Mbox) => self.index_mbox(&path, collection.as_deref()).await,
+                        Ok(Extracted::Record(file_record, _)) =>
+                            match embedded.next() {
+                                Some(Err(e)) => Err(e),
+                                _ => self.store_embedded(&path, *file_record, collection.as_deref()).await,
+                            }
+                        Err(e) => Err(e),
+                    };
+                    self.record_outcome(path.clone(), outcome, report)?;
+                    done += 1;
+                    self.report_progress(done, total, path, started);
+                }
+            }
+            Ok(())
+        };
+
+        let ((), result) = tokio::join!(extract, embed);
+        result
+    }
+
+    /// Embeds the extracted records in a batch of `index_pipelined` together, returning one
+    /// result per record in order.
+    fn embed_extracted(
+        &self,
+        batch: &mut [(PathBuf, Result<Extracted, FileEmbeddingError>)]
+    ) -> Vec<Result<(), FileEmbeddingError>> {
+        let mut records: Vec<(&mut FileRecord, &str)> = batch
+            .iter_mut()
+            .filter_map(|(_, extracted)| {
+                match extracted {
+                    Ok(Extracted::Record(file_record, content)) =>
+                        Some((&mut **file_record, content.as_str())),
+                    _ => None,
+                }
+            })
+            .collect();
+        match self.embed_batch(&mut records) {
+            Ok(embedded) => embedded,
+            // Retry one by one, so an input the model rejects only fails its own file
+            Err(_) if records.len() > 1 =>
+                records
+                    .iter_mut()
+                    .map(|(record, content)| self.embed_into(record, content))
+                    .collect(),
+            Err(e) => vec![Err(e)],
+        }
+    }
+
+    /// The extraction stage of `index_pipelined` for one file.
+    async fn extract_for_pipeline(&self, path: &Path) -> Result<Extracted, FileEmbeddingError> {
+        if self.check_duplicate(path).await? {
+            log::debug!("Skipping already indexed file: {}", path.display());
+            return Ok(Extracted::Skipped);
+        }
+        if is_mbox(path) {
+            return Ok(Extracted::Mbox);
+        }
+        let (file_record, content) = self.extract_record(path).await?;
+        Ok(Extracted::Record(Box::new(file_record), content))
+    }
+
+    fn report_progress(
+        &self,
+        done: usize,
+        total: usize,
+        path: PathBuf,
+        started: std::time::Instant
+    ) {
+        if let Some(progress) = &self.config.progress {
+            let elapsed = started.elapsed();
+            progress.report(&IndexProgress {
+                done,
+                total,
+                path,
+                elapsed,
+                eta: progress::estimate_remaining(done, total, elapsed),
+            });
+        }
+    }
+
+    /// Re-attempts only the files in `report.failed`, e.g. after a transient lock or
+    /// permission problem has cleared, and reports on just those files.
+    pub async fn retry_failed(
+        &self,
+        report: &IndexReport
+    ) -> Result<IndexReport, FileEmbeddingError> {
+        self.ensure_writable()?;
+        let mut retried = IndexReport {
+            collection: report.collection.clone(),
+            started_at: Some(std::time::SystemTime::now()),
+            ..Default::default()
+        };
+        for (path, _) in &report.failed {
+            self.index_into_report(path.clone(), &mut retried).await?;
+        }
+        if self.config.flush_after_indexing {
+            self.flush().await?;
+        }
+        log::debug!(
+            "Retried {} files: {} indexed, {} still failing",
+            report.failed.len(),
+            retried.indexed.len(),
+            retried.failed.len()
+        );
+        Ok(retried)
+    }
+
+    /// Indexes one file into `report.collection` and files it under the right list of
+    /// `report`.
+    ///
+    /// Failures are only returned under [`IndexErrorPolicy::StopOnFirst`].
+    async fn index_into_report(
+        &self,
+        path: PathBuf,
+        report: &mut IndexReport
+    ) -> Result<(), FileEmbeddingError> {
+        let outcome = self.index_file_as(path.clone(), report.collection.as_deref()).await;
+        self.record_outcome(path, outcome, report)
+    }
+
+    /// Files `path` under the list of `report` that `outcome` belongs to.
+    fn record_outcome(
+        &self,
+        path: PathBuf,
+        outcome: Result<IndexOutcome, FileEmbeddingError>,
+        report: &mut IndexReport
+    ) -> Result<(), FileEmbeddingError> {
+        match outcome {
+            Ok(IndexOutcome::Skipped) => report.skipped.push((path, SkipReason::AlreadyIndexed)),
+            Ok(_) => report.indexed.push(path),
+            Err(FileEmbeddingError::UnsupportedFileType(extension)) => {
+                let reason = self.unsupported_reason(&path, extension);
+                report.skipped.push((path, reason));
+            }
+            Err(FileEmbeddingError::ContentTooShort(_)) => {
+                report.skipped.push((path, SkipReason::TooShort));
+            }
+            Err(FileEmbeddingError::Io(e)) if e.kind() == std::io::ErrorKind::NotFound => {
+                log::debug!("Skipping vanished file: {}", path.display());
+                report.vanished += 1;
+            }
+            Err(e) => {
+                log::warn!("Error indexing {}: {:?}", path.display(), e);
+                if self.config.index_error_policy == IndexErrorPolicy::StopOnFirst {
+                    return Err(e);
+                }
+                report.failed.push((path, e.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// Why `describe_file` rejected `path` as [`FileEmbeddingError::UnsupportedFileType`].
+    fn unsupported_reason(&self, path: &Path, extension: String) -> SkipReason {
+        // Unrecognised files are only rejected after sniffing when sniffing is on
+        if self.config.index_extensionless_text && path.is_file() {
+            match fs::metadata(path) {
+                Ok(metadata) if metadata.len() == 0 => SkipReason::Empty,
+                _ => SkipReason::Binary,
+            }
+        } else {
+            SkipReason::UnsupportedExtension(extension)
+        }
+    }
+
+    /// Walks `dir_path` like [`FileEmbeddingSystem::index_directory`] and reports what it
+    /// would index and skip, without extracting, embedding or writing anything.
+    ///
+    /// Already-indexed files are looked up in the database, so the plan reflects
+    /// `config.on_duplicate` too.
+    pub async fn index_directory_dry_run(
+        &self,
+        dir_path: PathBuf
+    ) -> Result<IndexPlan, FileEmbeddingError> {
+        let mut plan = IndexPlan {
+            models: 1 + self.ensemble.len(),
+            ..Default::default()
+        };
+        for entry in WalkDir::new(dir_path) {
+            let entry = entry?;
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let path = entry.into_path();
+            let planned = match self.check_duplicate(&path).await {
+                Ok(true) => {
+                    plan.skipped.push((path, SkipReason::AlreadyIndexed));
+                    continue;
+                }
+                Ok(false) => self.describe_file(&path),
+                Err(e) => Err(e),
+            };
+            match planned {
+                Ok((record, _)) => {
+                    plan.files.push(PlannedFile {
+                        path,
+                        size_bytes: record.size_bytes,
+                        extension: record.extension,
+                        mime_type: record.mime_type,
+                    });
+                }
+                Err(FileEmbeddingError::UnsupportedFileType(extension)) => {
+                    let reason = self.unsupported_reason(&path, extension);
+                    plan.skipped.push((path, reason));
+                }
+                Err(FileEmbeddingError::Database(e)) => {
+                    return Err(FileEmbeddingError::Database(e));
+                }
+                Err(e) => plan.failed.push((path, e.to_string())),
+            }
+        }
+        log::debug!(
+            "Dry run: would index {} files ({} bytes), skip {}",
+            plan.files.len(),
+            plan.total_bytes(),
+            plan.skipped.len()
+        );
+        Ok(plan)
+    }
+
+    /// Keeps the index in sync with `dir_path`, re-indexing files as they change and removing
+    /// records of deleted files. Runs until the watcher stops.
+    ///
+    /// Changes are committed in batches; see [`WatchConfig`].
+    pub async fn watch_directory(
+        &self,
+        dir_path: PathBuf,
+        watch: &WatchConfig
+    ) -> Result<(), FileEmbeddingError> {
+        use notify::Watcher;
+
+        self.ensure_writable()?;
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+            match event {
+                Ok(event) => {
+                    for path in event.paths {
+                        let _ = sender.send(path);
+                    }
+                }
+                Err(e) => log::error!("Watch error: {:?}", e),
+            }
+        })?;
+        watcher.watch(&dir_path, notify::RecursiveMode::Recursive)?;
+        log::debug!("Watching {} for changes", dir_path.display());
+
+        self.apply_changes(receiver, watch).await?;
+        Ok(())
+    }
+
+    /// Commits batches of changed paths from `changes` until the channel closes, returning
+    /// how many commits were made.
+    async fn apply_changes(
+        &self,
+        changes: tokio::sync::mpsc::UnboundedReceiver<PathBuf>,
+        watch: &WatchConfig
+    ) -> Result<usize, FileEmbeddingError> {
+        watch::batch_changes(changes, watch, |paths| self.commit_changes(paths)).await
+    }
+
+    /// Re-indexes the files among `paths` that exist as `index_directory` would, into the
+    /// collection they were indexed into, and drops the records of those that don't.
+    ///
+    /// A file that can no longer be indexed, e.g. one edited down to too little content,
+    /// loses its previous record rather than staying searchable with stale content.
+    async fn commit_changes(&self, paths: Vec<PathBuf>) -> Result<(), FileEmbeddingError> {
+        #[derive(Deserialize)]
+        struct StoredCollection {
+            collection: Option<String>,
+        }
+
+        let mut report = IndexReport::default();
+        let mut removed = 0;
+        for path in paths {
+            let Some(path_str) = path.to_str() else {
+                continue;
+            };
+            if !path.exists() {
+                if self.delete_file(path_str).await? {
+                    removed += 1;
+                }
+                continue;
+            }
+            if !path.is_file() {
+                continue;
+            }
+
+            let stored: Vec<StoredCollection> = self.with_retry(|| async {
+                self.db
+                    .query(
+                        "SELECT collection FROM files WHERE path = $path OR parent_path = $path \
+                         LIMIT 1"
+                    )
+                    .bind(("path", path_str)).await?
+                    .take(0)
+            }).await?;
+            let collection = stored.into_iter().next().and_then(|s| s.collection);
+            let outcome = self.index_file_as(path.clone(), collection.as_deref()).await;
+            match &outcome {
+                Ok(_) | Err(FileEmbeddingError::Database(_) | FileEmbeddingError::DuplicatePath(_)) => {}
+                Err(_) => {
+                    if self.delete_file(path_str).await? {
+                        removed += 1;
+                    }
+                }
+            }
+            self.record_outcome(path, outcome, &mut report)?;
+        }
+        self.evict().await?;
+        log::debug!(
+            "Committed changes: {} re-indexed, {} skipped, {} failed, {} removed",
+            report.indexed.len(),
+            report.skipped.len(),
+            report.failed.len(),
+            removed
+        );
+
+        Ok(())
+    }
+
+    /// Makes every write so far durable against power loss, not just a process crash.
+    ///
+    /// SurrealDB has no sync API, so this fsyncs the RocksDB files directly; see
+    /// [`storage`] for the guarantees without it.
+    pub async fn flush(&self) -> Result<(), FileEmbeddingError> {
+        let db_path = self.db_path.clone();
+        let synced = tokio::task
+            ::spawn_blocking(move || storage::sync_directory(&db_path)).await
+            .map_err(std::io::Error::other)??;
+        log::debug!("Flushed {} database files in {}", synced, self.db_path.display());
+        Ok(())
+    }
+
+    /// Maintenance for long-lived indexes: rebuilds every index of the `files` table from
+    /// the current records, then syncs the database files like
+    /// [`FileEmbeddingSystem::flush`].
+    ///
+    /// Search vectors are scored by scanning, so there is no vector index to rebuild. RocksDB
+    /// compaction can't be triggered through SurrealDB; see [`storage`] for when deleted
+    /// records actually free disk space.
+    pub async fn compact(&self) -> Result<CompactionReport, FileEmbeddingError> {
+        self.ensure_writable()?;
+        let bytes_before = storage::directory_size(&self.db_path)?;
+
+        let info: Option<serde_json::Value> = self.with_retry(|| async {
+            self.db.query("INFO FOR TABLE files").await?.take(0)
+        }).await?;
+        let indexes: Vec<(String, String)> = info
+            .as_ref()
+            .and_then(|info| info.get("indexes"))
+            .and_then(|indexes| indexes.as_object())
+            .map(|indexes| {
+                indexes
+                    .iter()
+                    .filter_map(|(name, definition)| {
+                        definition.as_str().map(|definition| (name.clone(), definition.to_string()))
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
+
+        let mut sql = String::from("BEGIN TRANSACTION;");
+        for (name, definition) in &indexes {
+            sql.push_str(&format!(" REMOVE INDEX {} ON files; {};", name, definition));
+        }
+        sql.push_str(" COMMIT TRANSACTION;");
+        self.with_retry(|| async { self.db.query(sql.as_str()).await?.check() }).await?;
+        self.flush().await?;
+
+        let report = CompactionReport {
+            bytes_before,
+            bytes_after: storage::directory_size(&self.db_path)?,
+            indexes_rebuilt: indexes.len(),
+        };
+        log::debug!(
+            "Rebuilt {} indexes, reclaimed {} bytes ({} -> {})",
+            report.indexes_rebuilt,
+            report.reclaimed_bytes(),
+            report.bytes_before,
+            report.bytes_after
+        );
+        Ok(report)
+    }
+
+    /// Returns the `limit` most recently indexed files, newest first.
+    pub async fn recent(&self, limit: usize) -> Result<Vec<FileRecord>> {
+        let mut records: Vec<FileRecord> = self.with_retry(|| async {
+            self.db
+                .query("SELECT * FROM files ORDER BY indexed_at DESC LIMIT $limit")
+                .bind(("limit", limit)).await?
+                .take(0)
+        }).await?;
+        records.iter_mut().for_each(FileRecord::dequantize_embedding);
+
+        Ok(records)
+    }
+
+    /// Returns the record stored for `path`, or `None` if it isn't indexed.
+    pub async fn get_file(&self, path: &str) -> Result<Option<FileRecord>> {
+        let mut records: Vec<FileRecord> = self.with_retry(|| async {
+            self.db
+                .query("SELECT * FROM files WHERE path = $path LIMIT 1")
+                .bind(("path", path)).await?
+                .take(0)
+        }).await?;
+        records.iter_mut().for_each(FileRecord::dequantize_embedding);
+
+        Ok(records.pop())
+    }
+
+    /// Removes the record stored for `path`, or the messages of an mbox archive at `path`,
+    /// returning whether any existed.
+    pub async fn delete_file(&self, path: &str) -> Result<bool, FileEmbeddingError> {
+        self.ensure_writable()?;
+        let deleted: Vec<Record> = self.with_retry(|| async {
+            self.db
+                .query("DELETE files WHERE path = $path OR parent_path = $path RETURN BEFORE")
+                .bind(("path", path)).await?
+                .take(0)
+        }).await?;
+
+        Ok(!deleted.is_empty())
+    }
+
+    /// Moves the record stored for `old_path` to `new_path`, updating its name, extension
+    /// and MIME type but keeping its embeddings, so a renamed file isn't re-embedded.
+    ///
+    /// Returns whether a record was moved. Fails with `DuplicatePath` if `new_path` is
+    /// already indexed. Messages of an mbox archive aren't moved; re-index the archive.
+    pub async fn rename_file(&self, old_path: &str, new_path: &str) -> Result<bool, FileEmbeddingError> {
+        self.ensure_writable()?;
+        if old_path == new_path {
+            return self.is_indexed(old_path).await;
+        }
+        if self.is_indexed(new_path).await? {
+            return Err(FileEmbeddingError::DuplicatePath(new_path.to_string()));
+        }
+
+        let path = Path::new(new_path);
+        let name = path.file_name().unwrap_or_default().to_string_lossy().to_string();
+        let raw_extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_string);
+        let extension = raw_extension.as_deref().map(str::to_lowercase);
+        // Keeps the stored type when the new name doesn't suggest one, e.g. sniffed text
+        let mime_type = mime_guess
+            ::from_path(path)
+            .first()
+            .map(|m| m.to_string());
+        let updated: Vec<Record> = self.with_retry(|| async {
+            self.db
+                .query(
+                    "UPDATE files SET path = $new_path, name = $name, extension = $extension, \
+                     raw_extension = $raw_extension, mime_type = $mime_type ?? mime_type, \
+                     path_tokens = $path_tokens \
+                     WHERE path = $old_path"
+                )
+                .bind(("old_path", old_path))
+                .bind(("new_path", new_path))
+                .bind(("name", name.as_str()))
+                .bind(("extension", extension.as_deref()))
+                .bind(("raw_extension", raw_extension.as_deref()))
+                .bind(("mime_type", mime_type.as_deref()))
+                .bind(("path_tokens", self.path_tokens(new_path))).await?
+                .take(0)
+        }).await?;
+
+        Ok(!updated.is_empty())
+    }
+
+    /// Removes records whose file no longer exists on disk, returning the pruned paths.
+    ///
+    /// Records created with [`FileEmbeddingSystem::index_content`] are never pruned.
+    pub async fn prune_missing(&self) -> Result<Vec<PathBuf>, FileEmbeddingError> {
+        self.ensure_writable()?;
+        #[derive(Deserialize)]
+        struct PathRow {
+            path: String,
+            parent_path: Option<String>,
+        }
+
+        let rows: Vec<PathRow> = self.with_retry(|| async {
+            self.db
+                .query("SELECT path, parent_path FROM files WHERE is_virtual = false").await?
+                .take(0)
+        }).await?;
+
+        let mut pruned = Vec::new();
+        for row in rows {
+            // Messages of an mbox archive go when the archive does
+            let source = row.parent_path.unwrap_or(row.path);
+            let path = PathBuf::from(&source);
+            if !path.exists() && self.delete_file(&source).await? {
+                log::debug!("Pruned missing file: {}", path.display());
+                pruned.push(path);
+            }
+        }
+
+        Ok(pruned)
+    }
+
+    /// Scans every stored record for bad embeddings, missing files and duplicates.
+    ///
+    /// Only reads the index; see [`FileEmbeddingSystem::repair_index`] to act on the report.
+    pub async fn verify_index(&self) -> Result<VerifyReport, FileEmbeddingError> {
+        Ok(self.verify_records().await?.1)
+    }
+
+    async fn verify_records(&self) -> Result<(Vec<FileRecord>, VerifyReport), FileEmbeddingError> {
+        let mut records: Vec<FileRecord> = self.with_retry(|| async {
+            self.db.query("SELECT * FROM files").await?.take(0)
+        }).await?;
+        records.iter_mut().for_each(FileRecord::dequantize_embedding);
+
+        let mut report = VerifyReport {
+            checked: records.len(),
+            ..Default::default()
+        };
+        let dimension = self.stored_dimension();
+        for record in &records {
+            for issue in verify::embedding_issues(record, dimension) {
+                report.issues.push((record.path.clone(), issue));
+            }
+            let source = record.parent_path.as_deref().unwrap_or(&record.path);
+            if !record.is_virtual && !Path::new(source).exists() {
+                report.issues.push((record.path.clone(), IndexIssue::MissingFile));
+            }
+        }
+        report.issues.extend(verify::duplicate_issues(&records));
+        log::debug!("Verified {} records, {} issues", report.checked, report.issues.len());
+
+        Ok((records, report))
+    }
+
+    /// Runs [`FileEmbeddingSystem::verify_index`] and re-embeds or drops the flagged
+    /// records according to `options`.
+    pub async fn repair_index(
+        &self,
+        options: &RepairOptions
+    ) -> Result<RepairReport, FileEmbeddingError> {
+        self.ensure_writable()?;
+        let (records, verified) = self.verify_records().await?;
+        let records: HashMap<&str, &FileRecord> = records
+            .iter()
+            .map(|record| (record.path.as_str(), record))
+            .collect();
+
+        let mut report = RepairReport::default();
+        let mut handled = HashSet::new();
+        for (path, issue) in &verified.issues {
+            if handled.contains(path) {
+                continue;
+            }
+            let drop = match issue {
+                IndexIssue::MissingFile => options.prune_missing,
+                IndexIssue::DuplicateContent { .. } => options.drop_duplicates,
+                _ => {
+                    let reembedded = match records.get(path.as_str()) {
+                        Some(record) if options.reembed => {
+                            self.reembed(record).await.unwrap_or_else(|e| {
+                                log::warn!("Error re-embedding {}: {:?}", path, e);
+                                false
+                            })
+                        }
+                        _ => false,
+                    };
+                    if reembedded {
+                        report.reembedded.push(path.clone());
+                        handled.insert(path);
+                        continue;
+                    }
+                    true
+                }
+            };
+            if drop && self.delete_file(path).await? {
+                report.dropped.push(path.clone());
+                handled.insert(path);
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Re-embeds `record` from its file, its mbox archive or its stored content, returning
+    /// false when there is nothing left to embed it from.
+    async fn reembed(&self, record: &FileRecord) -> Result<bool, FileEmbeddingError> {
+        let collection = record.collection.as_deref();
+        if let Some(parent) = &record.parent_path {
+            if !Path::new(parent).exists() {
+                return Ok(false);
+            }
+            self.index_mbox(Path::new(parent), collection).await?;
+            return Ok(true);
+        }
+
+        let mut fresh = if record.is_virtual {
+            let Some(content) = &record.content else {
+                return Ok(false);
+            };
+            let mut fresh = record.clone();
+            fresh.strip_embeddings();
+            self.embed_into(&mut fresh, content)?;
+            fresh
+        } else {
+            if !Path::new(&record.path).exists() {
+                return Ok(false);
+            }
+            self.prepare_record(Path::new(&record.path)).await?
+        };
+        fresh.collection = record.collection.clone();
+        self.upsert(&fresh).await?;
+        log::debug!("Re-embedded: {}", record.path);
+        Ok(true)
+    }
+
+    /// Removes every record matching `filter` server-side and returns how many were deleted.
+    ///
+    /// An empty filter is rejected rather than treated as "delete everything".
+    pub async fn delete_by_filter(&self, filter: SearchFilter) -> Result<usize, FileEmbeddingError> {
+        self.ensure_writable()?;
+        let Some(condition) = filter.where_clause() else {
+            return Err(
+                FileEmbeddingError::InvalidFilter(
+                    String::from("refusing to delete with an empty filter")
+                )
+            );
+        };
+
+        let sql = format!("DELETE files WHERE {} RETURN BEFORE", condition);
+        let deleted: Vec<Record> = self.with_retry(|| async {
+            filter.bind(self.db.query(sql.as_str())).await?.take(0)
+        }).await?;
+
+        log::debug!("Deleted {} records matching {:?}", deleted.len(), filter);
+        Ok(deleted.len())
+    }
+
+    /// Stopwords for `config.language`, or none if it isn't set.
+    fn stopwords(&self) -> &'static [&'static str] {
+        self.config.language
+            .as_deref()
+            .and_then(stopwords::for_language)
+            .unwrap_or(&[])
+    }
+
+    /// Picks the `preview_chars` window of `content` closest to the query embedding, using
+    /// the preview length configured for `extension`, plus `context_chunks` windows on either
+    /// side.
+    ///
+    /// Uses the chunk embeddings stored at index time when they line up with the windows,
+    /// and only embeds the windows itself for records indexed before they were stored (or
+    /// with a different `preview_chars`).
+    fn best_match_preview(
+        &self,
+        content: &str,
+        extension: Option<&str>,
+        chunk_embeddings: &[Vec<f32>],
+        query_embedding: &[f32],
+        context_chunks: usize
+    ) -> Result<String> {
+        let preview_chars = self.config.preview_chars_for(extension);
+        let windows = preview::windows(content, preview_chars);
+        if windows.len() <= 1 {
+            return Ok(preview::head(content, preview_chars));
+        }
+
+        let embedded;
+        let embeddings = if chunk_embeddings.len() == windows.len() {
+            chunk_embeddings
+        } else {
+            let texts: Vec<&str> = windows.iter().map(String::as_str).collect();
+            embedded = self.embed_stored(&texts)?;
+            &embedded[..]
+        };
+        let scores: Vec<f32> = embeddings
+            .iter()
+            .map(|embedding| cosine_similarity(embedding, query_embedding))
+            .collect();
+
+        Ok(
+            preview::best_window(&scores)
+                .map(|i| preview::with_context(&windows, i, context_chunks))
+                .unwrap_or_default()
+        )
+    }
+
+    pub async fn hybrid_search(&self, query: &str, limit: usize) -> Result<Vec<SearchResult>> {
+        self.search(query, &SearchOptions::new(limit)).await
+    }
+
+    /// Searches with a query string that may embed filters, e.g. `ext:rs size:>1kb "parsing"`.
+    ///
+    /// Only the text left over after removing filters is embedded; see [`query`].
+    pub async fn search_query(&self, input: &str, limit: usize) -> Result<Vec<SearchResult>> {
+        let parsed = parse_query(input)?;
+        log::debug!("Parsed query: {:?}", parsed);
+
+        let options = SearchOptions {
+            filter: parsed.filter,
+            ..SearchOptions::new(limit)
+        };
+        self.search(&parsed.text, &options).await
+    }
+
+    pub async fn search(&self, query: &str, options: &SearchOptions) -> Result<Vec<SearchResult>> {
+        let query_vectors = self.embed_queries(vec![query.to_string()])?.remove(0);
+        log::debug!("Query embedding size: {}", query_vectors.primary.len());
+
+        let results = if self.scores_in_database() {
+            self.rank_in_database(&query_vectors, options).await?
+        } else {
+            let records = self.fetch_candidates(&options.filter).await?;
+            self.rank(&records, &query_vectors, options)?
+        };
+        self.record_matches(&results).await?;
+        Ok(results)
+    }
+
+    /// Dimension of the vectors the primary model produces, and that
+    /// [`FileEmbeddingSystem::search_by_embedding`] expects.
+    pub fn embedding_dim(&self) -> usize {
+        self.embedder.dimension()
+    }
+
+    /// Ranks files against a caller-supplied vector instead of an embedded query, e.g. a
+    /// cached query embedding or the centroid of several files' embeddings.
+    ///
+    /// `embedding` must come from the primary model's space and have
+    /// [`FileEmbeddingSystem::embedding_dim`] dimensions; it is reduced like a query if a
+    /// projection has been fitted. Ensemble models are left out of the score.
+    pub async fn search_by_embedding(
+        &self,
+        embedding: &[f32],
+        limit: usize
+    ) -> Result<Vec<SearchResult>> {
+        if embedding.len() != self.embedding_dim() {
+            return Err(
+                FileEmbeddingError::InvalidQuery(
+                    format!(
+                        "Embedding has {} dimensions, but the model produces {}",
+                        embedding.len(),
+                        self.embedding_dim()
+                    )
+                ).into()
+            );
+        }
+        validate_embedding("query", embedding)?;
+
+        let query_vectors = QueryVectors {
+            text: String::new(),
+            primary: match self.reduction() {
+                Some(projection) => projection.project(embedding),
+                None => embedding.to_vec(),
+            },
+            ensemble: Vec::new(),
+        };
+        let options = SearchOptions::new(limit);
+        let results = if self.scores_in_database() {
+            self.rank_in_database(&query_vectors, &options).await?
+        } else {
+            let records = self.fetch_candidates(&options.filter).await?;
+            self.rank(&records, &query_vectors, &options)?
+        };
+        self.record_matches(&results).await?;
+        Ok(results)
+    }
+
+    /// Like [`FileEmbeddingSystem::hybrid_search`], but hands results to `visitor` one at a
+    /// time, best first, and stops as soon as it returns [`ControlFlow::Break`].
+    ///
+    /// Previews are computed per result just before it is visited, so results after the
+    /// break never pay for them. Returns the number of results visited.
+    pub async fn hybrid_search_visit<F>(
+        &self,
+        query: &str,
+        limit: usize,
+        mut visitor: F
+    ) -> Result<usize>
+        where F: FnMut(SearchResult) -> ControlFlow<()>
+    {
+        let options = SearchOptions::new(limit);
+        let query_vectors = self.embed_queries(vec![query.to_string()])?.remove(0);
+        let results = if self.scores_in_database() {
+            self.score_in_database(&query_vectors, &options).await?
+        } else {
+            let records = self.fetch_candidates(&options.filter).await?;
+            self.score_candidates(&records, &query_vectors, &options)
+        };
+
+        let mut visited = 0;
+        for mut result in results.iter().cloned() {
+            self.finish_result(&mut result, &query_vectors, &options)?;
+            visited += 1;
+            if visitor(result).is_break() {
+                break;
+            }
+        }
+        self.record_matches(&results[..visited]).await?;
+        Ok(visited)
+    }
+
+    /// Like [`FileEmbeddingSystem::search`], but returns lightweight [`SearchHit`]s.
+    pub async fn search_hits(&self, query: &str, options: &SearchOptions) -> Result<Vec<SearchHit>> {
+        Ok(
+            self
+                .search(query, options).await?
+                .into_iter()
+                .map(|result| {
+                    let mut hit = SearchHit::from(result);
+                    if let Some(decimals) = options.score_decimals {
+                        hit.score = search::round_score(hit.score, decimals);
+                    }
+                    hit
+                })
+                .collect()
+        )
+    }
+
+    /// Like [`FileEmbeddingSystem::search`], but with results grouped by file extension.
+    ///
+    /// `options.limit` caps the total number of results across all groups.
+    pub async fn search_grouped(
+        &self,
+        query: &str,
+        options: &SearchOptions
+    ) -> Result<Vec<(String, Vec<SearchResult>)>> {
+        Ok(group_by_extension(self.search(query, options).await?))
+    }
+
+    /// Groups indexed files whose stored embeddings are all pairwise more similar than
+    /// `threshold`, returning each cluster as a list of paths.
+    ///
+    /// Useful for spotting duplicate or closely related documents; files with nothing
+    /// similar come back as single-element clusters. See [`cluster::cluster_embeddings`].
+    pub async fn cluster(&self, threshold: f32) -> Result<Vec<Vec<String>>> {
+        let items: Vec<(String, Vec<f32>)> = self
+            .fetch_candidates(&SearchFilter::default()).await?
+            .into_iter()
+            .map(|record| (record.path, record.content_embedding))
+            .collect();
+
+        Ok(cluster::cluster_embeddings(&items, threshold))
+    }
+
+    /// Runs several searches at once, embedding all queries in a single batch.
+    ///
+    /// Each query is scored the way [`FileEmbeddingSystem::hybrid_search`] would score it:
+    /// by SurrealDB when scoring server-side, otherwise against a corpus fetched once for
+    /// all queries. Results are returned in the same order as `queries`.
+    pub async fn hybrid_search_many(
+        &self,
+        queries: &[String],
+        limit: usize
+    ) -> Result<Vec<Vec<SearchResult>>> {
+        if queries.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let options = SearchOptions::new(limit);
+        let query_vectors = self.embed_queries(queries.to_vec())?;
+        let mut results = Vec::with_capacity(query_vectors.len());
+        if self.scores_in_database() {
+            for query in &query_vectors {
+                results.push(self.rank_in_database(query, &options).await?);
+            }
+        } else {
+            let records = self.fetch_candidates(&options.filter).await?;
+            for query in &query_vectors {
+                results.push(self.rank(&records, query, &options)?);
+            }
+        }
+        for query_results in &results {
+            self.record_matches(query_results).await?;
+        }
+        Ok(results)
+    }
+
+    /// Searches like [`FileEmbeddingSystem::search`] with a `min_score` threshold, but never
+    /// dead-ends when nothing clears it.
+    ///
+    /// If no result scores at least `min_score`, the threshold is halved and files whose names
+    /// fuzzily match a query term are added. Those results are flagged `low_confidence`, and
+    /// `suggestions` lists indexed words close to query terms the corpus doesn't contain.
+    pub async fn search_with_fallback(
+        &self,
+        query: &str,
+        limit: usize,
+        min_score: f32
+    ) -> Result<FallbackResults> {
+        let query_vectors = self.embed_queries(vec![query.to_string()])?.remove(0);
+        let records = self.fetch_candidates(&SearchFilter::default()).await?;
+
+        let options = SearchOptions {
+            min_score: Some(min_score),
+            ..SearchOptions::new(limit)
+        };
+        let results = self.rank(&records, &query_vectors, &options)?;
+        if !results.is_empty() {
+            self.record_matches(&results).await?;
+            return Ok(FallbackResults {
+                results,
+                suggestions: Vec::new(),
+                used_fallback: false,
+            });
+        }
+
+        log::debug!("No results scored above {:.4}, falling back", min_score);
+        let loosened = SearchOptions {
+            min_score: Some(min_score / 2.0),
+            ..SearchOptions::new(limit)
+        };
+        let mut results = self.rank(&records, &query_vectors, &loosened)?;
+
+        let stopwords = self.stopwords();
+        let terms: Vec<String> = fallback
+            ::words(query)
+            .filter(|term| !stopwords.contains(&term.as_str()))
+            .collect();
+        let metric = self.config.metric;
+        for record in &records {
+            let already_found = results.iter().any(|r| r.file.path == record.path);
+            if !already_found && fallback::name_matches(&record.name, &terms) {
+                let mut file = record.clone();
+                file.strip_embeddings();
+                results.push(SearchResult {
+                    file,
+                    score: self.score_record(record, &query_vectors, metric),
+                    low_confidence: true,
+                    snippet: None,
+                });
+            }
+        }
+
+        results.iter_mut().for_each(|result| {
+            result.low_confidence = true;
+        });
+        results.sort_by(|a, b| self.config.tie_breaker.compare(a, b));
+        results.truncate(limit);
+
+        let suggestions = fallback::suggest_terms(
+            &terms,
+            records.iter().map(|record| record.content_preview.as_str())
+        );
+        log::debug!("Fallback found {} results, suggestions: {:?}", results.len(), suggestions);
+        self.record_matches(&results).await?;
+
+        Ok(FallbackResults {
+            results,
+            suggestions,
+            used_fallback: true,
+        })
+    }
+
+    /// Condition selecting the records a search may score: embedded ones matching `filter`.
+    fn candidate_condition(filter: &SearchFilter) -> String {
+        match filter.where_clause() {
+            Some(condition) => format!("embedded = true AND {}", condition),
+            None => String::from("embedded = true"),
+        }
+    }
+
+    /// Loads every record matching `filter` for scoring.
+    async fn fetch_candidates(&self, filter: &SearchFilter) -> Result<Vec<FileRecord>> {
+        // Get all records matching the filter first to help debug
+        let sql = format!("SELECT * FROM files WHERE {}", Self::candidate_condition(filter));
+        let mut records: Vec<FileRecord> = self.with_retry(|| async {
+            filter.bind(self.db.query(sql.as_str())).await?.take(0)
+        }).await?;
+        records.iter_mut().for_each(FileRecord::dequantize_embedding);
+        log::debug!("Total records in DB: {}", records.len());
+
+        Ok(records)
+    }
+
+    /// Embeds `texts` with the primary model into the space of stored vectors, i.e. reduced
+    /// if a projection has been fitted.
+    fn embed_stored(&self, texts: &[&str]) -> Result<Vec<Vec<f32>>> {
+        let embeddings = embed_checked(&*self.embedder, texts, self.config.embed_batch_size)?;
+        Ok(match self.reduction() {
+            Some(projection) => embeddings
+                .iter()
+                .map(|embedding| projection.project(embedding))
+                .collect(),
+            None => embeddings,
+        })
+    }
+
+    /// Embeds queries with the primary model and every ensemble model, batching each model.
+    fn embed_queries(&self, queries: Vec<String>) -> Result<Vec<QueryVectors>> {
+        let texts: Vec<&str> = queries.iter().map(String::as_str).collect();
+        let primary = self.embed_stored(&texts)?;
+        let mut per_model = Vec::with_capacity(self.ensemble.len());
+        for member in &self.ensemble {
+            per_model.push(embed_checked(&member.model, &texts, self.config.embed_batch_size)?);
+        }
+
+        Ok(
+            primary
+                .into_iter()
+                .zip(queries)
+                .enumerate()
+                .map(|(i, (primary, text))| QueryVectors {
+                    text,
+                    primary,
+                    ensemble: per_model
+                        .iter()
+                        .map(|embeddings| embeddings[i].clone())
+                        .collect(),
+                })
+                .collect()
+        )
+    }
+
+    /// Scores a record, fusing the primary and ensemble model scores by weight.
+    fn score_record(&self, record: &FileRecord, query: &QueryVectors, metric: Metric) -> f32 {
+        let primary = metric.score(&record.content_embedding, &query.primary);
+        if self.ensemble.is_empty() {
+            return primary;
+        }
+
+        let ensemble_scores = self.ensemble
+            .iter()
+            .zip(&query.ensemble)
+            .map(|(member, query_embedding)| {
+                let score = record.embeddings
+                    .get(&member.name)
+                    .map(|embedding| metric.score(embedding, query_embedding));
+                (member.weight, score)
+            });
+        ensemble::fuse_scores(
+            std::iter::once((self.config.primary_weight, Some(primary))).chain(ensemble_scores)
+        )
+    }
+
+    /// Scores `records` against a query and returns the top `options.limit`.
+    fn rank(
+        &self,
+        records: &[FileRecord],
+        query: &QueryVectors,
+        options: &SearchOptions
+    ) -> Result<Vec<SearchResult>> {
+        let results = self.score_candidates(records, query, options);
+        self.finish_results(results, query, options)
+    }
+
+    /// The top `options.limit` of `records`, best first, without query-time previews.
+    fn score_candidates(
+        &self,
+        records: &[FileRecord],
+        query: &QueryVectors,
+        options: &SearchOptions
+    ) -> Vec<SearchResult> {
+        let metric = options.metric.unwrap_or(self.config.metric);
+        let boost_terms = self.path_boost_terms(&query.text);
+
+        // Compute similarities in Rust instead of relying on SurrealDB's vector operations
+        let scored = records
+            .iter()
+            .enumerate()
+            .map(|(i, record)| {
+                let score =
+                    self.score_record(record, query, metric) +
+                    self.config.path_token_boost *
+                        paths::token_overlap(&record.path_tokens, &boost_terms);
+                (i, score, self.config.tie_breaker.key(record))
+            })
+            .filter(|(_, score, _)| options.min_score.is_none_or(|min_score| *score >= min_score));
+
+        // Keep only the requested number of results
+        let scored = search::top_k(scored, options.limit);
+
+        scored
+            .into_iter()
+            .map(|(i, score)| SearchResult {
+                file: records[i].clone(),
+                score,
+                low_confidence: false,
+                snippet: None,
+            })
+            .collect()
+    }
+
+    /// Distinct query words matched against `path_tokens`, or none when path tokens
+    /// aren't indexed or boosted.
+    fn path_boost_terms(&self, query: &str) -> Vec<String> {
+        if !self.config.index_path_tokens || self.config.path_token_boost == 0.0 {
+            return Vec::new();
+        }
+        let stopwords = self.stopwords();
+        let mut terms: Vec<String> = fallback
+            ::words(query)
+            .filter(|term| !stopwords.contains(&term.as_str()))
+            .collect();
+        terms.sort();
+        terms.dedup();
+        terms
+    }
+
+    /// Whether [`FileEmbeddingSystem::search`] can score with SurrealDB's vector functions.
+    ///
+    /// Quantized records have no `content_embedding` to compare and ensemble scores are
+    /// fused in Rust, so both need the in-Rust path.
+    fn scores_in_database(&self) -> bool {
+        self.config.server_side_scoring &&
+            self.config.embedding_precision == EmbeddingPrecision::F32 &&
+            self.ensemble.is_empty()
+    }
+
+    /// Like [`FileEmbeddingSystem::rank`], but scores and sorts inside SurrealDB so only the
+    /// top `options.limit` rows are transferred.
+    async fn rank_in_database(
+        &self,
+        query: &QueryVectors,
+        options: &SearchOptions
+    ) -> Result<Vec<SearchResult>> {
+        let results = self.score_in_database(query, options).await?;
+        self.finish_results(results, query, options)
+    }
+
+    /// Like [`FileEmbeddingSystem::score_candidates`], but scored inside SurrealDB.
+    async fn score_in_database(
+        &self,
+        query: &QueryVectors,
+        options: &SearchOptions
+    ) -> Result<Vec<SearchResult>> {
+        #[derive(Deserialize)]
+        struct ScoredRow {
+            file: FileRecord,
+            /// `NONE` when the score is NaN, e.g. against a zero vector.
+            score: Option<f32>,
+        }
+
+        let metric = options.metric.unwrap_or(self.config.metric);
+        let boost_terms = self.path_boost_terms(&query.text);
+        let mut score = metric.surreal_expression().to_string();
+        if !boost_terms.is_empty() {
+            score = format!(
+                "({}) + $path_boost * array::len(array::intersect(path_tokens, $path_terms)) \
+                 / $path_term_count",
+                score
+            );
+        }
+        // SurrealDB's vector functions fail the whole query on a dimension mismatch, where
+        // scoring in Rust gives such a record 0, so leave stale vectors out up front
+        let sql = format!(
+            "SELECT $this AS file, path, name, {} AS score FROM files \
+             WHERE array::len(content_embedding) = $dim AND {} \
+             ORDER BY score DESC, {} LIMIT $limit",
+            score,
+            Self::candidate_condition(&options.filter),
+            self.config.tie_breaker.surreal_order()
+        );
+
+        let rows: Vec<ScoredRow> = self.with_retry(|| async {
+            options.filter
+                .bind(self.db.query(sql.as_str()))
+                .bind(("query_embedding", query.primary.clone()))
+                .bind(("dim", query.primary.len()))
+                .bind(("path_boost", self.config.path_token_boost))
+                .bind(("path_terms", boost_terms.clone()))
+                .bind(("path_term_count", boost_terms.len() as f64))
+                .bind(("limit", options.limit)).await?
+                .take(0)
+        }).await?;
+
+        Ok(
+            rows
+                .into_iter()
+                .map(|row| SearchResult {
+                    file: row.file,
+                    score: row.score.map_or(0.0, finite_or_zero),
+                    low_confidence: false,
+                    snippet: None,
+                })
+                // Rows arrive sorted, so dropping weak ones keeps the top results intact
+                .filter(|result| options.min_score.is_none_or(|min_score| result.score >= min_score))
+                .collect()
+        )
+    }
+
+    /// Applies `options.auto_threshold`, then [`FileEmbeddingSystem::finish_result`] to each
+    /// ranked result, and logs them.
+    fn finish_results(
+        &self,
+        mut results: Vec<SearchResult>,
+        query: &QueryVectors,
+        options: &SearchOptions
+    ) -> Result<Vec<SearchResult>> {
+        Self::apply_auto_threshold(&mut results, options);
+        for result in results.iter_mut() {
+            self.finish_result(result, query, options)?;
+        }
+
+        log::debug!("Found {} results", results.len());
+        for result in results.iter() {
+            log::debug!("Path: {}, Score: {}", result.file.path, result.score);
+        }
+
+        Ok(results)
+    }
+
+    fn apply_auto_threshold(results: &mut Vec<SearchResult>, options: &SearchOptions) {
+        if let Some(auto_threshold) = &options.auto_threshold {
+            let scores: Vec<f32> = results
+                .iter()
+                .map(|result| result.score)
+                .collect();
+            results.truncate(auto_threshold.cutoff(&scores));
+        }
+    }
+
+    /// Computes the query-time preview and snippet of a ranked result and drops its vectors
+    /// unless `options` asks for them.
+    fn finish_result(
+        &self,
+        result: &mut SearchResult,
+        query: &QueryVectors,
+        options: &SearchOptions
+    ) -> Result<()> {
+        if let Some(content) = &result.file.content {
+            let sentence = aggregate::best_sentence(
+                content,
+                &result.file.sentence_embeddings,
+                &query.primary
+            );
+            if let Some(sentence) = sentence {
+                let preview_chars = self.config.preview_chars_for(result.file.extension.as_deref());
+                result.file.content_preview = preview::head(sentence, preview_chars);
+            } else if self.config.preview_strategy == PreviewStrategy::BestMatch {
+                result.file.content_preview = self.best_match_preview(
+                    content,
+                    result.file.extension.as_deref(),
+                    &result.file.chunk_embeddings,
+                    &query.primary,
+                    options.context_chunks
+                )?;
+            }
+        }
+
+        if !options.include_embeddings {
+            result.file.strip_embeddings();
+        }
+        if let Some(highlight) = &options.highlight {
+            result.snippet = Some(
+                highlight.apply_excluding(&result.file.content_preview, &query.text, self.stopwords())
+            );
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir; // Add tempfile to your dependencies
+    use fastembed::EmbeddingModel;
+
+    async fn setup_test_system() -> (FileEmbeddingSystem, TempDir) {
+        setup_test_system_with_config(FileEmbeddingConfig::default()).await
+    }
+
+    async fn setup_test_system_with_config(
+        config: FileEmbeddingConfig
+    ) -> (FileEmbeddingSystem, TempDir) {
+        // Create a temporary directory for the database
+        let temp_dir = TempDir::new().expect("Failed to create temp dir");
+        let db_path = temp_dir.path().join("test_db");
+
+        let system = FileEmbeddingSystem::with_config(db_path.to_str().unwrap(), config).await.expect(
+            "Failed to create FileEmbeddingSystem"
+        );
+
+        (system, temp_dir)
+    }
+
+    /// Opens `db_path` read-only once the previous connection has let go of it.
+    async fn reopen_read_only(db_path: &str) -> Result<FileEmbeddingSystem> {
+        reopen_with(|| FileEmbeddingSystem::open_read_only(db_path)).await
+    }
+
+    /// Calls `open` until the previous connection has let go of the database.
+    async fn reopen_with<F, Fut>(open: F) -> Result<FileEmbeddingSystem>
+        where F: Fn() -> Fut, Fut: Future<Output = Result<FileEmbeddingSystem>>
+    {
+        // RocksDB releases its lock once the dropped connection's background task finishes
+        let mut attempts = 0;
+        loop {
+            match open().await {
+                Ok(system) => return Ok(system),
+                Err(e) if attempts < 20 => {
+                    println!("Waiting for database lock: {}", e);
+                    attempts += 1;
+                    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_file_indexing_and_search() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+
+        // Create a test file
+        let test_dir = TempDir::new()?;
+        let test_file_path = test_dir.path().join("test.txt");
+
+        let test_content =
+            r#"Rust is a systems programming language that runs blazingly fast, prevents segfaults, 
+        and guarantees thread safety. Features include zero-cost abstractions, move semantics,
+        guaranteed memory safety, threads without data races, trait-based generics,
+        pattern matching, type inference, minimal runtime, and efficient C bindings."#;
+
+        fs::write(&test_file_path, test_content)?;
+
+        // Test file indexing
+        system.index_file(test_file_path).await?;
+
+        // Test search functionality
+        let search_terms = [
+            ("rust programming", true),
+            ("memory safety", true),
+            ("python scripting", false), // Should not match strongly
+        ];
+
+        for (term, should_match) in search_terms {
+            let results = system.hybrid_search(term, 5).await?;
+
+            if should_match {
+                assert!(!results.is_empty(), "Expected matches for term: {}", term);
+                if !results.is_empty() {
+                    assert!(
+                        results[0].score > 0.0,
+                        "Expected positive score for matching term: {}, got: {}",
+                        term,
+                        results[0].score
+                    );
+                }
+            } else {
+                assert!(
+                    results.is_empty() || results[0].score < 0.1,
+                    "Expected low/no score for non-matching term: {}",
+                    term
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pdf_handling() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+
+        // Create a test PDF file
+        let test_dir = TempDir::new()?;
+        let pdf_path = test_dir.path().join("test.pdf");
+
+        // You might want to create a small PDF file here for testing
+        // For now, we'll just test that it properly rejects non-PDF files
+        fs::write(&pdf_path, b"Not a real PDF file")?;
+
+        let result = system.index_file(pdf_path).await;
+        assert!(result.is_err(), "Should fail on invalid PDF file");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_file_types() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+
+        // Create a test file with unsupported extension
+        let test_dir = TempDir::new()?;
+        let test_file = test_dir.path().join("test.xyz");
+        fs::write(&test_file, "Some content")?;
+
+        let result = system.index_file(test_file).await;
+        assert!(matches!(result, Err(FileEmbeddingError::UnsupportedFileType(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_directory_indexing() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+
+        // Create a test directory structure
+        let test_dir = TempDir::new()?;
+
+        let files = [
+            ("test1.txt", "Rust programming content"),
+            ("test2.md", "Python scripting guide"),
+            ("subfolder/test3.txt", "More Rust content"),
+        ];
+
+        println!("Creating test files...");
+        for (path, content) in files {
+            let full_path = test_dir.path().join(path);
+            if let Some(parent) = full_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            fs::write(&full_path, content)?;
+            println!("Created file: {} with content: {}", full_path.display(), content);
+        }
+
+        // Test directory indexing
+        println!("Indexing directory...");
+        system.index_directory(test_dir.path().to_path_buf()).await?;
+
+        // Verify files are in the database with a proper count query
+        #[derive(Debug, Deserialize)]
+        struct CountResult {
+            count: i64,
+        }
+
+        let count: Vec<CountResult> = system.db
+            .query("SELECT count() as count FROM files").await?
+            .take(0)?;
+
+        println!(
+            "Files in database: {}",
+            count.first().map_or(0, |c| c.count)
+        );
+
+        // Print all files in database
+        let all_files: Vec<FileRecord> = system.db.query("SELECT * FROM files").await?.take(0)?;
+        println!("Files in database:");
+        for file in &all_files {
+            println!("- {} ({})", file.name, file.content_preview);
+        }
+
+        // Now test search
+        println!("\nSearching for 'rust'...");
+        let rust_results = system.hybrid_search("rust", 5).await?;
+        println!("\nSearching for 'python'...");
+        let python_results = system.hybrid_search("python", 5).await?;
+
+        assert!(
+            rust_results.len() >= 2,
+            "Should find at least 2 Rust-related files. Found: {}. Results: {:?}",
+            rust_results.len(),
+            rust_results
+        );
+        assert!(
+            !python_results.is_empty(),
+            "Should find at least 1 Python-related file. Found: {}. Results: {:?}",
+            python_results.len(),
+            python_results
+        );
+
+        Ok(())
+    }
+    
+    #[tokio::test]
+    async fn test_search_quality() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+
+        println!("\n=== Starting Search Quality Test ===");
+
+        // Create test files with more varied content
+        let test_files = [
+            (
+                "rust_doc.txt",
+                "Rust is a systems programming language focused on safety, speed, and concurrency. It prevents segfaults and ensures thread safety.",
+            ),
+            (
+                "python_doc.txt",
+                "Python is a high-level programming language known for its simplicity and readability. Great for data science.",
+            ),
+            (
+                "mixed_doc.txt",
+                "While Python is great for rapid development, Rust provides memory safety without garbage collection.",
+            ),
+            ("unrelated.txt", "The weather is nice today. Birds are singing in the trees."),
+        ];
+
+        // Index all files
+        println!("\nIndexing test files:");
+        for (name, content) in test_files {
+            println!("\n--- Indexing {} ---\nContent: {}", name, content);
+            let test_dir = TempDir::new()?;
+            let file_path = test_dir.path().join(name);
+            fs::write(&file_path, content)?;
+            system.index_file(file_path).await?;
+        }
+
+        // Verify indexed content
+        let all_files: Vec<FileRecord> = system.db.query("SELECT * FROM files").await?.take(0)?;
+        println!("\nVerifying indexed files:");
+        for file in &all_files {
+            println!(
+                "\nFile: {}\nPreview: {}\nEmbedding size: {}",
+                file.name,
+                file.content_preview,
+                file.content_embedding.len()
+            );
+        }
+
+        // Test different search queries with detailed output
+        let search_tests = [
+            ("rust safety", vec!["rust_doc.txt", "mixed_doc.txt"]),
+            ("python data", vec!["python_doc.txt"]),
+            ("programming language", vec!["rust_doc.txt", "python_doc.txt"]),
+            ("weather", vec!["unrelated.txt"]),
+        ];
+
+        println!("\n=== Running Search Tests ===");
+        for (query, expected_files) in search_tests {
+            println!("\n--- Testing search for: '{}' ---", query);
+            println!("Expected files: {:?}", expected_files);
+
+            let results = system.hybrid_search(query, 5).await?;
+
+            println!("\nResults (in order of relevance):");
+            for (i, result) in results.iter().enumerate() {
+                println!(
+                    "{}. {} (Score: {:.4})\n   Preview: {}",
+                    i + 1,
+                    result.file.name,
+                    result.score,
+                    result.file.content_preview.chars().take(50).collect::<String>()
+                );
+            }
+
+            // Verification with debug output
+            let result_files: Vec<&str> = results
+                .iter()
+                .map(|r| r.file.name.as_str())
+                .collect();
+
+            for expected in &expected_files {
+                let found = result_files.contains(expected);
+                println!("Checking for {}: {}", expected, if found {
+                    "FOUND ✓"
+                } else {
+                    "NOT FOUND ✗"
+                });
+                assert!(
+                    found,
+                    "Expected to find {} in results for query '{}', but got {:?}",
+                    expected,
+                    query,
+                    result_files
+                );
+            }
+
+            // Verify scores
+            if let Some(first_score) = results.first().map(|r| r.score) {
+                println!("\nScore analysis:");
+                println!("Top score: {:.4}", first_score);
+                println!(
+                    "Score range: {:.4} to {:.4}",
+                    results
+                        .iter()
+                        .map(|r| r.score)
+                        .fold(f32::INFINITY, f32::min),
+                    results
+                        .iter()
+                        .map(|r| r.score)
+                        .fold(f32::NEG_INFINITY, f32::max)
+                );
+            }
+        }
+
+        println!("\n=== Search Quality Test Complete ===");
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_edge_cases() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+
+        // Create and index a test file
+        let test_dir = TempDir::new()?;
+        let file_path = test_dir.path().join("test.txt");
+        fs::write(&file_path, "Sample content for testing")?;
+        system.index_file(file_path).await?;
+
+        // Test empty query
+        let results = system.hybrid_search("", 5).await?;
+        assert!(!results.is_empty(), "Should handle empty queries gracefully");
+
+        // Test very long query
+        let long_query = "a".repeat(1000);
+        let results = system.hybrid_search(&long_query, 5).await?;
+        assert!(results[0].score >= -1.0 && results[0].score <= 1.0, "Should handle long queries");
+
+        // Test special characters
+        let special_query = "!@#$%^&*()";
+        let results = system.hybrid_search(special_query, 5).await?;
+        assert!(
+            results[0].score >= -1.0 && results[0].score <= 1.0,
+            "Should handle special characters"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_delete_by_filter() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+
+        let test_dir = TempDir::new()?;
+        let files = [
+            ("notes.txt", "Meeting notes about the quarterly roadmap"),
+            ("server.log", "ERROR connection refused while contacting upstream"),
+            ("readme.md", "Project readme with setup instructions"),
+        ];
+        for (name, content) in files {
+            let path = test_dir.path().join(name);
+            fs::write(&path, content)?;
+            system.index_file(path).await?;
+        }
+
+        let deleted = system.delete_by_filter(SearchFilter {
+            extensions: vec!["log".to_string()],
+            ..Default::default()
+        }).await?;
+        assert_eq!(deleted, 1, "Expected exactly one .log file to be deleted");
+
+        let remaining: Vec<FileRecord> = system.db.query("SELECT * FROM files").await?.take(0)?;
+        let mut names: Vec<&str> = remaining
+            .iter()
+            .map(|f| f.name.as_str())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["notes.txt", "readme.md"]);
+
+        let result = system.delete_by_filter(SearchFilter::default()).await;
+        assert!(matches!(result, Err(FileEmbeddingError::InvalidFilter(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_best_match_preview() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            preview_chars: 100,
+            preview_strategy: PreviewStrategy::BestMatch,
+            ..Default::default()
+        }).await;
+
+        let test_dir = TempDir::new()?;
+        let file_path = test_dir.path().join("report.txt");
+        let boilerplate = "Copyright notice. All rights reserved. Internal use only. ".repeat(4);
+        let content = format!(
+            "{}Sourdough bread needs a starter, flour, water and salt.",
+            boilerplate
+        );
+        fs::write(&file_path, &content)?;
+        system.index_file(file_path).await?;
+
+        let stored: Vec<FileRecord> = system.db.query("SELECT * FROM files").await?.take(0)?;
+        assert!(stored[0].content_preview.starts_with("Copyright notice"));
+
+        let results = system.hybrid_search("baking sourdough bread", 5).await?;
+        println!("Best match preview: {}", results[0].file.content_preview);
+        assert!(
+            results[0].file.content_preview.contains("Sourdough"),
+            "Expected the preview to come from the matching region, got: {}",
+            results[0].file.content_preview
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_index_content() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+
+        let outcome = system.index_content(
+            "clipboard://1",
+            "Rust ownership rules prevent data races at compile time.",
+            Some("txt")
+        ).await?;
+        assert_eq!(outcome, IndexOutcome::Created);
+
+        system.index_content(
+            "api://weather/today",
+            "Sunny with light winds and a high of twenty degrees.",
+            None
+        ).await?;
+
+        let results = system.hybrid_search("borrow checker and memory safety in rust", 2).await?;
+        assert_eq!(results[0].file.path, "clipboard://1");
+        assert_eq!(results[0].file.extension.as_deref(), Some("txt"));
+
+        let results = system.hybrid_search("weather forecast", 2).await?;
+        assert_eq!(results[0].file.path, "api://weather/today");
+        assert_eq!(results[0].file.name, "today");
+
+        // The virtual path is the unique key, so re-indexing replaces the record
+        let outcome = system.index_content(
+            "clipboard://1",
+            "Python is a dynamically typed scripting language.",
+            Some("txt")
+        ).await?;
+        assert_eq!(outcome, IndexOutcome::Updated);
+        let all_files: Vec<FileRecord> = system.db.query("SELECT * FROM files").await?.take(0)?;
+        assert_eq!(all_files.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_metrics() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+
+        system.index_content("rust.txt", "Rust is a systems programming language.", None).await?;
+        system.index_content("garden.txt", "Tomatoes grow best in full sun.", None).await?;
+        system.index_content("ocean.txt", "Whales migrate across the ocean.", None).await?;
+
+        for metric in [Metric::Cosine, Metric::DotProduct, Metric::Euclidean] {
+            let options = SearchOptions {
+                metric: Some(metric),
+                ..SearchOptions::new(3)
+            };
+            let results = system.search("rust programming", &options).await?;
+            for result in &results {
+                println!("{:?}: {} (Score: {:.4})", metric, result.file.path, result.score);
+            }
+
+            assert_eq!(results.len(), 3);
+            assert_eq!(results[0].file.path, "rust.txt", "Unexpected top hit for {:?}", metric);
+            assert!(
+                results.windows(2).all(|w| w[0].score >= w[1].score),
+                "Scores should be descending for {:?}",
+                metric
+            );
+        }
+
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_non_utf8_path_rejected() -> Result<()> {
+        use std::ffi::OsStr;
+        use std::os::unix::ffi::OsStrExt;
+
+        let (system, _temp_dir) = setup_test_system().await;
+
+        let test_dir = TempDir::new()?;
+        let file_path = test_dir.path().join(OsStr::from_bytes(b"caf\xe9.txt"));
+        if fs::write(&file_path, "Some content").is_err() {
+            println!("Filesystem doesn't allow non-UTF8 names, skipping");
+            return Ok(());
+        }
+
+        let result = system.index_file(file_path).await;
+        assert!(matches!(result, Err(FileEmbeddingError::NonUtf8Path(_))));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_prune_missing() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+
+        let test_dir = TempDir::new()?;
+        let kept = test_dir.path().join("kept.txt");
+        let removed = test_dir.path().join("removed.txt");
+        fs::write(&kept, "This file stays on disk")?;
+        fs::write(&removed, "This file will be deleted")?;
+        system.index_file(kept.clone()).await?;
+        system.index_file(removed.clone()).await?;
+        system.index_content("clipboard://note", "Not backed by a file", None).await?;
+
+        fs::remove_file(&removed)?;
+        let pruned = system.prune_missing().await?;
+        assert_eq!(pruned, vec![removed]);
+
+        let remaining: Vec<FileRecord> = system.db.query("SELECT * FROM files").await?.take(0)?;
+        let mut paths: Vec<String> = remaining
+            .into_iter()
+            .map(|f| f.path)
+            .collect();
+        paths.sort();
+        let mut expected = vec!["clipboard://note".to_string(), kept.to_string_lossy().to_string()];
+        expected.sort();
+        assert_eq!(paths, expected);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_query_with_filters() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+
+        system.index_content("src/parser.rs", "fn parse_tokens() handles error recovery", Some("rs")).await?;
+        system.index_content("notes/errors.md", "Notes on error handling in services", Some("md")).await?;
+        system.index_content("big.rs", &"Error handling with Result types. ".repeat(100), Some("rs")).await?;
+
+        let results = system.search_query(r#"ext:rs size:<1kb "error handling""#, 5).await?;
+        let paths: Vec<&str> = results
+            .iter()
+            .map(|r| r.file.path.as_str())
+            .collect();
+        assert_eq!(paths, vec!["src/parser.rs"]);
+
+        // The residual text is exactly what gets embedded and scored
+        let direct = system.search("error handling", &SearchOptions {
+            filter: parse_query("ext:rs size:<1kb").unwrap().filter,
+            ..SearchOptions::new(5)
+        }).await?;
+        assert_eq!(direct[0].score, results[0].score);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_hybrid_search_many_matches_single_queries() -> Result<()> {
+        let queries = vec![
+            "memory safe systems language".to_string(),
+            "sunny skies".to_string(),
+            "machine learning notebooks".to_string(),
+        ];
+        let ranked = |results: &[SearchResult]| -> Vec<(String, f32)> {
+            results.iter().map(|r| (r.file.path.clone(), r.score)).collect()
+        };
+        for server_side_scoring in [true, false] {
+            let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+                server_side_scoring,
+                ..Default::default()
+            }).await;
+            system.index_content("rust.txt", "Rust is a systems programming language.", None).await?;
+            system.index_content("python.txt", "Python is great for data science.", None).await?;
+            system.index_content("weather.txt", "The weather is nice today.", None).await?;
+
+            let batched = system.hybrid_search_many(&queries, 2).await?;
+            assert_eq!(batched.len(), queries.len());
+            for (query, batch_results) in queries.iter().zip(&batched) {
+                let single = system.hybrid_search(query, 2).await?;
+                assert_eq!(ranked(batch_results), ranked(&single), "Results differ for: {}", query);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_quantized_storage_scores() -> Result<()> {
+        let (baseline, _baseline_dir) = setup_test_system().await;
+        let (quantized, _quantized_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            embedding_precision: EmbeddingPrecision::Int8,
+            ..Default::default()
+        }).await;
+
+        let docs = [
+            ("rust.txt", "Rust guarantees memory safety without a garbage collector."),
+            ("garden.txt", "Water tomato plants early in the morning."),
+        ];
+        for (path, content) in docs {
+            baseline.index_content(path, content, None).await?;
+            quantized.index_content(path, content, None).await?;
+        }
+
+        let stored: Vec<FileRecord> = quantized.db.query("SELECT * FROM files").await?.take(0)?;
+        assert!(stored.iter().all(|r| r.content_embedding.is_empty()));
+        assert!(stored.iter().all(|r| r.quantized_embedding.as_ref().is_some_and(|b| b.len() == 384)));
+
+        let expected = baseline.hybrid_search("memory safe programming", 2).await?;
+        let actual = quantized.hybrid_search("memory safe programming", 2).await?;
+        for (expected, actual) in expected.iter().zip(&actual) {
+            assert_eq!(expected.file.path, actual.file.path);
+            assert!((expected.score - actual.score).abs() <= quantize::INT8_SCORE_TOLERANCE);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_with_fallback() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+
+        system.index_content("rust_guide.txt", "Rust programming language guide", None).await?;
+        system.index_content("garden.txt", "Planting tomatoes and herbs", None).await?;
+
+        let found = system.search_with_fallback("rust programming", 5, 0.3).await?;
+        assert!(!found.used_fallback);
+        assert_eq!(found.results[0].file.path, "rust_guide.txt");
+        assert!(!found.results[0].low_confidence);
+
+        // A typo-laden query with an unreachable threshold falls back
+        let fallback = system.search_with_fallback("progrmming rsut", 5, 0.99).await?;
+        assert!(fallback.used_fallback);
+        assert!(fallback.results.iter().all(|r| r.low_confidence));
+        assert!(
+            fallback.suggestions.contains(&"programming".to_string()),
+            "Expected a suggestion for the misspelled term, got {:?}",
+            fallback.suggestions
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_grouped_by_extension() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+
+        system.index_content("lib.rs", "fn parse() handles Rust errors", Some("rs")).await?;
+        system.index_content("main.rs", "fn main() runs the Rust program", Some("rs")).await?;
+        system.index_content("guide.md", "A guide to Rust error handling", Some("md")).await?;
+        system.index_content("notes", "Shopping list: eggs and milk", None).await?;
+
+        let groups = system.search_grouped("rust errors", &SearchOptions::new(10)).await?;
+        let mut sizes: Vec<(&str, usize)> = groups
+            .iter()
+            .map(|(ext, results)| (ext.as_str(), results.len()))
+            .collect();
+        sizes.sort();
+        assert_eq!(sizes, vec![("(none)", 1), ("md", 1), ("rs", 2)]);
+
+        for (_, results) in &groups {
+            assert!(results.windows(2).all(|w| w[0].score >= w[1].score));
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ensemble_fuses_model_scores() -> Result<()> {
+        let docs = [
+            ("rust.txt", "Rust is a systems programming language."),
+            ("garden.txt", "Tomatoes grow best in full sun."),
+        ];
+        let query = "memory safe programming";
+
+        let mut scores: Vec<f32> = Vec::new();
+        for model in [EmbeddingModel::AllMiniLML6V2, EmbeddingModel::BGESmallENV15] {
+            let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+                embedding_model: model,
+                ..Default::default()
+            }).await;
+            for (path, content) in docs {
+                system.index_content(path, content, None).await?;
+            }
+            let results = system.hybrid_search(query, 2).await?;
+            let rust = results.iter().find(|r| r.file.path == "rust.txt").unwrap();
+            scores.push(rust.score);
+        }
+
+        let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            ensemble: vec![EnsembleModel {
+                name: "bge".to_string(),
+                model: EmbeddingModel::BGESmallENV15,
+                weight: 1.0,
+            }],
+            ..Default::default()
+        }).await;
+        for (path, content) in docs {
+            system.index_content(path, content, None).await?;
+        }
+        let results = system.hybrid_search(query, 2).await?;
+        let rust = results.iter().find(|r| r.file.path == "rust.txt").unwrap();
+        println!("MiniLM: {}, BGE: {}, fused: {}", scores[0], scores[1], rust.score);
+
+        let stored: Vec<FileRecord> = system.db.query("SELECT * FROM files").await?.take(0)?;
+        assert!(stored.iter().all(|r| r.embeddings["bge"].len() == 384));
+        assert!((rust.score - (scores[0] + scores[1]) / 2.0).abs() < 1e-5);
+        assert!((rust.score - scores[0]).abs() > 1e-5);
+        assert!((rust.score - scores[1]).abs() > 1e-5);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_open_existing_read_only() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let db_path = temp_dir.path().join("test_db");
+        let db_path = db_path.to_str().unwrap();
+
+        {
+            let system = FileEmbeddingSystem::new(db_path).await?;
+            system.index_content("rust.txt", "Rust is a systems programming language.", None).await?;
+            system.index_content("garden.txt", "Tomatoes grow best in full sun.", None).await?;
+        }
+
+        let system = reopen_read_only(db_path).await?;
+        let results = system.hybrid_search("rust programming", 2).await?;
+        assert_eq!(results.len(), 2, "Existing records should survive a read-only open");
+        assert_eq!(results[0].file.path, "rust.txt");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_read_only_rejects_writes() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let db_path = temp_dir.path().join("test_db");
+        let db_path = db_path.to_str().unwrap();
+
+        {
+            let system = FileEmbeddingSystem::new(db_path).await?;
+            system.index_content("rust.txt", "Rust is a systems programming language.", None).await?;
+        }
+
+        let system = reopen_read_only(db_path).await?;
+
+        let file_path = temp_dir.path().join("new.txt");
+        fs::write(&file_path, "Tomatoes grow best in full sun.")?;
+        assert!(matches!(system.index_file(file_path).await, Err(FileEmbeddingError::ReadOnly)));
+        assert!(matches!(
+            system.index_content("garden.txt", "Tomatoes", None).await,
+            Err(FileEmbeddingError::ReadOnly)
+        ));
+        assert!(matches!(system.delete_file("rust.txt").await, Err(FileEmbeddingError::ReadOnly)));
+
+        let results = system.hybrid_search("rust programming", 5).await?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].file.path, "rust.txt");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_index_extensionless_text() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let readme = temp_dir.path().join("README");
+        fs::write(&readme, "This project indexes files for semantic search.")?;
+        let binary = temp_dir.path().join("blob");
+        fs::write(&binary, [0x7f, b'E', b'L', b'F', 0x02, 0x01, 0x00, 0x00])?;
+
+        let (system, _db_dir) = setup_test_system().await;
+        assert!(
+            matches!(
+                system.index_file(readme.clone()).await,
+                Err(FileEmbeddingError::UnsupportedFileType(_))
+            ),
+            "Extensionless files should be skipped unless sniffing is enabled"
+        );
+
+        let (system, _db_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            index_extensionless_text: true,
+            ..Default::default()
+        }).await;
+        system.index_file(readme).await?;
+        assert!(system.index_file(binary).await.is_err());
+
+        let results = system.hybrid_search("semantic search project", 5).await?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].file.name, "README");
+        assert_eq!(results[0].file.mime_type.as_deref(), Some("text/plain"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_cluster_near_duplicates() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+
+        system.index_content("fox1.txt", "The quick brown fox jumps over the lazy dog.", None).await?;
+        system.index_content("fox2.txt", "The quick brown fox jumped over the lazy dog.", None).await?;
+        system.index_content("fox3.txt", "A quick brown fox jumps over a lazy dog.", None).await?;
+        system.index_content("tax.txt", "Quarterly tax filings are due at the end of April.", None).await?;
+
+        let clusters = system.cluster(0.85).await?;
+        println!("Clusters: {:?}", clusters);
+
+        assert_eq!(clusters.len(), 2);
+        assert_eq!(clusters[0], vec!["fox1.txt", "fox2.txt", "fox3.txt"]);
+        assert_eq!(clusters[1], vec!["tax.txt"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_small_embed_batch_size() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            embed_batch_size: Some(1),
+            preview_strategy: PreviewStrategy::BestMatch,
+            preview_chars: 50,
+            ..Default::default()
+        }).await;
+
+        let content = "Tomatoes grow best in full sun. Rust is a systems programming language.";
+        system.index_content("mixed.txt", content, None).await?;
+        system.index_content("garden.txt", "Water the garden every morning.", None).await?;
+
+        let queries = vec!["rust programming".to_string(), "gardening".to_string()];
+        let results = system.hybrid_search_many(&queries, 2).await?;
+        assert_eq!(results[0][0].file.path, "mixed.txt");
+        assert!(results[0][0].file.content_preview.contains("Rust"));
+        assert_eq!(results[1].len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_corrupt_database() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let db_path = temp_dir.path().join("test_db");
+        fs::create_dir(&db_path)?;
+        fs::write(db_path.join("CURRENT"), "garbage")?;
+        let db_path = db_path.to_str().unwrap();
+
+        let error = match FileEmbeddingSystem::new(db_path).await {
+            Ok(_) => panic!("Opening a garbage directory should fail"),
+            Err(e) => e,
+        };
+        println!("{}", error);
+        assert!(matches!(
+            error.downcast_ref::<FileEmbeddingError>(),
+            Some(FileEmbeddingError::DatabaseCorrupt { .. })
+        ));
+        assert!(error.to_string().contains("reset_if_corrupt"));
+
+        let config = FileEmbeddingConfig {
+            storage: StorageConfig {
+                reset_if_corrupt: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let system = FileEmbeddingSystem::with_config(db_path, config).await?;
+        system.index_content("rust.txt", "Rust is a systems programming language.", None).await?;
+        assert_eq!(system.hybrid_search("rust", 5).await?.len(), 1);
+
+        let backups = fs::read_dir(temp_dir.path())?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.file_name().to_string_lossy().starts_with("test_db.corrupt-"))
+            .count();
+        assert_eq!(backups, 1, "The corrupt database should be kept as a backup");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_preprocessing_hook() -> Result<()> {
+        let mut config = FileEmbeddingConfig::default();
+        config.preprocessors.register("txt", |_, content| content.to_uppercase());
+        let (system, temp_dir) = setup_test_system_with_config(config).await;
+
+        let file_path = temp_dir.path().join("notes.txt");
+        fs::write(&file_path, "rust is a systems programming language.")?;
+        system.index_file(file_path).await?;
+        system.index_content("notes.md", "markdown stays as written", Some("md")).await?;
+
+        let results = system.hybrid_search("rust programming", 5).await?;
+        let notes = results.iter().find(|r| r.file.name == "notes.txt").unwrap();
+        assert_eq!(notes.file.content_preview, "RUST IS A SYSTEMS PROGRAMMING LANGUAGE.");
+        let markdown = results.iter().find(|r| r.file.name == "notes.md").unwrap();
+        assert_eq!(markdown.file.content_preview, "markdown stays as written");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_recent() -> Result<()> {
+        let (system, temp_dir) = setup_test_system().await;
+
+        for name in ["first.txt", "second.txt", "third.txt"] {
+            let file_path = temp_dir.path().join(name);
+            fs::write(&file_path, format!("Contents of {}", name))?;
+            system.index_file(file_path).await?;
+        }
+        // Re-indexing moves a file back to the front
+        system.index_content("virtual.md", "Virtual notes", None).await?;
+        system.index_content("virtual.md", "Updated virtual notes", None).await?;
+
+        let recent = system.recent(3).await?;
+        let names: Vec<&str> = recent
+            .iter()
+            .map(|r| r.name.as_str())
+            .collect();
+        assert_eq!(names, vec!["virtual.md", "third.txt", "second.txt"]);
+        assert!(recent.iter().all(|r| r.indexed_at.is_some()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_path_prefix() -> Result<()> {
+        let (system, temp_dir) = setup_test_system().await;
+
+        let notes = temp_dir.path().join("notes");
+        let other = temp_dir.path().join("notes-old");
+        fs::create_dir(&notes)?;
+        fs::create_dir(&other)?;
+        fs::write(notes.join("rust.txt"), "Rust is a systems programming language.")?;
+        fs::write(other.join("rust.txt"), "Rust programming notes from last year.")?;
+        system.index_directory(temp_dir.path().to_path_buf()).await?;
+
+        let prefix = format!("{}{}", notes.display(), std::path::MAIN_SEPARATOR);
+        let options = SearchOptions {
+            filter: SearchFilter {
+                path_prefix: Some(prefix.clone()),
+                ..Default::default()
+            },
+            ..SearchOptions::new(5)
+        };
+        let results = system.search("rust programming", &options).await?;
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file.path.starts_with(&prefix));
+        assert_eq!(system.hybrid_search("rust programming", 5).await?.len(), 2);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_non_finite_scores() {
+        let query = [1.0, 0.0, 0.0];
+        let corpus: Vec<(&str, Vec<f32>)> = vec![
+            ("nan", vec![f32::NAN, 0.5, 0.5]),
+            ("near", vec![0.9, 0.1, 0.0]),
+            ("inf", vec![f32::INFINITY, 0.0, 0.0]),
+            ("far", vec![0.1, 0.9, 0.0]),
+        ];
+
+        for metric in [Metric::Cosine, Metric::DotProduct, Metric::Euclidean] {
+            let (nan, inf) = (&corpus[0].1, &corpus[2].1);
+            assert_eq!(metric.score(&query, nan), 0.0, "NaN should score 0.0 for {:?}", metric);
+            assert!(metric.score(&query, inf).is_finite());
+
+            let rank = || {
+                let mut scored: Vec<(&str, f32)> = corpus
+                    .iter()
+                    .map(|(name, v)| (*name, metric.score(&query, v)))
+                    .collect();
+                scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+                scored
+                    .into_iter()
+                    .map(|(name, _)| name)
+                    .collect::<Vec<_>>()
+            };
+            let first = rank();
+            assert_eq!(first[0], "near", "Unexpected ordering for {:?}: {:?}", metric, first);
+            for _ in 0..10 {
+                assert_eq!(rank(), first);
+            }
+        }
+
+        assert!(validate_embedding("ok.txt", &[0.1, 0.2]).is_ok());
+        assert!(matches!(
+            validate_embedding("bad.txt", &[0.1, f32::NAN]),
+            Err(FileEmbeddingError::InvalidEmbedding(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_server_side_scoring_matches_rust() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+        assert!(system.scores_in_database());
+
+        system.index_content("rust.txt", "Rust is a systems programming language.", Some("txt")).await?;
+        system.index_content("garden.txt", "Tomatoes grow best in full sun.", Some("txt")).await?;
+        system.index_content("ocean.md", "Whales migrate across the ocean.", Some("md")).await?;
+
+        let query = system.embed_queries(vec!["rust programming".to_string()])?.remove(0);
+        let records = system.fetch_candidates(&SearchFilter::default()).await?;
+        for metric in [Metric::Cosine, Metric::DotProduct, Metric::Euclidean] {
+            let options = SearchOptions {
+                metric: Some(metric),
+                ..SearchOptions::new(2)
+            };
+            let in_rust = system.rank(&records, &query, &options)?;
+            let in_database = system.rank_in_database(&query, &options).await?;
+
+            assert_eq!(in_database.len(), 2);
+            for (a, b) in in_rust.iter().zip(&in_database) {
+                assert_eq!(a.file.path, b.file.path, "Rankings differ for {:?}", metric);
+                assert!((a.score - b.score).abs() < 1e-4, "Scores differ for {:?}", metric);
+            }
+        }
+
+        let options = SearchOptions {
+            filter: SearchFilter {
+                extensions: vec!["md".to_string()],
+                ..Default::default()
+            },
+            ..SearchOptions::new(5)
+        };
+        let filtered = system.rank_in_database(&query, &options).await?;
+        assert_eq!(filtered.len(), 1);
+        assert_eq!(filtered[0].file.path, "ocean.md");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_server_side_scoring_skips_wrong_dimension() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+        system.index_content("rust.txt", "Rust is a systems programming language.", Some("txt")).await?;
+        system.index_content("cargo.txt", "Cargo builds Rust programming projects.", Some("txt")).await?;
+        system.index_content("garden.txt", "Tomatoes grow best in full sun.", Some("txt")).await?;
+        system.index_content("stale.txt", "Rust programming with the borrow checker.", Some("txt")).await?;
+        system.index_content("empty.txt", "Rust programming in embedded systems.", Some("txt")).await?;
+        // As if left over from before a reduction, or reopened from a quantized index
+        for (path, embedding) in [("stale.txt", vec![0.6, 0.8]), ("empty.txt", vec![])] {
+            system.db
+                .query("UPDATE files SET content_embedding = $embedding WHERE path = $path")
+                .bind(("embedding", embedding))
+                .bind(("path", path)).await?
+                .check()?;
+        }
+
+        let query = system.embed_queries(vec!["rust programming".to_string()])?.remove(0);
+        let records = system.fetch_candidates(&SearchFilter::default()).await?;
+        for metric in [Metric::Cosine, Metric::DotProduct, Metric::Euclidean] {
+            let options = SearchOptions {
+                metric: Some(metric),
+                ..SearchOptions::new(2)
+            };
+            let in_rust = system.rank(&records, &query, &options)?;
+            let in_database = system.rank_in_database(&query, &options).await?;
+
+            let paths = |results: &[SearchResult]| -> Vec<String> {
+                results.iter().map(|r| r.file.path.clone()).collect()
+            };
+            assert_eq!(paths(&in_database), paths(&in_rust), "Rankings differ for {:?}", metric);
+            assert!(paths(&in_database).iter().all(|p| p == "rust.txt" || p == "cargo.txt"));
+            for (a, b) in in_rust.iter().zip(&in_database) {
+                assert!((a.score - b.score).abs() < 1e-4, "Scores differ for {:?}", metric);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_clean_preview_for_pdf_text() -> Result<()> {
+        let extracted = "Quarterly   Report\x0c\x0c\n\n\n   Revenue grew    by 12%\x0c";
+
+        let (system, _temp_dir) = setup_test_system().await;
+        system.index_content("report.pdf", extracted, Some("pdf")).await?;
+        system.index_content("report.txt", extracted, Some("txt")).await?;
+
+        let results = system.hybrid_search("quarterly revenue report", 2).await?;
+        let pdf = results.iter().find(|r| r.file.path == "report.pdf").unwrap();
+        assert_eq!(pdf.file.content_preview, "Quarterly Report Revenue grew by 12%");
+        let text = results.iter().find(|r| r.file.path == "report.txt").unwrap();
+        assert_eq!(text.file.content_preview, extracted, "Plain text previews are left alone");
+
+        let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            clean_preview: false,
+            ..Default::default()
+        }).await;
+        system.index_content("report.pdf", extracted, Some("pdf")).await?;
+        let results = system.hybrid_search("quarterly revenue report", 1).await?;
+        assert_eq!(results[0].file.content_preview, extracted);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_metadata_only_then_embed_pending() -> Result<()> {
+        let (system, temp_dir) = setup_test_system().await;
+        let docs = temp_dir.path().join("docs");
+        fs::create_dir(&docs)?;
+        fs::write(docs.join("rust.txt"), "Rust is a systems programming language.")?;
+        fs::write(docs.join("garden.txt"), "Tomatoes grow best in full sun.")?;
+        fs::write(docs.join("photo.jpg"), [0xff, 0xd8, 0xff])?;
+
+        assert_eq!(system.index_metadata_only(docs).await?, 2);
+        let inventory = system.recent(10).await?;
+        assert_eq!(inventory.len(), 2);
+        assert!(inventory.iter().all(|r| !r.embedded && r.content_embedding.is_empty()));
+        assert!(system.hybrid_search("rust programming", 5).await?.is_empty());
+
+        assert_eq!(system.embed_pending(1).await?.embedded.len(), 1);
+        assert_eq!(system.hybrid_search("rust programming", 5).await?.len(), 1);
+        assert_eq!(system.embed_pending(10).await?.embedded.len(), 1);
+        assert!(system.embed_pending(10).await?.embedded.is_empty());
+
+        let results = system.hybrid_search("rust programming", 5).await?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].file.name, "rust.txt");
+        assert!(!results[0].file.content_preview.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_embed_pending_only_drops_unindexable_files() -> Result<()> {
+        let (system, temp_dir) = setup_test_system().await;
+        let docs = temp_dir.path().join("docs");
+        fs::create_dir(&docs)?;
+        // A header followed by garbage, as in test_malformed_pdf_does_not_abort_indexing
+        let broken = docs.join("broken.pdf");
+        fs::write(&broken, b"%PDF-1.7\n1 0 obj << /Type /Pages >>\nxref")?;
+        let gone = docs.join("gone.txt");
+        fs::write(&gone, "This file is about to be deleted.")?;
+        let short = docs.join("short.txt");
+        fs::write(&short, "A note that is long enough to index.")?;
+        let rust = docs.join("rust.txt");
+        fs::write(&rust, "Rust is a systems programming language.")?;
+        assert_eq!(system.index_metadata_only(docs).await?, 4);
+        fs::remove_file(&gone)?;
+        fs::write(&short, "tiny")?;
+
+        let report = system.embed_pending(2).await?;
+        let path = |p: &PathBuf| p.to_str().unwrap().to_string();
+        assert_eq!(report.failed.len(), 1);
+        assert_eq!(report.failed[0].0, path(&broken));
+        assert_eq!(report.dropped.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>(), vec![path(&gone)]);
+        assert_eq!(report.embedded, vec![path(&rust)]);
+
+        let report = system.embed_pending(10).await?;
+        assert_eq!(report.failed.len(), 1, "The broken file stays pending");
+        assert_eq!(report.dropped.iter().map(|(p, _)| p.clone()).collect::<Vec<_>>(), vec![path(&short)]);
+        assert!(report.embedded.is_empty());
+
+        let records = system.recent(10).await?;
+        assert_eq!(records.len(), 2);
+        let pending = records.iter().find(|r| r.path == path(&broken)).expect("Pending record");
+        assert!(!pending.embedded);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_metadata_only_rerun() -> Result<()> {
+        let (system, temp_dir) = setup_test_system().await;
+        let docs = temp_dir.path().join("docs");
+        fs::create_dir(&docs)?;
+        fs::write(docs.join("rust.txt"), "Rust is a systems programming language.")?;
+        fs::write(docs.join("garden.txt"), "Tomatoes grow best in full sun.")?;
+
+        assert_eq!(system.index_metadata_only(docs.clone()).await?, 2);
+        assert_eq!(system.embed_pending(10).await?.embedded.len(), 2);
+        assert_eq!(system.index_metadata_only(docs.clone()).await?, 0, "Unchanged files stay embedded");
+        assert!(system.embed_pending(10).await?.embedded.is_empty());
+
+        fs::write(docs.join("garden.txt"), "Tomatoes grow best in full sun and warm soil.")?;
+        assert_eq!(system.index_metadata_only(docs.clone()).await?, 1);
+        let inventory = system.recent(10).await?;
+        assert_eq!(inventory.len(), 2);
+        let garden = inventory.iter().find(|r| r.name == "garden.txt").unwrap();
+        assert!(!garden.embedded);
+        assert_eq!(garden.size_bytes, 45);
+        assert!(inventory.iter().find(|r| r.name == "rust.txt").unwrap().embedded);
+
+        let (system, temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            on_duplicate: OnDuplicate::Skip,
+            ..Default::default()
+        }).await;
+        let docs = temp_dir.path().join("docs");
+        fs::create_dir(&docs)?;
+        fs::write(docs.join("rust.txt"), "Rust is a systems programming language.")?;
+        assert_eq!(system.index_metadata_only(docs.clone()).await?, 1);
+        fs::write(docs.join("rust.txt"), "Rust is a memory-safe systems programming language.")?;
+        assert_eq!(system.index_metadata_only(docs).await?, 0);
+        assert_eq!(system.recent(10).await?[0].size_bytes, 39);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_results_exclude_embeddings_by_default() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+        system.index_content("rust.txt", "Rust is a systems programming language.", None).await?;
+
+        let results = system.hybrid_search("rust programming", 5).await?;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].file.content_embedding.is_empty());
+
+        let options = SearchOptions {
+            include_embeddings: true,
+            ..SearchOptions::new(5)
+        };
+        let results = system.search("rust programming", &options).await?;
+        assert_eq!(results[0].file.content_embedding.len(), 384);
+
+        let hits = system.search_hits("rust programming", &SearchOptions::new(5)).await?;
+        assert_eq!(hits[0].path, "rust.txt");
+        assert_eq!(hits[0].snippet, "Rust is a systems programming language.");
+        let json = serde_json::to_string(&hits[0])?;
+        assert!(!json.contains("embedding"), "SearchHit should not carry vectors: {}", json);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_include_path_in_embedding() -> Result<()> {
+        let docs = [
+            ("/scans/finance/tax_return_2023.pdf", "Page 1 of 3"),
+            ("/scans/misc/scan_0042.pdf", "Page 1 of 2"),
+        ];
+
+        let mut top_scores = Vec::new();
+        for include_path_in_embedding in [false, true] {
+            let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+                include_path_in_embedding,
+                ..Default::default()
+            }).await;
+            for (path, content) in docs {
+                system.index_content(path, content, Some("pdf")).await?;
+            }
+
+            let results = system.hybrid_search("tax return", 2).await?;
+            if include_path_in_embedding {
+                assert_eq!(results[0].file.name, "tax_return_2023.pdf");
+                assert!(results[0].score - results[1].score > 0.1);
+            }
+            let tax = results.iter().find(|r| r.file.name == "tax_return_2023.pdf").unwrap();
+            assert_eq!(tax.file.content_preview, "Page 1 of 3", "Previews never include the path");
+            top_scores.push(tax.score);
+        }
+
+        assert!(top_scores[1] > top_scores[0], "Path terms should raise the match: {:?}", top_scores);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_large_file_truncated_read() -> Result<()> {
+        let (system, temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            max_embed_chars: Some(2000),
+            ..Default::default()
+        }).await;
+
+        // ~50 MB of log lines
+        let file_path = temp_dir.path().join("huge.log");
+        let mut file = std::io::BufWriter::new(fs::File::create(&file_path)?);
+        for i in 0..1_000_000 {
+            std::io::Write::write_all(
+                &mut file,
+                format!("{:08} INFO request handled by worker pool\n", i).as_bytes()
+            )?;
+        }
+        std::io::Write::flush(&mut file)?;
+        drop(file);
+
+        system.index_file(file_path.clone()).await?;
+
+        let results = system.hybrid_search("worker pool request", 1).await?;
+        assert_eq!(results[0].file.path, file_path.to_str().unwrap());
+        assert!(results[0].file.size_bytes > 40_000_000, "size_bytes is the real file size");
+        assert_eq!(results[0].file.content_preview.chars().count(), 1000);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_flush_then_reopen() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let db_path = temp_dir.path().join("test_db");
+        let db_path = db_path.to_str().unwrap();
+        let docs = temp_dir.path().join("docs");
+        fs::create_dir(&docs)?;
+        fs::write(docs.join("rust.txt"), "Rust is a systems programming language.")?;
+
+        {
+            let system = FileEmbeddingSystem::new(db_path).await?;
+            system.index_directory(docs).await?;
+            system.index_content("garden.txt", "Tomatoes grow best in full sun.", None).await?;
+            system.flush().await?;
+        }
+
+        let system = reopen_read_only(db_path).await?;
+        let results = system.hybrid_search("rust programming", 5).await?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].file.name, "rust.txt");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_highlighted_snippets() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+        system.index_content("rust.txt", "Rust is a systems programming language.", None).await?;
+
+        let results = system.hybrid_search("rust programming", 1).await?;
+        assert_eq!(results[0].snippet, None);
+
+        let options = SearchOptions {
+            highlight: Some(Highlight::new("[", "]")),
+            ..SearchOptions::new(1)
+        };
+        let results = system.search("rust programming", &options).await?;
+        assert_eq!(
+            results[0].snippet.as_deref(),
+            Some("[Rust] is a systems [programming] language.")
+        );
+        assert_eq!(results[0].file.content_preview, "Rust is a systems programming language.");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dimension_mismatch_is_rejected() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let db_path = temp_dir.path().join("test_db");
+        let embedder = MockEmbedder::new(384).with_output_dimension(100);
+        let system = FileEmbeddingSystem::with_embedder(
+            db_path.to_str().unwrap(),
+            FileEmbeddingConfig::default(),
+            Box::new(embedder)
+        ).await?;
+
+        let file_path = temp_dir.path().join("rust.txt");
+        fs::write(&file_path, "Rust is a systems programming language.")?;
+        match system.index_file(file_path.clone()).await {
+            Err(FileEmbeddingError::DimensionMismatch { path, expected, actual }) => {
+                assert_eq!(path, file_path.to_str().unwrap());
+                assert_eq!((expected, actual), (384, 100));
+            }
+            other => panic!("Expected DimensionMismatch, got {:?}", other.map(|_| ())),
+        }
+        assert!(system.recent(10).await?.is_empty(), "Nothing should have been stored");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_index_error_policy() -> Result<()> {
+        let docs = TempDir::new()?;
+        fs::write(docs.path().join("rust.txt"), "Rust is a systems programming language.")?;
+        fs::write(docs.path().join("broken.txt"), [0xff, 0xfe, 0xfd])?;
+        fs::write(docs.path().join("image.png"), [0x89, 0x50, 0x4e, 0x47])?;
+
+        let (system, _temp_dir) = setup_test_system().await;
+        system.index_directory(docs.path().to_path_buf()).await?;
+        let indexed = system.recent(10).await?;
+        assert_eq!(indexed.len(), 1);
+        assert_eq!(indexed[0].name, "rust.txt");
+
+        let config = FileEmbeddingConfig {
+            index_error_policy: IndexErrorPolicy::StopOnFirst,
+            ..Default::default()
+        };
+        let (system, _temp_dir) = setup_test_system_with_config(config).await;
+        let result = system.index_directory(docs.path().to_path_buf()).await;
+        assert!(matches!(result, Err(FileEmbeddingError::Io(_))), "Got {:?}", result);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_stopwords_are_not_highlighted() -> Result<()> {
+        let config = FileEmbeddingConfig {
+            language: Some("en".to_string()),
+            ..Default::default()
+        };
+        let (system, _temp_dir) = setup_test_system_with_config(config).await;
+        system.index_content("rust.txt", "The borrow checker and the compiler.", None).await?;
+
+        let options = SearchOptions {
+            highlight: Some(Highlight::new("[", "]")),
+            ..SearchOptions::new(1)
+        };
+        let results = system.search("the borrow checker and the compiler", &options).await?;
+        assert_eq!(
+            results[0].snippet.as_deref(),
+            Some("The [borrow] [checker] and the [compiler].")
+        );
+
+        let config = FileEmbeddingConfig {
+            language: Some("klingon".to_string()),
+            ..Default::default()
+        };
+        let temp_dir = TempDir::new()?;
+        let db_path = temp_dir.path().join("test_db");
+        let error = FileEmbeddingSystem::with_config(db_path.to_str().unwrap(), config).await.err();
+        assert!(matches!(
+            error.as_ref().and_then(|e| e.downcast_ref()),
+            Some(FileEmbeddingError::InvalidConfig(_))
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_retry_failed() -> Result<()> {
+        let docs = TempDir::new()?;
+        let fixed = docs.path().join("fixed.txt");
+        let broken = docs.path().join("broken.txt");
+        fs::write(docs.path().join("rust.txt"), "Rust is a systems programming language.")?;
+        fs::write(&fixed, [0xff, 0xfe, 0xfd])?;
+        fs::write(&broken, [0xff, 0xfe, 0xfd])?;
+
+        let (system, _temp_dir) = setup_test_system().await;
+        let report = system.index_directory(docs.path().to_path_buf()).await?;
+        assert_eq!(report.indexed.len(), 1);
+        assert_eq!(report.failed.len(), 2);
+
+        fs::write(&fixed, "Tomatoes grow best in full sun.")?;
+        let retried = system.retry_failed(&report).await?;
+        assert_eq!(retried.indexed, vec![fixed]);
+        assert_eq!(retried.failed.len(), 1);
+        assert_eq!(retried.failed[0].0, broken);
+        assert_eq!(system.recent(10).await?.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_bom_is_stripped() -> Result<()> {
+        let (system, temp_dir) = setup_test_system().await;
+        let file_path = temp_dir.path().join("notes.txt");
+        fs::write(&file_path, "\u{FEFF}Rust is a systems programming language.")?;
+
+        system.index_file(file_path).await?;
+        let records = system.recent(1).await?;
+        assert_eq!(records[0].content_preview, "Rust is a systems programming language.");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_preview_from_matching_chunk() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            preview_chars: 60,
+            preview_strategy: PreviewStrategy::BestMatch,
+            ..Default::default()
+        }).await;
+
+        let ending = "Sourdough bread needs a starter, flour, water and salt.";
+        let mut content = "Meeting notes about the quarterly budget and staffing. ".repeat(5);
+        // Pad so the ending is a chunk of its own
+        while !content.chars().count().is_multiple_of(60) {
+            content.push(' ');
+        }
+        content.push_str(ending);
+        system.index_content("notes.txt", &content, None).await?;
+
+        let stored: Vec<FileRecord> = system.db.query("SELECT * FROM files").await?.take(0)?;
+        let chunks = preview::windows(&content, 60);
+        assert_eq!(stored[0].chunk_embeddings.len(), chunks.len());
+
+        let results = system.hybrid_search("baking sourdough bread", 1).await?;
+        assert_eq!(chunks.last().map(String::as_str), Some(ending));
+        assert_eq!(results[0].file.content_preview, ending);
+        assert!(results[0].file.chunk_embeddings.is_empty(), "Chunk vectors are stripped");
+
+        // Single-chunk documents keep the head preview and store no chunk vectors
+        system.index_content("short.txt", ending, None).await?;
+        let short: Vec<FileRecord> = system.db
+            .query("SELECT * FROM files WHERE name = 'short.txt'").await?
+            .take(0)?;
+        assert!(short[0].chunk_embeddings.is_empty());
+        assert_eq!(short[0].content_preview, ending);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_malformed_pdf_does_not_abort_indexing() -> Result<()> {
+        let docs = TempDir::new()?;
+        // A header followed by garbage; pdf_extract errors or panics on input like this
+        fs::write(docs.path().join("broken.pdf"), b"%PDF-1.7\n1 0 obj << /Type /Pages >>\nxref")?;
+        fs::write(docs.path().join("rust.txt"), "Rust is a systems programming language.")?;
+
+        let (system, _temp_dir) = setup_test_system().await;
+        let report = system.index_directory(docs.path().to_path_buf()).await?;
+        assert_eq!(report.indexed, vec![docs.path().join("rust.txt")]);
+        assert_eq!(report.failed.len(), 1);
+        assert!(report.failed[0].1.starts_with("PDF extraction error"), "{:?}", report.failed);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watched_changes_commit_in_one_batch() -> Result<()> {
+        let (system, temp_dir) = setup_test_system().await;
+        let docs = temp_dir.path().join("docs");
+        fs::create_dir(&docs)?;
+        let gone = docs.join("gone.txt");
+        fs::write(&gone, "This file is about to be deleted.")?;
+        system.index_file(gone.clone()).await?;
+        fs::remove_file(&gone)?;
+
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        for i in 0..5 {
+            let path = docs.join(format!("note{}.txt", i));
+            fs::write(&path, format!("Note number {} about Rust.", i))?;
+            // Editors often write a file more than once per save
+            sender.send(path.clone())?;
+            sender.send(path)?;
+        }
+        sender.send(gone)?;
+        drop(sender);
+
+        let watch = WatchConfig {
+            commit_interval: std::time::Duration::from_secs(60),
+            ..Default::default()
+        };
+        assert_eq!(system.apply_changes(receiver, &watch).await?, 1);
+
+        let records = system.recent(10).await?;
+        assert_eq!(records.len(), 5);
+        assert!(records.iter().all(|record| record.name.starts_with("note")));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watched_changes_reindex_like_index_directory() -> Result<()> {
+        let (system, temp_dir) = setup_test_system().await;
+        let docs = temp_dir.path().join("docs");
+        fs::create_dir(&docs)?;
+        let notes = docs.join("notes.txt");
+        fs::write(&notes, "Meeting notes about the Rust rewrite.")?;
+        let short = docs.join("short.txt");
+        fs::write(&short, "A note that is long enough to index.")?;
+        let mbox = docs.join("archive.mbox");
+        let message = |from: &str, subject: &str, body: &str| {
+            format!(
+                "From {from} Mon Jun  3 10:00:00 2024\nFrom: {from}\nSubject: {subject}\n\n{body}\n\n"
+            )
+        };
+        fs::write(&mbox, message("carol@example.com", "Rust meetup", "Slides about Rust programming."))?;
+        system.index_directory_as(docs.clone(), "work").await?;
+
+        fs::write(&notes, "Meeting notes about the Rust rewrite, now with a schedule.")?;
+        fs::write(&short, "tiny")?;
+        fs::write(
+            &mbox,
+            message("carol@example.com", "Rust meetup", "Slides about Rust programming.") +
+                &message("dave@example.com", "Garden", "Tomatoes grow best in full sun.")
+        )?;
+        let (sender, receiver) = tokio::sync::mpsc::unbounded_channel();
+        for path in [&notes, &short, &mbox] {
+            sender.send(path.clone())?;
+        }
+        drop(sender);
+        system.apply_changes(receiver, &WatchConfig::default()).await?;
+
+        let record = system.get_file(notes.to_str().unwrap()).await?.expect("Re-indexed notes");
+        assert!(record.content_preview.ends_with("now with a schedule."));
+        assert_eq!(record.collection.as_deref(), Some("work"));
+        assert!(system.get_file(short.to_str().unwrap()).await?.is_none(), "Too short now");
+        assert!(system.get_file(mbox.to_str().unwrap()).await?.is_none());
+        let mbox_str = mbox.to_str().unwrap();
+        for n in [1, 2] {
+            let message = system
+                .get_file(&format!("{}#{}", mbox_str, n)).await?
+                .expect("Archive message");
+            assert_eq!(message.parent_path.as_deref(), Some(mbox_str));
+            assert_eq!(message.collection.as_deref(), Some("work"));
+        }
+        assert_eq!(system.recent(10).await?.len(), 3);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_get_file() -> Result<()> {
+        let (system, temp_dir) = setup_test_system().await;
+        let file_path = temp_dir.path().join("rust.txt");
+        fs::write(&file_path, "Rust is a systems programming language.")?;
+        system.index_file(file_path.clone()).await?;
+
+        let record = system.get_file(file_path.to_str().unwrap()).await?.expect("Indexed file");
+        assert_eq!(record.name, "rust.txt");
+        assert_eq!(record.content_preview, "Rust is a systems programming language.");
+        assert_eq!(record.content_embedding.len(), 384);
+
+        assert!(system.get_file("/no/such/file.txt").await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_collections() -> Result<()> {
+        let (system, temp_dir) = setup_test_system().await;
+        let work = temp_dir.path().join("work");
+        let personal = temp_dir.path().join("personal");
+        fs::create_dir(&work)?;
+        fs::create_dir(&personal)?;
+        fs::write(work.join("roadmap.txt"), "Rust programming roadmap for the platform team.")?;
+        fs::write(personal.join("hobby.txt"), "Learning Rust programming on weekends.")?;
+
+        let report = system.index_directory_as(work, "work").await?;
+        assert_eq!(report.collection.as_deref(), Some("work"));
+        system.index_directory_as(personal, "personal").await?;
+
+        let options = SearchOptions {
+            filter: SearchFilter {
+                collection: Some("work".to_string()),
+                ..Default::default()
+            },
+            ..SearchOptions::new(10)
+        };
+        let results = system.search("rust programming", &options).await?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].file.name, "roadmap.txt");
+        assert_eq!(results[0].file.collection.as_deref(), Some("work"));
+
+        let results = system.hybrid_search("rust programming", 10).await?;
+        assert_eq!(results.len(), 2, "Collections are searched together without a filter");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_email_indexing() -> Result<()> {
+        let (system, temp_dir) = setup_test_system().await;
+        let eml = temp_dir.path().join("budget.eml");
+        fs::write(
+            &eml,
+            "From: Alice <alice@example.com>\r\n\
+             To: bob@example.com\r\n\
+             Subject: Quarterly budget review\r\n\
+             \r\n\
+             The sourdough bakery needs a bigger oven next quarter.\r\n"
+        )?;
+        let mbox = temp_dir.path().join("archive.mbox");
+        fs::write(
+            &mbox,
+            "From carol@example.com Mon Jun  3 10:00:00 2024\n\
+             From: Carol <carol@example.com>\n\
+             Subject: Rust meetup\n\
+             \n\
+             Slides about Rust programming and the borrow checker.\n\
+             \n\
+             From dave@example.com Tue Jun  4 10:00:00 2024\n\
+             From: Dave <dave@example.com>\n\
+             Subject: Garden\n\
+             \n\
+             Tomatoes grow best in full sun.\n"
+        )?;
+
+        system.index_file(eml.clone()).await?;
+        system.index_file(mbox.clone()).await?;
+
+        let record = system.get_file(eml.to_str().unwrap()).await?.expect("Indexed email");
+        let headers = record.email.expect("Email headers");
+        assert_eq!(headers.subject.as_deref(), Some("Quarterly budget review"));
+        assert_eq!(headers.from.as_deref(), Some("Alice <alice@example.com>"));
+        assert!(record.content_preview.starts_with("The sourdough bakery"));
+
+        let results = system.hybrid_search("baking bread oven", 1).await?;
+        assert_eq!(results[0].file.path, eml.to_str().unwrap());
+
+        let options = SearchOptions {
+            filter: SearchFilter {
+                sender: Some("CAROL@".to_string()),
+                ..Default::default()
+            },
+            ..SearchOptions::new(10)
+        };
+        let results = system.search("rust programming", &options).await?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].file.path, format!("{}#1", mbox.display()));
+        assert_eq!(results[0].file.name, "Rust meetup");
+
+        // Re-indexing an archive replaces its messages, and deleting it removes them all
+        system.index_file(mbox.clone()).await?;
+        assert_eq!(system.recent(10).await?.len(), 3);
+        assert!(system.delete_file(mbox.to_str().unwrap()).await?);
+        assert_eq!(system.recent(10).await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tie_breaker() -> Result<()> {
+        let content = "Rust is a systems programming language.";
+        for (tie_breaker, expected) in [
+            (TieBreaker::Path, ["alpha/banana.txt", "zeta/apple.txt"]),
+            (TieBreaker::Name, ["zeta/apple.txt", "alpha/banana.txt"]),
+        ] {
+            for server_side_scoring in [true, false] {
+                let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+                    tie_breaker,
+                    server_side_scoring,
+                    ..Default::default()
+                }).await;
+                // Identical content scores identically
+                system.index_content("zeta/apple.txt", content, None).await?;
+                system.index_content("alpha/banana.txt", content, None).await?;
+
+                for _ in 0..5 {
+                    let results = system.hybrid_search("rust programming", 2).await?;
+                    assert_eq!(results[0].score, results[1].score);
+                    let paths: Vec<&str> = results
+                        .iter()
+                        .map(|r| r.file.path.as_str())
+                        .collect();
+                    assert_eq!(
+                        paths,
+                        expected,
+                        "{:?}, server side: {}",
+                        tie_breaker,
+                        server_side_scoring
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_progress_events() -> Result<()> {
+        let docs = TempDir::new()?;
+        for i in 0..3 {
+            fs::write(docs.path().join(format!("note{}.txt", i)), format!("Note number {}", i))?;
+        }
+
+        let events = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let config = FileEmbeddingConfig {
+            progress: Some(
+                ProgressCallback::new(move |progress| {
+                    recorded.lock().unwrap().push(progress.clone());
+                })
+            ),
+            ..Default::default()
+        };
+        let (system, _temp_dir) = setup_test_system_with_config(config).await;
+        system.index_directory(docs.path().to_path_buf()).await?;
+
+        let events = events.lock().unwrap();
+        let done: Vec<usize> = events
+            .iter()
+            .map(|event| event.done)
+            .collect();
+        assert_eq!(done, vec![1, 2, 3]);
+        assert!(events.iter().all(|event| event.total == 3 && event.eta.is_some()));
+        assert_eq!(events[2].eta, Some(std::time::Duration::ZERO));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_index_directory_since() -> Result<()> {
+        let docs = TempDir::new()?;
+        let old = docs.path().join("old.txt");
+        let new = docs.path().join("new.txt");
+        fs::write(&old, "Notes from last year's planning.")?;
+        fs::write(&new, "Rust is a systems programming language.")?;
+
+        let cutoff = std::time::SystemTime::now() - std::time::Duration::from_secs(3600);
+        fs::File
+            ::options()
+            .write(true)
+            .open(&old)?
+            .set_modified(cutoff - std::time::Duration::from_secs(3600))?;
+
+        let (system, _temp_dir) = setup_test_system().await;
+        let report = system.index_directory_since(docs.path().to_path_buf(), cutoff).await?;
+        assert_eq!(report.indexed, vec![new]);
+        assert_eq!(report.unchanged, 1);
+        assert!(report.started_at.is_some_and(|started| started > cutoff));
+        assert!(system.get_file(old.to_str().unwrap()).await?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_on_duplicate_policies() -> Result<()> {
+        let docs = TempDir::new()?;
+        let file_path = docs.path().join("notes.txt");
+
+        for policy in [OnDuplicate::Error, OnDuplicate::Skip, OnDuplicate::Update] {
+            let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+                on_duplicate: policy,
+                ..Default::default()
+            }).await;
+            fs::write(&file_path, "Rust is a systems programming language.")?;
+            assert_eq!(system.index_file(file_path.clone()).await?, IndexOutcome::Created);
+
+            fs::write(&file_path, "Tomatoes grow best in full sun.")?;
+            let second = system.index_file(file_path.clone()).await;
+            let stored = system.get_file(file_path.to_str().unwrap()).await?.expect("Indexed");
+            match policy {
+                OnDuplicate::Error => {
+                    match &second {
+                        Err(FileEmbeddingError::DuplicatePath(path)) => assert_eq!(path, &stored.path),
+                        other => panic!("Expected DuplicatePath, got {:?}", other),
+                    }
+                    assert!(stored.content_preview.starts_with("Rust"));
+                }
+                OnDuplicate::Skip => {
+                    assert_eq!(second?, IndexOutcome::Skipped);
+                    assert!(stored.content_preview.starts_with("Rust"));
+                }
+                OnDuplicate::Update => {
+                    assert_eq!(second?, IndexOutcome::Updated);
+                    assert!(stored.content_preview.starts_with("Tomatoes"));
+                }
+            }
+            assert_eq!(system.recent(10).await?.len(), 1);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_shared_model() -> Result<()> {
+        let model: Arc<dyn Embedder> = Arc::new(FastEmbedder::new(EmbeddingModel::AllMiniLML6V2)?);
+        let first_dir = TempDir::new()?;
+        let second_dir = TempDir::new()?;
+        let first = FileEmbeddingSystem::with_shared_model(
+            first_dir.path().join("db").to_str().unwrap(),
+            FileEmbeddingConfig::default(),
+            model.clone()
+        ).await?;
+        let second = FileEmbeddingSystem::with_shared_model(
+            second_dir.path().join("db").to_str().unwrap(),
+            FileEmbeddingConfig::default(),
+            model.clone()
+        ).await?;
+        assert_eq!(Arc::strong_count(&model), 3);
+
+        first.index_content("rust.txt", "Rust is a systems programming language.", Some("txt")).await?;
+        second.index_content("garden.txt", "Tomatoes grow best in full sun.", Some("txt")).await?;
+        for (system, expected) in [(&first, "rust.txt"), (&second, "garden.txt")] {
+            let stored = system.get_file(expected).await?.expect("Indexed");
+            assert_eq!(stored.content_embedding.len(), model.dimension());
+            let results = system.search("vegetables and programming", &SearchOptions::new(5)).await?;
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].file.path, expected);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_score_decimals_only_round_hits() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+        system.index_content("rust.txt", "Rust is a systems programming language.", None).await?;
+
+        let options = SearchOptions {
+            score_decimals: Some(4),
+            ..SearchOptions::new(5)
+        };
+        let results = system.search("memory safe languages", &options).await?;
+        let hits = system.search_hits("memory safe languages", &options).await?;
+        assert_eq!(hits[0].score, round_score(results[0].score, 4));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_vanished_file_is_not_a_failure() -> Result<()> {
+        let (system, temp_dir) = setup_test_system().await;
+        let file_path = temp_dir.path().join("deleted.txt");
+        fs::write(&file_path, "Short-lived editor backup")?;
+        fs::remove_file(&file_path)?;
+
+        let mut report = IndexReport::default();
+        system.index_into_report(file_path, &mut report).await?;
+        assert_eq!(report.vanished, 1);
+        assert!(report.failed.is_empty(), "Vanished file counted as failed: {:?}", report.failed);
+        assert!(report.indexed.is_empty() && report.skipped.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_preview_chars_by_extension() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            preview_chars: 40,
+            preview_chars_by_extension: std::collections::HashMap::from([
+                ("pdf".to_string(), 10),
+                ("md".to_string(), 80),
+            ]),
+            ..Default::default()
+        }).await;
+        let text = "Rust is a systems programming language. ".repeat(5);
+        system.index_content("report.pdf", &text, Some("pdf")).await?;
+        system.index_content("notes.md", &text, Some("md")).await?;
+        system.index_content("notes.txt", &text, Some("txt")).await?;
+
+        for (path, expected) in [("report.pdf", 10), ("notes.md", 80), ("notes.txt", 40)] {
+            let stored = system.get_file(path).await?.expect("Indexed");
+            assert_eq!(stored.content_preview.chars().count(), expected, "Preview of {}", path);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_rename_file_keeps_embedding() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+        system.index_content("/notes/draft.txt", "Rust is a systems programming language.", Some("txt")).await?;
+        system.index_content("/notes/taken.md", "Tomatoes grow best in full sun.", Some("md")).await?;
+        let before = system.get_file("/notes/draft.txt").await?.expect("Indexed");
+
+        assert!(system.rename_file("/notes/draft.txt", "/archive/Rust.MD").await?);
+        assert!(system.get_file("/notes/draft.txt").await?.is_none());
+        let after = system.get_file("/archive/Rust.MD").await?.expect("Renamed");
+        assert_eq!(after.name, "Rust.MD");
+        assert_eq!(after.extension.as_deref(), Some("md"));
+        assert_eq!(after.mime_type.as_deref(), Some("text/markdown"));
+        assert_eq!(after.content_embedding, before.content_embedding);
+        assert_eq!(after.indexed_at, before.indexed_at, "Rename should not re-embed");
+
+        let results = system.hybrid_search("rust programming", 1).await?;
+        assert_eq!(results[0].file.path, "/archive/Rust.MD");
+
+        match system.rename_file("/archive/Rust.MD", "/notes/taken.md").await {
+            Err(FileEmbeddingError::DuplicatePath(path)) => assert_eq!(path, "/notes/taken.md"),
+            other => panic!("Expected DuplicatePath, got {:?}", other),
+        }
+        assert!(!system.rename_file("/notes/missing.txt", "/notes/other.txt").await?);
+        assert_eq!(system.recent(10).await?.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sentence_aggregation() -> Result<()> {
+        let content =
+            "Rust is a systems programming language with a borrow checker. \
+             Tomatoes grow best in full sun and need regular watering. \
+             The quarterly budget meeting moved to Thursday.";
+        let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            aggregation: Aggregation::MeanSentences,
+            ..Default::default()
+        }).await;
+        system.index_content("mixed.txt", content, Some("txt")).await?;
+
+        let stored = system.get_file("mixed.txt").await?.expect("Indexed");
+        assert_eq!(stored.sentence_embeddings.len(), 3);
+        let whole_doc = system.embedder.embed(&[content], None)?.remove(0);
+        let pooled = Aggregation::MeanSentences.pool(&stored.sentence_embeddings).unwrap();
+        assert!(cosine_similarity(&stored.content_embedding, &pooled) > 0.999);
+        assert!(
+            cosine_similarity(&stored.content_embedding, &whole_doc) < 0.999,
+            "Pooled embedding should differ from the whole-document one"
+        );
+
+        let results = system.hybrid_search("vegetable gardening", 1).await?;
+        assert_eq!(
+            results[0].file.content_preview,
+            "Tomatoes grow best in full sun and need regular watering."
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_verify_and_repair_index() -> Result<()> {
+        let (system, temp_dir) = setup_test_system().await;
+        let file_path = temp_dir.path().join("rust.txt");
+        fs::write(&file_path, "Rust is a systems programming language.")?;
+        system.index_file(file_path.clone()).await?;
+        system.index_content("virtual.txt", "Tomatoes grow best in full sun.", Some("txt")).await?;
+        system.index_content("fine.txt", "The quarterly budget meeting moved.", Some("txt")).await?;
+        let stored_path = file_path.to_str().unwrap();
+        // As if written by a model with a different dimension
+        for (path, embedding) in [(stored_path, vec![0.6, 0.8]), ("virtual.txt", vec![0.8, 0.6])] {
+            system.db
+                .query("UPDATE files SET content_embedding = $embedding WHERE path = $path")
+                .bind(("embedding", embedding))
+                .bind(("path", path)).await?
+                .check()?;
+        }
+
+        let report = system.verify_index().await?;
+        assert_eq!(report.checked, 3);
+        let wrong_dimension = IndexIssue::WrongDimension { expected: 384, actual: 2 };
+        assert_eq!(report.issues_for(stored_path), vec![&wrong_dimension]);
+        assert_eq!(report.issues_for("virtual.txt"), vec![&wrong_dimension]);
+        assert!(report.issues_for("fine.txt").is_empty());
+
+        let repaired = system.repair_index(&RepairOptions::default()).await?;
+        assert_eq!(repaired.reembedded, vec![stored_path.to_string()]);
+        // Its content wasn't stored, so there is nothing to re-embed it from
+        assert_eq!(repaired.dropped, vec!["virtual.txt".to_string()]);
+        assert_eq!(system.get_file(stored_path).await?.unwrap().content_embedding.len(), 384);
+        assert!(system.verify_index().await?.is_clean());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_structured_files_are_flattened() -> Result<()> {
+        let (system, temp_dir) = setup_test_system().await;
+        let config_path = temp_dir.path().join("service.json");
+        fs::write(
+            &config_path,
+            r#"{"database": {"engine": "postgres", "replicas": [{"region": "frankfurt"}]}, "owner": "payments team"}"#
+        )?;
+        let notes_path = temp_dir.path().join("notes.txt");
+        fs::write(&notes_path, "Tomatoes grow best in full sun.")?;
+        system.index_file(config_path.clone()).await?;
+        system.index_file(notes_path).await?;
+
+        let stored = system.get_file(config_path.to_str().unwrap()).await?.expect("Indexed");
+        assert!(stored.content_preview.contains("database.replicas[0].region: frankfurt"));
+        assert!(!stored.content_preview.contains(['{', '"']), "Raw JSON in preview: {}", stored.content_preview);
+        assert_eq!(stored.top_level_keys, vec!["database", "owner"]);
+
+        let results = system.hybrid_search("postgres replica in frankfurt", 1).await?;
+        assert_eq!(results[0].file.path, stored.path);
+
+        let options = SearchOptions {
+            filter: SearchFilter::new().top_level_key("owner").build()?,
+            ..SearchOptions::new(5)
+        };
+        let results = system.search("gardening", &options).await?;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].file.path, stored.path);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_skip_reasons() -> Result<()> {
+        let docs = TempDir::new()?;
+        fs::write(docs.path().join("notes.txt"), "Rust is a systems programming language.")?;
+        fs::write(docs.path().join("photo.png"), [0x89, b'P', b'N', b'G', 0x00, 0x1a, 0x00])?;
+        let photo = docs.path().join("photo.png");
+        let notes = docs.path().join("notes.txt");
+        let empty = docs.path().join("PLACEHOLDER");
+        fs::write(&empty, "")?;
+
+        let (system, _temp_dir) = setup_test_system().await;
+        let mut report = system.index_directory(docs.path().to_path_buf()).await?;
+        report.skipped.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(report.skipped, vec![
+            (empty.clone(), SkipReason::UnsupportedExtension("unknown".to_string())),
+            (photo.clone(), SkipReason::UnsupportedExtension("png".to_string())),
+        ]);
+
+        let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            index_extensionless_text: true,
+            on_duplicate: OnDuplicate::Skip,
+            ..Default::default()
+        }).await;
+        let mut report = system.index_directory(docs.path().to_path_buf()).await?;
+        report.skipped.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(report.skipped, vec![
+            (empty.clone(), SkipReason::Empty),
+            (photo.clone(), SkipReason::Binary),
+        ]);
+        assert_eq!(report.indexed, vec![notes.clone()]);
+
+        let mut report = system.index_directory(docs.path().to_path_buf()).await?;
+        report.skipped.sort_by(|a, b| a.0.cmp(&b.0));
+        assert_eq!(report.skipped, vec![
+            (empty, SkipReason::Empty),
+            (notes, SkipReason::AlreadyIndexed),
+            (photo, SkipReason::Binary),
+        ]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_fit_reduction() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+        let docs = [
+            ("rust.txt", "Rust is a systems programming language with a borrow checker."),
+            ("cargo.txt", "Cargo builds Rust crates and manages their dependencies."),
+            ("tomatoes.txt", "Tomatoes grow best in full sun with regular watering."),
+            ("compost.txt", "Compost improves garden soil for vegetables."),
+            ("budget.txt", "The quarterly budget review covers revenue and expenses."),
+            ("invoice.txt", "Send the invoice to accounting before the end of the month."),
+        ];
+        for (path, content) in docs {
+            system.index_content(path, content, Some("txt")).await?;
+        }
+        let queries = ["compiling rust code", "growing vegetables", "company finances"];
+        let mut before = Vec::new();
+        for query in queries {
+            before.push(system.hybrid_search(query, 1).await?[0].file.path.clone());
+        }
+
+        assert_eq!(system.fit_reduction(5).await?, docs.len());
+        assert_eq!(system.get_file("rust.txt").await?.unwrap().content_embedding.len(), 5);
+        for (query, expected) in queries.iter().zip(&before) {
+            let results = system.hybrid_search(query, 1).await?;
+            assert_eq!(&results[0].file.path, expected, "Top result for {:?} changed", query);
+        }
+
+        system.index_content("soup.txt", "A recipe for tomato soup.", Some("txt")).await?;
+        assert_eq!(system.get_file("soup.txt").await?.unwrap().content_embedding.len(), 5);
+        assert!(system.verify_index().await?.is_clean());
+        assert!(matches!(system.fit_reduction(3).await, Err(FileEmbeddingError::Reduction(_))));
+
+        Ok(())
+    }
+
+    /// Counts calls to the wrapped embedder.
+    struct CountingEmbedder {
+        inner: MockEmbedder,
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    impl Embedder for CountingEmbedder {
+        fn embed(&self, texts: &[&str], batch_size: Option<usize>) -> Result<Vec<Vec<f32>>> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            self.inner.embed(texts, batch_size)
+        }
+
+        fn dimension(&self) -> usize {
+            self.inner.dimension()
+        }
+    }
+
+    /// Drops the last vector of every batch, like a faulty custom embedder.
+    struct DroppingEmbedder(MockEmbedder);
+
+    impl Embedder for DroppingEmbedder {
+        fn embed(&self, texts: &[&str], batch_size: Option<usize>) -> Result<Vec<Vec<f32>>> {
+            let mut embeddings = self.0.embed(texts, batch_size)?;
+            embeddings.pop();
+            Ok(embeddings)
+        }
+
+        fn dimension(&self) -> usize {
+            self.0.dimension()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_short_embedder_output_is_an_error() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let system = FileEmbeddingSystem::with_embedder(
+            temp_dir.path().join("db").to_str().unwrap(),
+            FileEmbeddingConfig::default(),
+            Box::new(DroppingEmbedder(MockEmbedder::new(64)))
+        ).await?;
+
+        let indexed = system.index_content("notes.txt", "Rust ownership and borrowing notes.", None).await;
+        assert!(
+            matches!(&indexed, Err(FileEmbeddingError::Embedding(message)) if message.contains("0 vectors for 1")),
+            "Unexpected result: {:?}",
+            indexed
+        );
+        let searched = system.search("rust", &SearchOptions::new(5)).await;
+        assert!(searched.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_embed_batch_makes_one_model_call() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let embedder = Arc::new(CountingEmbedder {
+            inner: MockEmbedder::new(64),
+            calls: std::sync::atomic::AtomicUsize::new(0),
+        });
+        let system = FileEmbeddingSystem::with_shared_model(
+            temp_dir.path().join("db").to_str().unwrap(),
+            FileEmbeddingConfig::default(),
+            embedder.clone()
+        ).await?;
+        let contents = ["Rust ownership notes.", "Tomato garden plans.", "Train timetable to Lyon."];
+        let record = |i: usize| FileRecord {
+            path: format!("note{}.txt", i),
+            extension: Some("txt".to_string()),
+            ..Default::default()
+        };
+
+        let mut records: Vec<FileRecord> = (0..contents.len()).map(record).collect();
+        let mut items: Vec<(&mut FileRecord, &str)> = records.iter_mut().zip(contents).collect();
+        assert!(system.embed_batch(&mut items)?.iter().all(Result::is_ok));
+        assert_eq!(embedder.calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+
+        for (i, batched) in records.iter().enumerate() {
+            let mut single = record(i);
+            system.embed_into(&mut single, contents[i])?;
+            assert_eq!(single.content_embedding, batched.content_embedding);
+            assert_eq!(single.content_preview, batched.content_preview);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_visit_stops_early() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let embedder = Arc::new(CountingEmbedder {
+            inner: MockEmbedder::new(64),
+            calls: std::sync::atomic::AtomicUsize::new(0),
+        });
+        let mut system = FileEmbeddingSystem::with_shared_model(
+            temp_dir.path().join("db").to_str().unwrap(),
+            FileEmbeddingConfig {
+                preview_strategy: PreviewStrategy::BestMatch,
+                ..Default::default()
+            },
+            embedder.clone()
+        ).await?;
+        for i in 0..5 {
+            let content = format!("Rust note {} about ownership. Later lines talk about borrowing.", i);
+            system.index_content(&format!("note_{}.txt", i), &content, Some("txt")).await?;
+        }
+        // Single-chunk documents stored no chunk vectors, so every preview has to embed
+        system.config.preview_chars = 20;
+        let calls = || embedder.calls.load(std::sync::atomic::Ordering::SeqCst);
+
+        let before = calls();
+        let mut seen = Vec::new();
+        let visited = system.hybrid_search_visit("rust ownership", 5, |result| {
+            seen.push(result.file.path);
+            ControlFlow::Break(())
+        }).await?;
+        assert_eq!((visited, seen.len()), (1, 1));
+        assert_eq!(calls() - before, 2, "Expected the query and one preview to be embedded");
+
+        let before = calls();
+        let visited = system.hybrid_search_visit("rust ownership", 5, |_| ControlFlow::Continue(())).await?;
+        assert_eq!(visited, 5);
+        assert_eq!(calls() - before, 6);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_dry_run_writes_nothing() -> Result<()> {
+        let docs = TempDir::new()?;
+        let notes = docs.path().join("notes.txt");
+        let photo = docs.path().join("photo.png");
+        let readme = docs.path().join("nested").join("README.md");
+        fs::create_dir(docs.path().join("nested"))?;
+        fs::write(&notes, "Rust is a systems programming language.")?;
+        fs::write(&photo, [0x89, b'P', b'N', b'G', 0x00, 0x1a, 0x00])?;
+        fs::write(&readme, "# Readme")?;
+
+        let (system, _temp_dir) = setup_test_system().await;
+        let mut plan = system.index_directory_dry_run(docs.path().to_path_buf()).await?;
+        plan.files.sort_by(|a, b| a.path.cmp(&b.path));
+        assert_eq!(plan.files, vec![
+            PlannedFile {
+                path: readme,
+                size_bytes: 8,
+                extension: Some("md".to_string()),
+                mime_type: Some("text/markdown".to_string()),
+            },
+            PlannedFile {
+                path: notes.clone(),
+                size_bytes: 39,
+                extension: Some("txt".to_string()),
+                mime_type: Some("text/plain".to_string()),
+            },
+        ]);
+        assert_eq!(plan.skipped, vec![
+            (photo, SkipReason::UnsupportedExtension("png".to_string())),
+        ]);
+        assert!(plan.failed.is_empty());
+        assert_eq!(plan.total_bytes(), 47);
+        assert_eq!(plan.estimated_embeddings(), 2);
+        assert_eq!(
+            plan.estimated_time(std::time::Duration::from_millis(50)),
+            std::time::Duration::from_millis(100)
+        );
+        assert!(system.recent(10).await?.is_empty(), "A dry run must not store anything");
+
+        let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            on_duplicate: OnDuplicate::Skip,
+            ..Default::default()
+        }).await;
+        system.index_file(notes.clone()).await?;
+        let plan = system.index_directory_dry_run(docs.path().to_path_buf()).await?;
+        assert_eq!(plan.files.len(), 1);
+        assert!(plan.skipped.contains(&(notes, SkipReason::AlreadyIndexed)));
+        assert_eq!(system.recent(10).await?.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_indexing_matches_serial() -> Result<()> {
+        let docs = TempDir::new()?;
+        let topics = ["Rust ownership", "tomato gardens", "tax returns", "train schedules"];
+        for i in 0..10 {
+            let content = format!("Note {} about {}.", i, topics[i % topics.len()]);
+            fs::write(docs.path().join(format!("note_{:02}.txt", i)), content)?;
+        }
+        fs::write(docs.path().join("photo.png"), [0x89, b'P', b'N', b'G'])?;
+
+        let (serial, _serial_dir) = setup_test_system().await;
+        let serial_report = serial.index_directory(docs.path().to_path_buf()).await?;
+        let (pipelined, _pipelined_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            extract_concurrency: 4,
+            pipeline_batch_size: 3,
+            ..Default::default()
+        }).await;
+        let pipelined_report = pipelined.index_directory(docs.path().to_path_buf()).await?;
+
+        assert_eq!(pipelined_report.indexed, serial_report.indexed);
+        assert_eq!(pipelined_report.skipped, serial_report.skipped);
+        assert_eq!(pipelined_report.indexed.len(), 10);
+
+        let records = |mut records: Vec<FileRecord>| {
+            records.sort_by(|a, b| a.path.cmp(&b.path));
+            records
+                .into_iter()
+                .map(|r| (r.path, r.content_preview, r.content_embedding))
+                .collect::<Vec<_>>()
+        };
+        let expected = records(serial.recent(100).await?);
+        let actual = records(pipelined.recent(100).await?);
+        assert_eq!(actual.len(), expected.len());
+        for (actual, expected) in actual.iter().zip(&expected) {
+            assert_eq!((&actual.0, &actual.1), (&expected.0, &expected.1));
+            let similarity = cosine_similarity(&actual.2, &expected.2);
+            assert!(similarity > 0.9999, "{} embedded differently", actual.0);
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_invalid_config_leaves_index_alone() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let db_path = temp_dir.path().join("db");
+        let db_path = db_path.to_str().unwrap();
+        let open = |config: FileEmbeddingConfig| {
+            FileEmbeddingSystem::with_embedder(db_path, config, Box::new(MockEmbedder::new(64)))
+        };
+        let system = open(FileEmbeddingConfig::default()).await?;
+        system.index_content("notes.txt", "Rust ownership and borrowing notes.", None).await?;
+        drop(system);
+
+        let invalid = FileEmbeddingConfig {
+            extract_concurrency: 0,
+            ..Default::default()
+        };
+        let error = match open(invalid).await {
+            Ok(_) => panic!("extract_concurrency 0 should be rejected"),
+            Err(e) => e,
+        };
+        assert!(
+            matches!(error.downcast_ref(), Some(FileEmbeddingError::InvalidConfig(_))),
+            "Unexpected error: {}",
+            error
+        );
+
+        let mut read_only = FileEmbeddingConfig::default();
+        read_only.storage.read_only = true;
+        let system = reopen_with(|| open(read_only.clone())).await?;
+        assert!(system.get_file("notes.txt").await?.is_some(), "A rejected config must not reset the index");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pipelined_stop_on_first_returns() -> Result<()> {
+        let docs = TempDir::new()?;
+        for i in 0..40 {
+            fs::write(docs.path().join(format!("note_{:02}.txt", i)), format!("Note {} about Rust.", i))?;
+        }
+        let temp_dir = TempDir::new()?;
+        let system = FileEmbeddingSystem::with_embedder(
+            temp_dir.path().join("db").to_str().unwrap(),
+            FileEmbeddingConfig {
+                extract_concurrency: 2,
+                pipeline_batch_size: 1,
+                on_duplicate: OnDuplicate::Error,
+                index_error_policy: IndexErrorPolicy::StopOnFirst,
+                ..Default::default()
+            },
+            Box::new(MockEmbedder::new(64))
+        ).await?;
+        assert_eq!(system.index_directory(docs.path().to_path_buf()).await?.indexed.len(), 40);
+
+        // Every file is now a duplicate, so the first one fails with far more still queued
+        // than the channel holds
+        let rerun = tokio::time
+            ::timeout(
+                std::time::Duration::from_secs(30),
+                system.index_directory(docs.path().to_path_buf())
+            ).await
+            .expect("StopOnFirst must stop the extraction stage too");
+        assert!(matches!(rerun, Err(FileEmbeddingError::DuplicatePath(_))), "Unexpected: {:?}", rerun);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_open_requires_initialize() -> Result<()> {
+        let temp_dir = TempDir::new()?;
+        let db_path = temp_dir.path().join("test_db");
+        let db_path = db_path.to_str().unwrap();
+
+        let error = match FileEmbeddingSystem::open(db_path).await {
+            Ok(_) => panic!("Opening a never-initialized index should fail"),
+            Err(e) => e,
+        };
+        assert!(
+            matches!(error.downcast_ref(), Some(FileEmbeddingError::SchemaMissing(_))),
+            "Unexpected error: {}",
+            error
+        );
+        assert!(error.to_string().contains("initialize"), "Unhelpful error: {}", error);
+
+        let system = reopen_with(|| FileEmbeddingSystem::initialize(db_path)).await?;
+        system.index_content("notes.txt", "Rust is a systems programming language.", None).await?;
+        drop(system);
+
+        let system = reopen_with(|| FileEmbeddingSystem::open(db_path)).await?;
+        assert!(system.get_file("notes.txt").await?.is_some(), "open must keep existing records");
+        system.index_content("garden.txt", "Water the tomatoes every morning.", None).await?;
+        assert_eq!(system.recent(10).await?.len(), 2);
+        drop(system);
+
+        let system = reopen_with(|| FileEmbeddingSystem::initialize(db_path)).await?;
+        assert!(system.recent(10).await?.is_empty(), "initialize must drop existing records");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_search_by_embedding() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+        system.index_content("rust.txt", "Rust is a systems programming language.", None).await?;
+        system.index_content("garden.txt", "Water the tomatoes every morning.", None).await?;
+        system.index_content("tax.txt", "File your tax return before April.", None).await?;
+
+        let garden = system.get_file("garden.txt").await?.unwrap();
+        let results = system.search_by_embedding(&garden.content_embedding, 2).await?;
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].file.path, "garden.txt");
+        assert!(results[0].score > 0.999, "Score against itself: {}", results[0].score);
+
+        let error = system.search_by_embedding(&[0.5, 0.5], 2).await.unwrap_err();
+        assert!(
+            matches!(error.downcast_ref(), Some(FileEmbeddingError::InvalidQuery(_))),
+            "Unexpected error: {}",
+            error
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_short_files_are_skipped() -> Result<()> {
+        let docs = TempDir::new()?;
+        let todo = docs.path().join("todo.txt");
+        let notes = docs.path().join("notes.txt");
+        fs::write(&todo, " ok\n")?;
+        fs::write(&notes, "Rust is a systems programming language.")?;
+
+        let (system, _temp_dir) = setup_test_system().await;
+        let report = system.index_directory(docs.path().to_path_buf()).await?;
+        assert_eq!(report.indexed, vec![notes]);
+        assert_eq!(report.skipped, vec![(todo.clone(), SkipReason::TooShort)]);
+        assert!(report.failed.is_empty());
+        assert!(matches!(
+            system.index_file(todo.clone()).await,
+            Err(FileEmbeddingError::ContentTooShort(_))
+        ));
+
+        let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            min_content_chars: 0,
+            ..Default::default()
+        }).await;
+        system.index_file(todo).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_compact_after_deletes() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+        for i in 0..6 {
+            let content = format!("Note number {} about Rust programming.", i);
+            system.index_content(&format!("note{}.txt", i), &content, None).await?;
+        }
+        for i in 0..4 {
+            assert!(system.delete_file(&format!("note{}.txt", i)).await?);
+        }
+
+        let report = system.compact().await?;
+        assert_eq!(report.indexes_rebuilt, 7);
+        assert!(report.bytes_after > 0);
+
+        let results = system.hybrid_search("rust programming", 10).await?;
+        let mut paths: Vec<&str> = results
+            .iter()
+            .map(|r| r.file.path.as_str())
+            .collect();
+        paths.sort();
+        assert_eq!(paths, vec!["note4.txt", "note5.txt"]);
+        // Writes still go through the rebuilt indexes
+        system.index_content("note5.txt", "Rewritten note about Rust.", None).await?;
+        assert_eq!(system.recent(10).await?.len(), 2);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_name_filter_ignores_case() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+        system.index_content("docs/README.md", "Setup instructions for the project.", Some("md")).await?;
+        system.index_content("docs/notes.txt", "Setup notes from the planning meeting.", Some("txt")).await?;
+
+        let matching = |filter: SearchFilter| {
+            let system = &system;
+            async move {
+                let options = SearchOptions {
+                    filter,
+                    ..SearchOptions::new(10)
+                };
+                let results = system.search("setup", &options).await?;
+                Ok::<_, anyhow::Error>(
+                    results
+                        .into_iter()
+                        .map(|r| r.file.path)
+                        .collect::<Vec<_>>()
+                )
+            }
+        };
+
+        let insensitive = SearchFilter::new().name("readme.MD").build()?;
+        assert_eq!(matching(insensitive).await?, vec!["docs/README.md"]);
+        let by_extension = SearchFilter::new().extensions(["TXT"]).build()?;
+        assert_eq!(matching(by_extension).await?, vec!["docs/notes.txt"]);
+
+        let sensitive = SearchFilter::new().name("readme.MD").case_sensitive(true).build()?;
+        assert!(matching(sensitive).await?.is_empty());
+        let exact = SearchFilter::new().name("README.md").case_sensitive(true).build()?;
+        assert_eq!(matching(exact).await?, vec!["docs/README.md"]);
+
+        system.rename_file("docs/README.md", "docs/Readme.markdown").await?;
+        let renamed = SearchFilter::new().name("readme.markdown").build()?;
+        assert_eq!(matching(renamed).await?, vec!["docs/Readme.markdown"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_case_sensitive_extension_filter() -> Result<()> {
+        let (system, temp_dir) = setup_test_system().await;
+        let upper = temp_dir.path().join("LEGACY.TXT");
+        fs::write(&upper, "Setup instructions from the old system.")?;
+        system.index_file(upper.clone()).await?;
+        system.index_content("docs/notes.txt", "Setup notes from the planning meeting.", Some("txt")).await?;
+
+        let matching = |filter: SearchFilter| {
+            let system = &system;
+            async move {
+                let options = SearchOptions {
+                    filter,
+                    ..SearchOptions::new(10)
+                };
+                let mut paths: Vec<String> = system
+                    .search("setup", &options).await?
+                    .into_iter()
+                    .map(|r| r.file.path)
+                    .collect();
+                paths.sort();
+                Ok::<_, anyhow::Error>(paths)
+            }
+        };
+
+        let upper = upper.to_str().unwrap().to_string();
+        let mut both = vec!["docs/notes.txt".to_string(), upper.clone()];
+        both.sort();
+        let insensitive = SearchFilter::new().extensions(["TXT"]).build()?;
+        assert_eq!(matching(insensitive).await?, both);
+        let sensitive = SearchFilter::new().extensions(["TXT"]).case_sensitive(true).build()?;
+        assert_eq!(matching(sensitive).await?, vec![upper]);
+        let lowercase = SearchFilter::new().extensions(["txt"]).case_sensitive(true).build()?;
+        assert_eq!(matching(lowercase).await?, vec!["docs/notes.txt"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_context_chunks() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+            preview_chars: 40,
+            preview_strategy: PreviewStrategy::BestMatch,
+            ..Default::default()
+        }).await;
+        let chunks: Vec<String> = [
+            "Copyright notice. All rights reserved.",
+            "The weather was mild on the drive over.",
+            "Sourdough bread needs starter and flour.",
+            "The meeting ran late into the evening.",
+            "Invoices are due at the end of a month.",
+        ]
+            .iter()
+            .map(|chunk| format!("{:<40}", chunk))
+            .collect();
+        system.index_content("journal.txt", &chunks.concat(), None).await?;
+
+        let search = |context_chunks: usize| {
+            let system = &system;
+            async move {
+                let options = SearchOptions {
+                    context_chunks,
+                    ..SearchOptions::new(1)
+                };
+                let results = system.search("baking sourdough bread", &options).await?;
+                Ok::<_, anyhow::Error>(results[0].file.content_preview.clone())
+            }
+        };
+        assert_eq!(search(0).await?, chunks[2]);
+        assert_eq!(search(1).await?, chunks[1..4].concat());
+        assert_eq!(search(10).await?, chunks.concat());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_path_tokens_boost_folder_matches() -> Result<()> {
+        let content = "Summary of the documents collected this year.";
+        for server_side_scoring in [true, false] {
+            let (system, _temp_dir) = setup_test_system_with_config(FileEmbeddingConfig {
+                index_path_tokens: true,
+                server_side_scoring,
+                ..Default::default()
+            }).await;
+            system.index_content("projects/recipes/summary.txt", content, None).await?;
+            system.index_content("projects/taxes/summary.txt", content, None).await?;
+
+            let stored = system.get_file("projects/taxes/summary.txt").await?.unwrap();
+            assert_eq!(stored.path_tokens, vec!["projects", "taxes"]);
+
+            let results = system.hybrid_search("taxes summary", 2).await?;
+            assert_eq!(results[0].file.path, "projects/taxes/summary.txt");
+            let boost = results[0].score - results[1].score;
+            assert!((boost - 0.05).abs() < 1e-4, "Half the terms match, boost was {}", boost);
+
+            // Both files share every other token, so neither is favoured
+            let results = system.hybrid_search("projects summary", 2).await?;
+            assert!((results[0].score - results[1].score).abs() < 1e-4);
+
+            let options = SearchOptions {
+                filter: SearchFilter::new().path_token("Recipes").build()?,
+                ..SearchOptions::new(5)
+            };
+            let results = system.search("summary", &options).await?;
+            assert_eq!(results.len(), 1);
+            assert_eq!(results[0].file.path, "projects/recipes/summary.txt");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_capacity_evicts_coldest() -> Result<()> {
+        let (system, _temp_dir) = setup_test_system().await;
+        let pause = || tokio::time::sleep(std::time::Duration::from_millis(10));
+        assert!(system.set_capacity(Capacity { max_files: Some(3), ..Default::default() }).await?.is_empty());
+
+        system.index_content("a.txt", "Sourdough bread needs a starter and a long proof.", None).await?;
+        pause().await;
+        system.index_content("b.txt", "Rust ownership rules prevent data races.", None).await?;
+        pause().await;
+        system.index_content("c.txt", "Tomatoes grow best in full sun.", None).await?;
+        pause().await;
+        // Matching a makes b the coldest record
+        let options = SearchOptions::new(1);
+        let results = system.search("sourdough bread starter", &options).await?;
+        assert_eq!(results[0].file.path, "a.txt");
+        pause().await;
+        system.index_content("d.txt", "The train to Lyon leaves at noon.", None).await?;
+
+        let mut paths: Vec<String> = system
+            .recent(10).await?
+            .into_iter()
+            .map(|record| record.path)
+            .collect();
+        paths.sort();
+        assert_eq!(paths, vec!["a.txt", "c.txt", "d.txt"]);
+        let a = system.get_file("a.txt").await?.unwrap();
+        assert_eq!(a.match_count, 1);
+        assert!(a.last_matched_at.is_some());
+
+        // Shrinking the bound evicts right away, coldest first
+        let evicted = system.set_capacity(Capacity { max_files: Some(1), ..Default::default() }).await?;
+        assert_eq!(evicted, vec!["c.txt", "a.txt"]);
+
+        Ok(())
+    }
+}
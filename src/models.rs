@@ -1,18 +1,253 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime};
+
 use serde::{Deserialize, Serialize};
+use surrealdb::sql::Datetime;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+use crate::email::EmailHeaders;
+use crate::quantize;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct FileRecord {
     pub path: String,
     pub name: String,
     pub extension: Option<String>,
+    /// `extension` as written, before lowercasing, for case-sensitive
+    /// [`SearchFilter`](crate::SearchFilter) matches.
+    #[serde(default)]
+    pub raw_extension: Option<String>,
     pub mime_type: Option<String>,
     pub size_bytes: u64,
     pub content_embedding: Vec<f32>,
     pub content_preview: String,
+    /// Full extracted content, only stored when previews are computed at query time.
+    #[serde(default)]
+    pub content: Option<String>,
+    /// Whether the record came from in-memory content rather than a file on disk.
+    #[serde(default)]
+    pub is_virtual: bool,
+    /// Quantized embedding bytes, stored instead of `content_embedding` when a reduced
+    /// [`EmbeddingPrecision`](crate::quantize::EmbeddingPrecision) is configured.
+    #[serde(default, with = "serde_bytes")]
+    pub quantized_embedding: Option<Vec<u8>>,
+    /// Scale factor for `int8` quantized embeddings.
+    #[serde(default)]
+    pub quantization_scale: Option<f32>,
+    /// Vectors from ensemble models, keyed by the model's configured name.
+    #[serde(default)]
+    pub embeddings: HashMap<String, Vec<f32>>,
+    /// When the record was last (re)embedded.
+    #[serde(default)]
+    pub indexed_at: Option<Datetime>,
+    /// False for records from a metadata-only scan that haven't been embedded yet.
+    #[serde(default)]
+    pub embedded: bool,
+    /// Embeddings of each `preview_chars`-sized chunk of `content`, stored with
+    /// `BestMatch` previews so searches can pick the matching chunk without re-embedding it.
+    /// Empty for single-chunk documents.
+    #[serde(default)]
+    pub chunk_embeddings: Vec<Vec<f32>>,
+    /// Embeddings of each sentence of `content`, stored with sentence
+    /// [`Aggregation`](crate::Aggregation) and used to preview the best-matching sentence.
+    #[serde(default)]
+    pub sentence_embeddings: Vec<Vec<f32>>,
+    /// Named corpus the file was indexed into with `index_directory_as`, e.g. `"work"`.
+    #[serde(default)]
+    pub collection: Option<String>,
+    /// For a message from an mbox archive, the archive's path. The message's own `path` is
+    /// the archive path with `#<n>` appended, counting messages from 1.
+    #[serde(default)]
+    pub parent_path: Option<String>,
+    /// Top-level keys of a JSON, YAML or TOML document, for
+    /// [`SearchFilter::top_level_key`](crate::SearchFilter).
+    #[serde(default)]
+    pub top_level_keys: Vec<String>,
+    /// Words from the file's parent directories, with `index_path_tokens` on; see
+    /// [`crate::paths::directory_tokens`].
+    #[serde(default)]
+    pub path_tokens: Vec<String>,
+    /// When a search last returned this record, tracked while a
+    /// [`Capacity`](crate::Capacity) is set.
+    #[serde(default)]
+    pub last_matched_at: Option<Datetime>,
+    /// How many searches have returned this record, tracked like `last_matched_at`.
+    #[serde(default)]
+    pub match_count: u64,
+    /// Headers of `.eml` and `.mbox` messages.
+    #[serde(default)]
+    pub email: Option<EmailHeaders>,
+}
+
+impl FileRecord {
+    /// Drops every stored vector, e.g. before returning the record from a search.
+    pub fn strip_embeddings(&mut self) {
+        self.content_embedding = Vec::new();
+        self.embeddings.clear();
+        self.chunk_embeddings = Vec::new();
+        self.sentence_embeddings = Vec::new();
+        self.quantized_embedding = None;
+        self.quantization_scale = None;
+    }
+
+    /// Restores `content_embedding` from its quantized form, if the record has one.
+    pub fn dequantize_embedding(&mut self) {
+        if let Some(bytes) = &self.quantized_embedding {
+            self.content_embedding = quantize::dequantize(bytes, self.quantization_scale);
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct SearchResult {
     pub file: FileRecord,
     pub score: f32,
-}
\ No newline at end of file
+    /// Set for results that only came from a loosened fallback search.
+    #[serde(default)]
+    pub low_confidence: bool,
+    /// `content_preview` with query terms wrapped in markers, when
+    /// [`SearchOptions::highlight`](crate::SearchOptions) is set.
+    #[serde(default)]
+    pub snippet: Option<String>,
+}
+
+/// The fields of a [`SearchResult`] a UI or API typically shows, without the stored record.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+pub struct SearchHit {
+    pub path: String,
+    pub name: String,
+    pub extension: Option<String>,
+    pub score: f32,
+    /// The result's highlighted snippet, or its `content_preview` without highlighting.
+    pub snippet: String,
+    pub low_confidence: bool,
+}
+
+impl From<SearchResult> for SearchHit {
+    fn from(result: SearchResult) -> Self {
+        Self {
+            path: result.file.path,
+            name: result.file.name,
+            extension: result.file.extension,
+            score: result.score,
+            snippet: result.snippet.unwrap_or(result.file.content_preview),
+            low_confidence: result.low_confidence,
+        }
+    }
+}
+
+/// Which files an indexing run stored, skipped and failed on.
+#[derive(Debug, Clone, Default)]
+pub struct IndexReport {
+    /// Collection the files were indexed into, if any.
+    pub collection: Option<String>,
+    /// When the run started; pass it to `index_directory_since` on the next run to pick up
+    /// only what changed in between.
+    pub started_at: Option<SystemTime>,
+    /// Files embedded and stored.
+    pub indexed: Vec<PathBuf>,
+    /// Files left out, with why.
+    pub skipped: Vec<(PathBuf, SkipReason)>,
+    /// Files that failed, with the error they failed with.
+    pub failed: Vec<(PathBuf, String)>,
+    /// Files left alone because they weren't modified since the cutoff.
+    pub unchanged: usize,
+    /// Files deleted between being listed and being read, e.g. editor temp files in an
+    /// active directory.
+    pub vanished: usize,
+}
+
+/// What `embed_pending` did with the records it tried.
+#[derive(Debug, Clone, Default)]
+pub struct PendingReport {
+    /// Paths embedded and stored.
+    pub embedded: Vec<String>,
+    /// Paths dropped from the index because the file is gone or too short, with why.
+    pub dropped: Vec<(String, String)>,
+    /// Paths that failed to read and stay pending, with the error they failed with.
+    pub failed: Vec<(String, String)>,
+}
+
+/// Why an indexing run left a file out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// The extension isn't indexed; holds the extension, or `"unknown"` without one.
+    UnsupportedExtension(String),
+    /// The extension isn't indexed and the content doesn't sniff as text, with
+    /// `index_extensionless_text` on.
+    Binary,
+    /// The extension isn't indexed and the file is empty, so there was nothing to sniff.
+    Empty,
+    /// The path was already indexed and left alone under `OnDuplicate::Skip`.
+    AlreadyIndexed,
+    /// The extracted text is shorter than `min_content_chars`.
+    TooShort,
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::UnsupportedExtension(extension) => {
+                write!(f, "unsupported extension ({})", extension)
+            }
+            SkipReason::Binary => f.write_str("binary content"),
+            SkipReason::Empty => f.write_str("empty file"),
+            SkipReason::AlreadyIndexed => f.write_str("already indexed"),
+            SkipReason::TooShort => f.write_str("too little content"),
+        }
+    }
+}
+
+/// A file an indexing run would store, as found by `index_directory_dry_run`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedFile {
+    pub path: PathBuf,
+    pub size_bytes: u64,
+    pub extension: Option<String>,
+    pub mime_type: Option<String>,
+}
+
+/// What `index_directory` would do, without having embedded or stored anything.
+#[derive(Debug, Clone, Default)]
+pub struct IndexPlan {
+    /// Files that would be embedded and stored.
+    pub files: Vec<PlannedFile>,
+    /// Files that would be left out, with why.
+    pub skipped: Vec<(PathBuf, SkipReason)>,
+    /// Files that would fail, with the error they would fail with.
+    pub failed: Vec<(PathBuf, String)>,
+    /// Models each file is embedded with: the primary plus any ensemble members.
+    pub models: usize,
+}
+
+impl IndexPlan {
+    pub fn total_bytes(&self) -> u64 {
+        self.files
+            .iter()
+            .map(|file| file.size_bytes)
+            .sum()
+    }
+
+    /// Document embeddings the run would compute. Chunk and sentence embeddings come on
+    /// top, and an mbox archive counts as one file however many messages it holds.
+    pub fn estimated_embeddings(&self) -> usize {
+        self.files.len() * self.models
+    }
+
+    /// How long the run would take at `per_embedding`, e.g. as measured on a sample file.
+    pub fn estimated_time(&self, per_embedding: Duration) -> Duration {
+        per_embedding * (self.estimated_embeddings() as u32)
+    }
+}
+
+/// What happened to a record when content was indexed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexOutcome {
+    /// A new record was created.
+    Created,
+    /// An existing record with the same path was replaced.
+    Updated,
+    /// The path was already indexed and left alone under `OnDuplicate::Skip`.
+    Skipped,
+}
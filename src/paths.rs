@@ -0,0 +1,97 @@
+//! Turning file paths into text that can be embedded or matched against queries.
+
+/// Number of trailing path components (including the file name) that are tokenized.
+///
+/// Leading components like `/home/me` are the same for most files and only add noise.
+const PATH_COMPONENTS: usize = 3;
+
+/// Lowercased words from the last few components of `path`, splitting on separators,
+/// punctuation and underscores: `/home/me/finance/tax_return_2023.pdf` becomes
+/// `["me", "finance", "tax", "return", "2023", "pdf"]`.
+pub fn path_terms(path: &str) -> Vec<String> {
+    let components: Vec<&str> = path
+        .split(['/', '\\'])
+        .filter(|component| !component.is_empty())
+        .collect();
+    components[components.len().saturating_sub(PATH_COMPONENTS)..]
+        .iter()
+        .flat_map(|component| component.split(|c: char| !c.is_alphanumeric()))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Distinct lowercased words from the last few directories above the file, in path order:
+/// `/home/me/projects/taxes/2023/return.pdf` becomes `["projects", "taxes", "2023"]`.
+pub fn directory_tokens(path: &str) -> Vec<String> {
+    let components: Vec<&str> = path
+        .split(['/', '\\'])
+        .filter(|component| !component.is_empty())
+        .collect();
+    let directories = &components[..components.len().saturating_sub(1)];
+    let mut tokens: Vec<String> = Vec::new();
+    for word in directories[directories.len().saturating_sub(PATH_COMPONENTS)..]
+        .iter()
+        .flat_map(|component| component.split(|c: char| !c.is_alphanumeric()))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase) {
+        if !tokens.contains(&word) {
+            tokens.push(word);
+        }
+    }
+    tokens
+}
+
+/// Fraction of `terms` that appear in `tokens`, 0 when there are no terms.
+pub fn token_overlap(tokens: &[String], terms: &[String]) -> f32 {
+    if terms.is_empty() {
+        return 0.0;
+    }
+    let matched = terms
+        .iter()
+        .filter(|term| tokens.contains(term))
+        .count();
+    (matched as f32) / (terms.len() as f32)
+}
+
+/// Prepends the path's words to `content`, so files are findable by what they're named
+/// even when their content is sparse.
+pub fn with_path_header(path: &str, content: &str) -> String {
+    format!("{}\n\n{}", path_terms(path).join(" "), content)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_path_terms() {
+        assert_eq!(path_terms("/home/me/finance/tax_return_2023.pdf"), vec![
+            "me",
+            "finance",
+            "tax",
+            "return",
+            "2023",
+            "pdf",
+        ]);
+        assert_eq!(path_terms(r"C:\Users\Me\Notes.md"), vec!["users", "me", "notes", "md"]);
+        assert_eq!(path_terms("README"), vec!["readme"]);
+        assert!(path_terms("").is_empty());
+    }
+
+    #[test]
+    fn test_directory_tokens() {
+        assert_eq!(directory_tokens("/home/me/projects/taxes/2023/return.pdf"), vec![
+            "projects",
+            "taxes",
+            "2023",
+        ]);
+        assert_eq!(directory_tokens(r"C:\Work\work_notes\todo.md"), vec!["c", "work", "notes"]);
+        assert!(directory_tokens("README").is_empty());
+
+        let tokens = directory_tokens("projects/taxes/summary.txt");
+        let terms = vec!["summary".to_string(), "taxes".to_string()];
+        assert_eq!(token_overlap(&tokens, &terms), 0.5);
+        assert_eq!(token_overlap(&tokens, &[]), 0.0);
+    }
+}
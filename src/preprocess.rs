@@ -0,0 +1,74 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{ Arc, Mutex, PoisonError };
+
+/// A content transform, called with the file's extension and its raw text.
+type Hook = dyn FnMut(&str, &str) -> String + Send;
+
+/// Per-extension transforms applied to file content before it is embedded.
+///
+/// The transformed text is what gets embedded and previewed, so a hook can strip license
+/// headers from source files, collapse whitespace in logs, and so on. Cloning the
+/// registry shares the hooks rather than copying them.
+#[derive(Clone, Default)]
+pub struct Preprocessors {
+    hooks: HashMap<String, Arc<Mutex<Hook>>>,
+}
+
+impl Preprocessors {
+    /// Registers `hook` for files with `extension`, replacing any previous hook for it.
+    ///
+    /// Extensions match case-insensitively, with or without the leading dot.
+    pub fn register<F>(&mut self, extension: &str, hook: F) -> &mut Self
+        where F: FnMut(&str, &str) -> String + Send + 'static
+    {
+        self.hooks.insert(Self::key(extension), Arc::new(Mutex::new(hook)));
+        self
+    }
+
+    /// Runs the hook registered for `extension`, or returns `None` if there isn't one.
+    pub fn apply(&self, extension: Option<&str>, content: &str) -> Option<String> {
+        let key = Self::key(extension?);
+        let hook = self.hooks.get(&key)?;
+        let mut hook = hook.lock().unwrap_or_else(PoisonError::into_inner);
+        Some(hook(&key, content))
+    }
+
+    fn key(extension: &str) -> String {
+        extension.trim_start_matches('.').to_lowercase()
+    }
+}
+
+impl fmt::Debug for Preprocessors {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut extensions: Vec<&String> = self.hooks.keys().collect();
+        extensions.sort();
+        f.debug_struct("Preprocessors").field("extensions", &extensions).finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hooks_match_by_extension() {
+        let mut calls = 0;
+        let mut preprocessors = Preprocessors::default();
+        preprocessors
+            .register(".LOG", move |_, content| {
+                calls += 1;
+                format!("{} {}", calls, content.split_whitespace().collect::<Vec<_>>().join(" "))
+            })
+            .register("rs", |extension, content| format!("{}: {}", extension, content));
+
+        assert_eq!(preprocessors.apply(Some("log"), "a   b\n\nc").as_deref(), Some("1 a b c"));
+        assert_eq!(preprocessors.apply(Some("Log"), "d").as_deref(), Some("2 d"));
+        assert_eq!(
+            preprocessors.apply(Some("rs"), "fn main() {}").as_deref(),
+            Some("rs: fn main() {}")
+        );
+        assert_eq!(preprocessors.apply(Some("md"), "text"), None);
+        assert_eq!(preprocessors.apply(None, "text"), None);
+    }
+}
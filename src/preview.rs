@@ -0,0 +1,121 @@
+/// How the `content_preview` shown for a file is chosen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PreviewStrategy {
+    /// The first `preview_chars` characters of the content.
+    #[default]
+    Head,
+    /// The `preview_chars`-sized window that best matches the query, computed at search time.
+    ///
+    /// This stores the full content alongside each record so it can be re-scored per query.
+    BestMatch,
+    /// The leading non-blank lines, skipping empty lines and whitespace-only padding.
+    FirstNonEmptyLines,
+}
+
+impl PreviewStrategy {
+    /// Builds the preview stored at index time.
+    ///
+    /// `BestMatch` stores the head as a fallback for results that can't be re-scored.
+    pub fn stored_preview(&self, content: &str, max_chars: usize) -> String {
+        match self {
+            PreviewStrategy::Head | PreviewStrategy::BestMatch => head(content, max_chars),
+            PreviewStrategy::FirstNonEmptyLines => first_non_empty_lines(content, max_chars),
+        }
+    }
+}
+
+pub fn head(content: &str, max_chars: usize) -> String {
+    content.chars().take(max_chars).collect()
+}
+
+pub fn first_non_empty_lines(content: &str, max_chars: usize) -> String {
+    let joined = content
+        .lines()
+        .map(str::trim_end)
+        .filter(|line| !line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    head(&joined, max_chars)
+}
+
+/// Formats whose text comes out of a layout-aware extractor rather than being read as-is.
+pub const BINARY_DERIVED_EXTENSIONS: &[&str] = &["pdf", "doc", "docx"];
+
+/// Tidies extracted text for display: control characters such as form feeds become spaces,
+/// and every run of whitespace (including line breaks) collapses to a single space.
+pub fn clean(content: &str) -> String {
+    content
+        .split(|c: char| c.is_whitespace() || c.is_control())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Splits content into consecutive windows of at most `max_chars` characters.
+pub fn windows(content: &str, max_chars: usize) -> Vec<String> {
+    let chars: Vec<char> = content.chars().collect();
+    chars
+        .chunks(max_chars.max(1))
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
+/// Returns the index of the highest-scoring window, preferring the earliest on ties.
+pub fn best_window(scores: &[f32]) -> Option<usize> {
+    scores
+        .iter()
+        .enumerate()
+        .fold(None, |best: Option<(usize, f32)>, (i, &score)| {
+            match best {
+                Some((_, best_score)) if best_score >= score => best,
+                _ => Some((i, score)),
+            }
+        })
+        .map(|(i, _)| i)
+}
+
+/// Window `best` joined with up to `context` windows on either side, i.e. the stretch of
+/// content they cover, since windows are consecutive.
+pub fn with_context(windows: &[String], best: usize, context: usize) -> String {
+    let start = best.saturating_sub(context);
+    let end = best.saturating_add(context).saturating_add(1).min(windows.len());
+    windows[start..end].concat()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_strategies() {
+        let content = "\n\n   \nTitle\n\n  body line one  \n\nbody line two\n";
+
+        assert_eq!(PreviewStrategy::Head.stored_preview(content, 8), "\n\n   \nTi");
+        assert_eq!(PreviewStrategy::BestMatch.stored_preview(content, 8), "\n\n   \nTi");
+        assert_eq!(
+            PreviewStrategy::FirstNonEmptyLines.stored_preview(content, 1000),
+            "Title\n  body line one\nbody line two"
+        );
+        assert_eq!(PreviewStrategy::FirstNonEmptyLines.stored_preview(content, 5), "Title");
+    }
+
+    #[test]
+    fn test_clean() {
+        let extracted = "Annual   Report\x0c\x0c\n\n  Page 2\tof\u{0}  10 \r\n";
+        assert_eq!(clean(extracted), "Annual Report Page 2 of 10");
+        assert_eq!(clean(" \x0c "), "");
+    }
+
+    #[test]
+    fn test_best_window() {
+        assert_eq!(windows("abcdefg", 3), vec!["abc", "def", "g"]);
+        assert_eq!(best_window(&[0.1, 0.7, 0.3, 0.7]), Some(1));
+        assert_eq!(best_window(&[]), None);
+
+        let parts = windows("abcdefg", 2);
+        assert_eq!(with_context(&parts, 1, 1), "abcdef");
+        assert_eq!(with_context(&parts, 0, 1), "abcd");
+        assert_eq!(with_context(&parts, 3, 5), "abcdefg");
+        assert_eq!(with_context(&parts, 2, 0), "ef");
+    }
+}
@@ -0,0 +1,86 @@
+//! Progress reporting for long indexing runs.
+
+use std::fmt;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Reported after each file an `index_directory` run processes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexProgress {
+    /// Files processed so far, including this one.
+    pub done: usize,
+    /// Files in the run.
+    pub total: usize,
+    /// The file just processed.
+    pub path: PathBuf,
+    /// Time since the run started.
+    pub elapsed: Duration,
+    /// Estimated time until the run finishes; see [`estimate_remaining`].
+    pub eta: Option<Duration>,
+}
+
+/// A callback receiving [`IndexProgress`] events, set with
+/// [`FileEmbeddingConfig::progress`](crate::FileEmbeddingConfig::progress).
+///
+/// Cloning shares the callback.
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(&IndexProgress) + Send + Sync>);
+
+impl ProgressCallback {
+    pub fn new<F>(callback: F) -> Self where F: Fn(&IndexProgress) + Send + Sync + 'static {
+        Self(Arc::new(callback))
+    }
+
+    pub(crate) fn report(&self, progress: &IndexProgress) {
+        (self.0)(progress)
+    }
+}
+
+impl fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("ProgressCallback")
+    }
+}
+
+/// Time left for `total - done` files at the average rate so far, or `None` before any
+/// file is done.
+///
+/// The first files are often unrepresentative (model warm-up, a few huge PDFs), so the
+/// estimate is rough early on and steadies as more files are averaged in.
+pub fn estimate_remaining(done: usize, total: usize, elapsed: Duration) -> Option<Duration> {
+    if done == 0 {
+        return None;
+    }
+    let per_file = elapsed.as_secs_f64() / (done as f64);
+    Some(Duration::from_secs_f64(per_file * (total.saturating_sub(done) as f64)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_estimate_remaining() {
+        assert_eq!(estimate_remaining(0, 10, Duration::from_secs(5)), None);
+        assert_eq!(
+            estimate_remaining(4, 10, Duration::from_secs(8)),
+            Some(Duration::from_secs(12))
+        );
+        assert_eq!(estimate_remaining(10, 10, Duration::from_secs(20)), Some(Duration::ZERO));
+
+        // A slow first file, then a steady 100ms per file: the estimate converges
+        let mut elapsed = Duration::from_secs(5);
+        let mut estimates = Vec::new();
+        for done in 1..=50 {
+            if done > 1 {
+                elapsed += Duration::from_millis(100);
+            }
+            estimates.push(estimate_remaining(done, 100, elapsed).unwrap());
+        }
+        assert!(estimates.windows(2).all(|pair| pair[1] < pair[0]));
+        // 9.9s for the first 50 files, so 9.9s for the other 50
+        let last = estimates.last().unwrap().as_secs_f64();
+        assert!((last - 9.9).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,129 @@
+//! Compact storage formats for embeddings.
+//!
+//! A 384-dimension embedding takes 1536 bytes as `f32`. Stored as `f16` it takes 768 bytes,
+//! and as `int8` 384 bytes plus one `f32` scale factor. Vectors are dequantized back to `f32`
+//! when loaded for scoring, which costs a little accuracy: cosine scores stay within
+//! [`F16_SCORE_TOLERANCE`] and [`INT8_SCORE_TOLERANCE`] of the `f32` baseline respectively.
+
+use half::f16;
+
+/// Bound on how far cosine scores drift from `f32` when embeddings are stored as `f16`.
+pub const F16_SCORE_TOLERANCE: f32 = 1e-3;
+/// Bound on how far cosine scores drift from `f32` when embeddings are stored as `int8`.
+pub const INT8_SCORE_TOLERANCE: f32 = 1e-2;
+
+/// How embeddings are stored in the database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmbeddingPrecision {
+    /// Full precision in `content_embedding`.
+    #[default]
+    F32,
+    /// Half precision bytes in `quantized_embedding`.
+    F16,
+    /// Signed bytes in `quantized_embedding`, scaled by `quantization_scale`.
+    Int8,
+}
+
+impl EmbeddingPrecision {
+    /// Encodes an embedding, returning the bytes and (for `Int8`) the scale factor.
+    ///
+    /// Returns `None` for `F32`, which is stored as-is.
+    pub fn quantize(&self, embedding: &[f32]) -> Option<(Vec<u8>, Option<f32>)> {
+        match self {
+            EmbeddingPrecision::F32 => None,
+            EmbeddingPrecision::F16 => {
+                let bytes = embedding
+                    .iter()
+                    .flat_map(|x| f16::from_f32(*x).to_le_bytes())
+                    .collect();
+                Some((bytes, None))
+            }
+            EmbeddingPrecision::Int8 => {
+                let max_abs = embedding.iter().fold(0.0f32, |max, x| max.max(x.abs()));
+                let scale = if max_abs > 0.0 { max_abs / 127.0 } else { 1.0 };
+                let bytes = embedding
+                    .iter()
+                    .map(|x| (x / scale).round().clamp(-127.0, 127.0) as i8 as u8)
+                    .collect();
+                Some((bytes, Some(scale)))
+            }
+        }
+    }
+}
+
+/// Decodes bytes produced by [`EmbeddingPrecision::quantize`].
+///
+/// A scale factor means the bytes are `int8`, otherwise they are `f16`.
+pub fn dequantize(bytes: &[u8], scale: Option<f32>) -> Vec<f32> {
+    match scale {
+        Some(scale) => bytes
+            .iter()
+            .map(|b| (*b as i8 as f32) * scale)
+            .collect(),
+        None => bytes
+            .chunks_exact(2)
+            .map(|pair| f16::from_le_bytes([pair[0], pair[1]]).to_f32())
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::similarity::cosine_similarity;
+
+    /// Deterministic pseudo-random unit vector, shaped like a fastembed output.
+    fn sample_embedding(seed: u64) -> Vec<f32> {
+        let mut state = seed;
+        let v: Vec<f32> = (0..384)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                ((state >> 33) as f32) / (u32::MAX as f32) - 0.25
+            })
+            .collect();
+        let norm = v
+            .iter()
+            .map(|x| x * x)
+            .sum::<f32>()
+            .sqrt();
+        v.iter()
+            .map(|x| x / norm)
+            .collect()
+    }
+
+    #[test]
+    fn test_quantized_scores_within_tolerance() {
+        let query = sample_embedding(1);
+        for seed in 2..20 {
+            let doc = sample_embedding(seed);
+            let baseline = cosine_similarity(&query, &doc);
+
+            for (precision, tolerance) in [
+                (EmbeddingPrecision::F16, F16_SCORE_TOLERANCE),
+                (EmbeddingPrecision::Int8, INT8_SCORE_TOLERANCE),
+            ] {
+                let (bytes, scale) = precision.quantize(&doc).unwrap();
+                let restored = dequantize(&bytes, scale);
+                assert_eq!(restored.len(), doc.len());
+
+                let score = cosine_similarity(&query, &restored);
+                assert!(
+                    (score - baseline).abs() <= tolerance,
+                    "{:?} drifted by {} (baseline {}, quantized {})",
+                    precision,
+                    (score - baseline).abs(),
+                    baseline,
+                    score
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_quantized_sizes() {
+        let doc = sample_embedding(7);
+        assert!(EmbeddingPrecision::F32.quantize(&doc).is_none());
+        assert_eq!(EmbeddingPrecision::F16.quantize(&doc).unwrap().0.len(), 768);
+        assert_eq!(EmbeddingPrecision::Int8.quantize(&doc).unwrap().0.len(), 384);
+    }
+}
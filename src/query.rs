@@ -0,0 +1,180 @@
+//! A small query language mixing structured filters with semantic text.
+//!
+//! ```text
+//! ext:rs,md size:>1kb "error handling" retries
+//! ```
+//!
+//! `ext:` takes a comma-separated list of extensions (any of them matches) and `size:` takes a
+//! comparison (`>`, `>=`, `<`, `<=`) against a size with an optional `b`/`kb`/`mb`/`gb` suffix.
+//! `name:` matches a file name exactly, ignoring case.
+//! Different filters are combined with AND. Everything else, quoted or not, is the semantic part
+//! of the query that gets embedded.
+
+use crate::error::FileEmbeddingError;
+use crate::filter::SearchFilter;
+
+/// A query string split into its structured filter and the text to embed.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ParsedQuery {
+    pub filter: SearchFilter,
+    pub text: String,
+}
+
+pub fn parse_query(input: &str) -> Result<ParsedQuery, FileEmbeddingError> {
+    let mut parsed = ParsedQuery::default();
+    let mut text_parts = Vec::new();
+
+    for token in tokenize(input)? {
+        match token {
+            Token::Quoted(text) => text_parts.push(text),
+            Token::Word(word) => {
+                if let Some(value) = word.strip_prefix("ext:") {
+                    parsed.filter.extensions.extend(
+                        value
+                            .split(',')
+                            .map(|ext| ext.trim_start_matches('.').to_lowercase())
+                            .filter(|ext| !ext.is_empty())
+                    );
+                } else if let Some(value) = word.strip_prefix("name:").filter(|value| !value.is_empty()) {
+                    parsed.filter.name = Some(value.to_string());
+                } else if let Some(value) = word.strip_prefix("size:") {
+                    apply_size(&mut parsed.filter, value)?;
+                } else {
+                    text_parts.push(word);
+                }
+            }
+        }
+    }
+
+    parsed.text = text_parts.join(" ");
+    Ok(parsed)
+}
+
+enum Token {
+    Word(String),
+    Quoted(String),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, FileEmbeddingError> {
+    let mut tokens = Vec::new();
+    let mut chars = input.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut quoted = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some(c) => quoted.push(c),
+                    None => {
+                        return Err(
+                            FileEmbeddingError::InvalidQuery(String::from("unterminated quote"))
+                        );
+                    }
+                }
+            }
+            let quoted = quoted.split_whitespace().collect::<Vec<_>>().join(" ");
+            if !quoted.is_empty() {
+                tokens.push(Token::Quoted(quoted));
+            }
+        } else {
+            let mut word = String::new();
+            while let Some(&c) = chars.peek() {
+                if c.is_whitespace() || c == '"' {
+                    break;
+                }
+                word.push(c);
+                chars.next();
+            }
+            tokens.push(Token::Word(word));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn apply_size(filter: &mut SearchFilter, value: &str) -> Result<(), FileEmbeddingError> {
+    let invalid = || FileEmbeddingError::InvalidQuery(format!("invalid size filter: size:{}", value));
+
+    let (op, amount) = [">=", "<=", ">", "<"]
+        .iter()
+        .find_map(|op| value.strip_prefix(op).map(|rest| (*op, rest)))
+        .ok_or_else(invalid)?;
+    let bytes = parse_size(amount).ok_or_else(invalid)?;
+
+    match op {
+        ">=" => {
+            filter.min_size = Some(bytes);
+        }
+        ">" => {
+            filter.min_size = Some(bytes.saturating_add(1));
+        }
+        "<=" => {
+            filter.max_size = Some(bytes);
+        }
+        _ => {
+            filter.max_size = Some(bytes.saturating_sub(1));
+        }
+    }
+    Ok(())
+}
+
+/// Parses sizes like `512`, `512b`, `1kb`, `1.5mb` or `2gb` into bytes.
+fn parse_size(value: &str) -> Option<u64> {
+    let value = value.to_lowercase();
+    let split = value.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(value.len());
+    let (number, unit) = value.split_at(split);
+    let multiplier: u64 = match unit {
+        "" | "b" => 1,
+        "kb" | "k" => 1024,
+        "mb" | "m" => 1024 * 1024,
+        "gb" | "g" => 1024 * 1024 * 1024,
+        _ => {
+            return None;
+        }
+    };
+    let number: f64 = number.parse().ok()?;
+    Some((number * (multiplier as f64)) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_mixed_queries() {
+        let parsed = parse_query(r#"ext:rs size:>1kb "error handling""#).unwrap();
+        assert_eq!(parsed.filter.extensions, vec!["rs"]);
+        assert_eq!(parsed.filter.min_size, Some(1025));
+        assert_eq!(parsed.filter.max_size, None);
+        assert_eq!(parsed.text, "error handling");
+
+        let parsed = parse_query("tax   return ext:PDF,.md size:<=2mb 2023").unwrap();
+        assert_eq!(parsed.filter.extensions, vec!["pdf", "md"]);
+        assert_eq!(parsed.filter.max_size, Some(2 * 1024 * 1024));
+        assert_eq!(parsed.text, "tax return 2023");
+
+        let parsed = parse_query("size:>=10 size:<1.5kb").unwrap();
+        assert_eq!(parsed.filter.min_size, Some(10));
+        assert_eq!(parsed.filter.max_size, Some(1535));
+        assert_eq!(parsed.text, "");
+
+        let parsed = parse_query(r#"what is "ext:rs" note: http://example.com"#).unwrap();
+        assert!(parsed.filter.is_empty());
+        assert_eq!(parsed.text, "what is ext:rs note: http://example.com");
+
+        let parsed = parse_query("name:README.md setup name:").unwrap();
+        assert_eq!(parsed.filter.name.as_deref(), Some("README.md"));
+        assert_eq!(parsed.text, "setup name:");
+    }
+
+    #[test]
+    fn test_parse_invalid_queries() {
+        assert!(matches!(parse_query("size:huge"), Err(FileEmbeddingError::InvalidQuery(_))));
+        assert!(matches!(parse_query("size:>1tb"), Err(FileEmbeddingError::InvalidQuery(_))));
+        assert!(matches!(parse_query(r#""unterminated"#), Err(FileEmbeddingError::InvalidQuery(_))));
+    }
+}
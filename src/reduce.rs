@@ -0,0 +1,259 @@
+//! PCA projection of embeddings into fewer dimensions.
+//!
+//! Reducing 384-dimensional vectors to 128 cuts storage by two thirds (and composes with
+//! [`crate::quantize`]), at the cost of the variance in the dropped directions. Searches
+//! project the query the same way, so cosine scores are computed in the reduced space.
+//! Vectors are centered on the sample mean before projecting, so dot products and
+//! euclidean distances change meaning too; cosine is the metric to use with a reduction.
+
+use serde::{ Deserialize, Serialize };
+
+/// Power-iteration steps per principal component.
+const ITERATIONS: usize = 100;
+
+/// A learned linear map from the model's dimension to a smaller one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Projection {
+    /// Mean of the samples the projection was fitted on.
+    pub mean: Vec<f32>,
+    /// Orthonormal principal directions, largest variance first; one row per output
+    /// dimension.
+    pub components: Vec<Vec<f32>>,
+}
+
+impl Projection {
+    /// Fits the `target_dim` principal components of `samples`, or returns `None` with fewer
+    /// than two samples, mismatched dimensions, or a `target_dim` outside
+    /// `1..=sample dimension`.
+    pub fn fit(samples: &[Vec<f32>], target_dim: usize) -> Option<Self> {
+        let dim = samples.first()?.len();
+        if samples.len() < 2 || target_dim == 0 || target_dim > dim {
+            return None;
+        }
+        if samples.iter().any(|sample| sample.len() != dim) {
+            return None;
+        }
+
+        let n = samples.len() as f64;
+        let mut mean = vec![0.0f64; dim];
+        for sample in samples {
+            mean.iter_mut()
+                .zip(sample)
+                .for_each(|(m, x)| *m += f64::from(*x) / n);
+        }
+        let mut covariance = vec![vec![0.0f64; dim]; dim];
+        for sample in samples {
+            let centered: Vec<f64> = sample
+                .iter()
+                .zip(&mean)
+                .map(|(x, m)| f64::from(*x) - m)
+                .collect();
+            for (i, row) in covariance.iter_mut().enumerate() {
+                for (j, cell) in row.iter_mut().enumerate() {
+                    *cell += (centered[i] * centered[j]) / n;
+                }
+            }
+        }
+
+        let mut components = Vec::with_capacity(target_dim);
+        for k in 0..target_dim {
+            let component = top_eigenvector(&covariance, k, &components);
+            let eigenvalue = quadratic_form(&covariance, &component);
+            // Deflate, so the next iteration finds the next direction
+            for (i, row) in covariance.iter_mut().enumerate() {
+                for (j, cell) in row.iter_mut().enumerate() {
+                    *cell -= eigenvalue * component[i] * component[j];
+                }
+            }
+            components.push(component);
+        }
+
+        Some(Self {
+            mean: mean
+                .iter()
+                .map(|m| *m as f32)
+                .collect(),
+            components: components
+                .iter()
+                .map(|c| {
+                    c.iter()
+                        .map(|x| *x as f32)
+                        .collect()
+                })
+                .collect(),
+        })
+    }
+
+    pub fn input_dim(&self) -> usize {
+        self.mean.len()
+    }
+
+    pub fn output_dim(&self) -> usize {
+        self.components.len()
+    }
+
+    /// Maps `embedding` into the reduced space.
+    pub fn project(&self, embedding: &[f32]) -> Vec<f32> {
+        self.components
+            .iter()
+            .map(|component| {
+                component
+                    .iter()
+                    .zip(embedding.iter().zip(&self.mean))
+                    .map(|(c, (x, m))| c * (x - m))
+                    .sum()
+            })
+            .collect()
+    }
+}
+
+/// The dominant eigenvector of the (deflated) symmetric `matrix`, kept orthogonal to the
+/// components found so far.
+fn top_eigenvector(matrix: &[Vec<f64>], k: usize, found: &[Vec<f64>]) -> Vec<f64> {
+    let dim = matrix.len();
+    // Deterministic start with some weight on every axis, leaning towards axis `k`
+    let mut v: Vec<f64> = (0..dim)
+        .map(|i| if i == k { 1.0 } else { 1.0 / ((i + 2) as f64) })
+        .collect();
+    for _ in 0..ITERATIONS {
+        let mut next: Vec<f64> = matrix
+            .iter()
+            .map(|row| {
+                row.iter()
+                    .zip(&v)
+                    .map(|(a, b)| a * b)
+                    .sum()
+            })
+            .collect();
+        for previous in found {
+            let overlap: f64 = next
+                .iter()
+                .zip(previous)
+                .map(|(a, b)| a * b)
+                .sum();
+            next.iter_mut()
+                .zip(previous)
+                .for_each(|(x, p)| *x -= overlap * p);
+        }
+        let norm = next
+            .iter()
+            .map(|x| x * x)
+            .sum::<f64>()
+            .sqrt();
+        if norm < 1e-12 {
+            // No variance left: any direction orthogonal to the others will do
+            break;
+        }
+        v = next
+            .iter()
+            .map(|x| x / norm)
+            .collect();
+    }
+    orthonormalize(v, found)
+}
+
+fn orthonormalize(mut v: Vec<f64>, found: &[Vec<f64>]) -> Vec<f64> {
+    for previous in found {
+        let overlap: f64 = v
+            .iter()
+            .zip(previous)
+            .map(|(a, b)| a * b)
+            .sum();
+        v.iter_mut()
+            .zip(previous)
+            .for_each(|(x, p)| *x -= overlap * p);
+    }
+    let norm = v
+        .iter()
+        .map(|x| x * x)
+        .sum::<f64>()
+        .sqrt();
+    if norm > 0.0 {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+    v
+}
+
+fn quadratic_form(matrix: &[Vec<f64>], v: &[f64]) -> f64 {
+    matrix
+        .iter()
+        .zip(v)
+        .map(|(row, vi)| {
+            vi *
+                row
+                    .iter()
+                    .zip(v)
+                    .map(|(a, b)| a * b)
+                    .sum::<f64>()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::similarity::cosine_similarity;
+
+    #[test]
+    fn test_fit_finds_principal_axis() {
+        // Spread along (1, 1, 0) with a little noise on the other axes
+        let samples: Vec<Vec<f32>> = (0..20)
+            .map(|i| {
+                let t = (i as f32) - 10.0;
+                vec![t + 0.01 * ((i % 3) as f32), t, 0.05 * ((i % 2) as f32)]
+            })
+            .collect();
+        let projection = Projection::fit(&samples, 1).unwrap();
+        assert_eq!((projection.input_dim(), projection.output_dim()), (3, 1));
+
+        let axis = &projection.components[0];
+        let expected = [std::f32::consts::FRAC_1_SQRT_2, std::f32::consts::FRAC_1_SQRT_2, 0.0];
+        assert!(cosine_similarity(axis, &expected).abs() > 0.999, "Axis: {:?}", axis);
+
+        assert!(Projection::fit(&samples, 4).is_none());
+        assert!(Projection::fit(&samples[..1], 1).is_none());
+    }
+
+    #[test]
+    fn test_reduction_preserves_top_k() {
+        // Three topics in 12 dimensions, each a cluster around its own direction
+        let topic = |t: usize, i: usize| -> Vec<f32> {
+            (0..12)
+                .map(|d| {
+                    let signal = if d / 4 == t { 1.0 } else { 0.0 };
+                    let noise = ((((i * 31 + d * 17) % 13) as f32) - 6.0) / 60.0;
+                    signal + noise
+                })
+                .collect()
+        };
+        let corpus: Vec<Vec<f32>> = (0..30).map(|i| topic(i % 3, i)).collect();
+        let projection = Projection::fit(&corpus, 3).unwrap();
+        let reduced: Vec<Vec<f32>> = corpus
+            .iter()
+            .map(|v| projection.project(v))
+            .collect();
+
+        for t in 0..3 {
+            let query = topic(t, 99);
+            let rank = |vectors: &[Vec<f32>], query: &[f32]| -> Vec<usize> {
+                let mut scored: Vec<(usize, f32)> = vectors
+                    .iter()
+                    .enumerate()
+                    .map(|(i, v)| (i, cosine_similarity(v, query)))
+                    .collect();
+                scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+                scored
+                    .iter()
+                    .take(10)
+                    .map(|(i, _)| *i)
+                    .collect()
+            };
+            let mut full = rank(&corpus, &query);
+            let mut small = rank(&reduced, &projection.project(&query));
+            full.sort();
+            small.sort();
+            assert_eq!(small, full, "Top 10 for topic {} changed", t);
+            assert!(small.iter().all(|i| i % 3 == t));
+        }
+    }
+}
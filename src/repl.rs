@@ -0,0 +1,219 @@
+//! `file-embeddings repl`: an interactive prompt over an existing index.
+//!
+//! The model is loaded once and stays resident, so each query only pays for embedding the
+//! query itself.
+
+use anyhow::Result;
+use file_embeddings::{
+    FallbackResults,
+    FileEmbeddingSystem,
+    Highlight,
+    SearchFilter,
+    SearchOptions,
+    SearchResult,
+};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+use std::process::Command as Process;
+
+use crate::render_results;
+
+const DEFAULT_LIMIT: usize = 5;
+
+const HELP: &str = "\
+Type a query to search, or one of:
+  :limit N       show up to N results
+  :ext rs,md     only search these extensions; `:ext` alone searches everything
+  :open N        open result N of the last search in $EDITOR
+  :help          show this message
+  :quit          leave (also Ctrl-D)";
+
+/// One line typed at the prompt.
+#[derive(Debug, Clone, PartialEq)]
+enum Command {
+    Search(String),
+    Limit(usize),
+    Extensions(Vec<String>),
+    Open(usize),
+    Help,
+    Quit,
+}
+
+/// Parses a line, returning `None` for a blank one and `Err` with a message for a bad
+/// command.
+fn parse(line: &str) -> Result<Option<Command>, String> {
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(None);
+    }
+    let Some(command) = line.strip_prefix(':') else {
+        return Ok(Some(Command::Search(line.to_string())));
+    };
+
+    let (name, argument) = command.split_once(char::is_whitespace).unwrap_or((command, ""));
+    let argument = argument.trim();
+    let number = |what: &str| {
+        argument
+            .parse::<usize>()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| format!(":{} takes a positive number, got '{}'", what, argument))
+    };
+    let parsed = match name {
+        "limit" => Command::Limit(number("limit")?),
+        "ext" =>
+            Command::Extensions(
+                argument
+                    .split(',')
+                    .map(|ext| ext.trim().trim_start_matches('.').to_string())
+                    .filter(|ext| !ext.is_empty())
+                    .collect()
+            ),
+        "open" => Command::Open(number("open")?),
+        "help" | "h" | "?" => Command::Help,
+        "quit" | "q" | "exit" => Command::Quit,
+        _ => {
+            return Err(format!("Unknown command :{}; type :help for the list", name));
+        }
+    };
+    Ok(Some(parsed))
+}
+
+/// What the prompt remembers between lines.
+struct Session {
+    limit: usize,
+    extensions: Vec<String>,
+    last_results: Vec<SearchResult>,
+}
+
+/// Runs the prompt until `:quit` or end of input.
+pub async fn run(system: &FileEmbeddingSystem, show_weak: bool, highlight: &Highlight) -> Result<()> {
+    let mut editor = DefaultEditor::new()?;
+    let mut session = Session {
+        limit: DEFAULT_LIMIT,
+        extensions: Vec::new(),
+        last_results: Vec::new(),
+    };
+    println!("{}", HELP);
+
+    loop {
+        let line = match editor.readline("search> ") {
+            Ok(line) => line,
+            // Ctrl-C abandons the current line, like a shell
+            Err(ReadlineError::Interrupted) => {
+                continue;
+            }
+            Err(ReadlineError::Eof) => {
+                break;
+            }
+            Err(e) => {
+                return Err(e.into());
+            }
+        };
+        editor.add_history_entry(line.as_str())?;
+
+        let command = match parse(&line) {
+            Ok(Some(command)) => command,
+            Ok(None) => {
+                continue;
+            }
+            Err(message) => {
+                println!("{}", message);
+                continue;
+            }
+        };
+        match command {
+            Command::Search(query) => {
+                let options = SearchOptions {
+                    filter: SearchFilter::new().extensions(session.extensions.clone()).build()?,
+                    ..SearchOptions::new(session.limit)
+                };
+                match system.search(&query, &options).await {
+                    Ok(results) => {
+                        let search = FallbackResults {
+                            results,
+                            suggestions: Vec::new(),
+                            used_fallback: false,
+                        };
+                        print!("{}", render_results(&search, &query, show_weak, highlight));
+                        session.last_results = search.results;
+                    }
+                    Err(e) => println!("Search failed: {}", e),
+                }
+            }
+            Command::Limit(limit) => {
+                session.limit = limit;
+                println!("Showing up to {} results", limit);
+            }
+            Command::Extensions(extensions) => {
+                if extensions.is_empty() {
+                    println!("Searching all extensions");
+                } else {
+                    println!("Only searching: {}", extensions.join(", "));
+                }
+                session.extensions = extensions;
+            }
+            Command::Open(n) => open_result(&session.last_results, n),
+            Command::Help => println!("{}", HELP),
+            Command::Quit => {
+                break;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Opens the `n`th (1-based) of `results` in `$EDITOR`, falling back to `vi`.
+fn open_result(results: &[SearchResult], n: usize) {
+    let Some(result) = results.get(n - 1) else {
+        println!("No result {}; the last search returned {}", n, results.len());
+        return;
+    };
+    let editor = std::env::var("EDITOR").unwrap_or_default();
+    let (program, args) = editor_command(&editor);
+    match Process::new(program).args(args).arg(&result.file.path).status() {
+        Ok(status) if !status.success() => println!("{} exited with {}", editor, status),
+        Ok(_) => {}
+        Err(e) => println!("Could not run {}: {}", editor, e),
+    }
+}
+
+/// Splits `$EDITOR` into the program and its arguments, e.g. `code --wait`, falling back to
+/// `vi` when it is empty.
+fn editor_command(editor: &str) -> (&str, Vec<&str>) {
+    let mut words = editor.split_whitespace();
+    match words.next() {
+        Some(program) => (program, words.collect()),
+        None => ("vi", Vec::new()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_editor_command() {
+        assert_eq!(editor_command("code --wait"), ("code", vec!["--wait"]));
+        assert_eq!(editor_command("  nano "), ("nano", vec![]));
+        assert_eq!(editor_command(""), ("vi", vec![]));
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        assert_eq!(parse("   "), Ok(None));
+        assert_eq!(parse(" rust ownership "), Ok(Some(Command::Search("rust ownership".to_string()))));
+        assert_eq!(parse(":limit 10"), Ok(Some(Command::Limit(10))));
+        assert_eq!(
+            parse(":ext rs, .md"),
+            Ok(Some(Command::Extensions(vec!["rs".to_string(), "md".to_string()])))
+        );
+        assert_eq!(parse(":ext"), Ok(Some(Command::Extensions(Vec::new()))));
+        assert_eq!(parse(":open 2"), Ok(Some(Command::Open(2))));
+        assert_eq!(parse(":q"), Ok(Some(Command::Quit)));
+
+        assert!(parse(":limit 0").is_err());
+        assert!(parse(":open first").is_err());
+        assert!(parse(":frobnicate").is_err());
+    }
+}
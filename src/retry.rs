@@ -0,0 +1,152 @@
+use std::future::Future;
+use std::time::Duration;
+
+use surrealdb::error::{ Api, Db };
+
+use crate::storage;
+
+/// Retry policy for database operations that fail with a transient error.
+///
+/// Only errors classified by [`is_transient`] are retried; schema, constraint and query
+/// errors are returned immediately. Note that a retried write may already have been applied
+/// if the connection dropped after the server committed it, in which case the retry surfaces
+/// as a unique-index error.
+#[derive(Debug, Clone)]
+pub struct RetryConfig {
+    /// Total attempts including the first one; `1` disables retrying.
+    pub max_attempts: u32,
+    /// Delay before the first retry.
+    pub initial_backoff: Duration,
+    /// Upper bound on the delay between attempts.
+    pub max_backoff: Duration,
+    /// Factor the delay grows by after each failed attempt.
+    pub multiplier: f64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(2),
+            multiplier: 2.0,
+        }
+    }
+}
+
+impl RetryConfig {
+    /// A policy that never retries.
+    pub fn none() -> Self {
+        Self {
+            max_attempts: 1,
+            ..Default::default()
+        }
+    }
+
+    /// Delay to wait after the given (1-based) failed attempt.
+    fn backoff(&self, attempt: u32) -> Duration {
+        let factor = self.multiplier.powi(attempt.saturating_sub(1) as i32);
+        self.initial_backoff.mul_f64(factor).min(self.max_backoff)
+    }
+}
+
+/// Whether an error is worth retrying: dropped connections, timeouts and transaction conflicts.
+///
+/// SurrealDB 1.5 reports RocksDB conflicts as `Db::Tx`, the same variant it uses for I/O
+/// and corruption errors; those are told apart by their message and never retried.
+pub fn is_transient(error: &surrealdb::Error) -> bool {
+    match error {
+        surrealdb::Error::Db(Db::Tx(_)) => !storage::is_corruption(error),
+        surrealdb::Error::Db(error) => matches!(error, Db::TxFailure | Db::QueryTimedout),
+        surrealdb::Error::Api(error) =>
+            matches!(error, Api::Ws(_) | Api::Http(_) | Api::ConnectionUninitialised),
+    }
+}
+
+/// Runs `operation` until it succeeds, fails with a non-transient error, or attempts run out.
+pub async fn retry<T, F, Fut>(config: &RetryConfig, mut operation: F) -> Result<T, surrealdb::Error>
+    where F: FnMut() -> Fut, Fut: Future<Output = Result<T, surrealdb::Error>>
+{
+    let mut attempt = 1;
+    loop {
+        match operation().await {
+            Err(e) if attempt < config.max_attempts && is_transient(&e) => {
+                let delay = config.backoff(attempt);
+                log::warn!(
+                    "Transient database error (attempt {}/{}), retrying in {:?}: {}",
+                    attempt,
+                    config.max_attempts,
+                    delay,
+                    e
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => {
+                return result;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{ AtomicU32, Ordering };
+
+    fn fast_config() -> RetryConfig {
+        RetryConfig {
+            max_attempts: 5,
+            initial_backoff: Duration::from_millis(1),
+            max_backoff: Duration::from_millis(5),
+            multiplier: 2.0,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retry_flaky_operation() {
+        let calls = AtomicU32::new(0);
+
+        // A mock database call that times out twice and then succeeds
+        let result = retry(&fast_config(), || async {
+            if calls.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(surrealdb::Error::Db(Db::QueryTimedout))
+            } else {
+                Ok("stored")
+            }
+        }).await;
+
+        assert_eq!(result.unwrap(), "stored");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_retry_skips_permanent_errors() {
+        let calls = AtomicU32::new(0);
+
+        let result: Result<(), _> = retry(&fast_config(), || async {
+            calls.fetch_add(1, Ordering::SeqCst);
+            Err(surrealdb::Error::Api(Api::Query(String::from("Parse error"))))
+        }).await;
+
+        assert!(result.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1, "Query errors must not be retried");
+    }
+
+    #[test]
+    fn test_corruption_is_not_transient() {
+        let tx = |message: &str| surrealdb::Error::Db(Db::Tx(message.to_string()));
+        assert!(is_transient(&tx("Resource busy: ")));
+        assert!(is_transient(&tx("IO error: While lock file: db/LOCK: Resource busy")));
+        assert!(!is_transient(&tx("Corruption: block checksum mismatch")));
+        assert!(!is_transient(&tx("IO error: No such file or directory: 000012.sst")));
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let config = fast_config();
+        assert_eq!(config.backoff(1), Duration::from_millis(1));
+        assert_eq!(config.backoff(2), Duration::from_millis(2));
+        assert_eq!(config.backoff(10), Duration::from_millis(5));
+    }
+}
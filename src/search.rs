@@ -0,0 +1,374 @@
+use std::cmp::{ Ordering, Reverse };
+use std::collections::BinaryHeap;
+
+use crate::filter::SearchFilter;
+use crate::highlight::Highlight;
+use crate::models::{ FileRecord, SearchResult };
+use crate::similarity::{ cosine_similarity, dot_product, euclidean_distance };
+
+/// How a query embedding is compared against stored embeddings.
+///
+/// Scores are always "higher is better" so results sort the same way for every metric.
+/// Plain searches compute them in SurrealDB (see [`Metric::surreal_expression`]); batched,
+/// fallback, ensemble and quantized searches compute them in Rust over the stored vectors.
+/// Both are full scans: SurrealDB 1.5 has no vector index on the `files` table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Metric {
+    /// Cosine similarity in `[-1, 1]`, independent of vector magnitude.
+    #[default]
+    Cosine,
+    /// Raw dot product; equal to cosine for the normalized vectors fastembed produces.
+    DotProduct,
+    /// Euclidean distance mapped to `1 / (1 + distance)`, so identical vectors score 1.
+    Euclidean,
+}
+
+impl Metric {
+    pub fn score(&self, a: &[f32], b: &[f32]) -> f32 {
+        match self {
+            Metric::Cosine => cosine_similarity(a, b),
+            Metric::DotProduct => dot_product(a, b),
+            Metric::Euclidean => 1.0 / (1.0 + euclidean_distance(a, b)),
+        }
+    }
+
+    /// The same score as a SurrealQL expression comparing `content_embedding` against the
+    /// `$query_embedding` parameter.
+    pub(crate) fn surreal_expression(&self) -> &'static str {
+        match self {
+            Metric::Cosine => "vector::similarity::cosine(content_embedding, $query_embedding)",
+            Metric::DotProduct => "vector::dot(content_embedding, $query_embedding)",
+            Metric::Euclidean =>
+                "1 / (1 + vector::distance::euclidean(content_embedding, $query_embedding))",
+        }
+    }
+}
+
+/// How results with equal scores are ordered, so repeated searches (and pages of them)
+/// always agree.
+///
+/// Identical scores are common with duplicate content. Whichever field is chosen, the path
+/// decides any remaining tie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TieBreaker {
+    #[default]
+    Path,
+    Name,
+}
+
+impl TieBreaker {
+    /// Sort key for `file`, smallest first.
+    pub(crate) fn key<'a>(&self, file: &'a FileRecord) -> (&'a str, &'a str) {
+        match self {
+            TieBreaker::Path => (&file.path, ""),
+            TieBreaker::Name => (&file.name, &file.path),
+        }
+    }
+
+    /// Orders results best first: by descending score, then by this tie-breaker.
+    pub fn compare(&self, a: &SearchResult, b: &SearchResult) -> Ordering {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| self.key(&a.file).cmp(&self.key(&b.file)))
+    }
+
+    /// The same ordering as a SurrealQL `ORDER BY` tail after `score DESC`, for rows that
+    /// also select `path` and `name`; SurrealDB only orders by selected fields.
+    pub(crate) fn surreal_order(&self) -> &'static str {
+        match self {
+            TieBreaker::Path => "path ASC",
+            TieBreaker::Name => "name ASC, path ASC",
+        }
+    }
+}
+
+/// Per-query settings for [`FileEmbeddingSystem::search`](crate::FileEmbeddingSystem::search).
+#[derive(Debug, Clone)]
+pub struct SearchOptions {
+    /// Maximum number of results to return.
+    pub limit: usize,
+    /// Metric to score with, or `None` to use the configured default.
+    pub metric: Option<Metric>,
+    /// Restricts which files are scored at all.
+    pub filter: SearchFilter,
+    /// Drop results scoring below this value.
+    pub min_score: Option<f32>,
+    /// Cut the results at the largest drop in score, applied after `min_score` and `limit`.
+    pub auto_threshold: Option<AutoThreshold>,
+    /// Keep `content_embedding` and the other stored vectors in each result's record.
+    ///
+    /// Off by default, since a 384-float vector per result is rarely wanted by callers.
+    pub include_embeddings: bool,
+    /// Wrap query terms in each result's `snippet` with these markers.
+    pub highlight: Option<Highlight>,
+    /// With a `BestMatch` preview, also include this many windows before and after the one
+    /// that matched best, in document order, so the preview doesn't stop mid-thought.
+    pub context_chunks: usize,
+    /// Round [`SearchHit::score`](crate::SearchHit) to this many decimal places, for clean
+    /// output and stable snapshots. Ranking and `SearchResult` scores keep full precision.
+    pub score_decimals: Option<u32>,
+}
+
+impl SearchOptions {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            metric: None,
+            filter: SearchFilter::default(),
+            min_score: None,
+            auto_threshold: None,
+            include_embeddings: false,
+            highlight: None,
+            context_chunks: 0,
+            score_decimals: None,
+        }
+    }
+}
+
+/// A per-query cutoff at the largest drop between consecutive scores.
+///
+/// A good absolute `min_score` depends on the query: broad queries score everything lower.
+/// The gap between the clearly relevant results and the rest is more stable, so this keeps
+/// the results above it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AutoThreshold {
+    /// Only cut when the largest drop is at least this big, so a smooth distribution
+    /// without a clear cluster is kept whole.
+    pub min_gap: f32,
+    /// Never cut below this many results.
+    pub min_results: usize,
+}
+
+impl Default for AutoThreshold {
+    fn default() -> Self {
+        Self {
+            min_gap: 0.05,
+            min_results: 1,
+        }
+    }
+}
+
+impl AutoThreshold {
+    /// How many of `scores`, sorted best first, come before the largest gap.
+    pub fn cutoff(&self, scores: &[f32]) -> usize {
+        let first = self.min_results.max(1);
+        let widest = (first..scores.len())
+            .map(|i| (i, scores[i - 1] - scores[i]))
+            .reduce(|widest, gap| if gap.1 > widest.1 { gap } else { widest });
+        match widest {
+            Some((i, gap)) if gap >= self.min_gap => i,
+            _ => scores.len(),
+        }
+    }
+}
+
+/// Rounds `score` to `decimals` decimal places, e.g. `0.48213562` to `0.4821` for 4.
+pub fn round_score(score: f32, decimals: u32) -> f32 {
+    let factor = 10f64.powi(decimals as i32);
+    ((f64::from(score) * factor).round() / factor) as f32
+}
+
+/// Group name used for files without an extension.
+pub const NO_EXTENSION_GROUP: &str = "(none)";
+
+/// Groups ranked results by file extension.
+///
+/// Groups are ordered by their best result and each group keeps the input ranking, so a UI
+/// can show "3 rs files, 2 md files" with the strongest group first.
+pub fn group_by_extension(results: Vec<SearchResult>) -> Vec<(String, Vec<SearchResult>)> {
+    let mut groups: Vec<(String, Vec<SearchResult>)> = Vec::new();
+    for result in results {
+        let extension = result.file.extension
+            .clone()
+            .unwrap_or_else(|| NO_EXTENSION_GROUP.to_string());
+        match groups.iter_mut().find(|(name, _)| *name == extension) {
+            Some((_, group)) => group.push(result),
+            None => groups.push((extension, vec![result])),
+        }
+    }
+    groups
+}
+
+/// A scored item, ordered so that a higher score is greater and, among equal scores, the
+/// smaller tie-breaking key and then the earlier index are greater.
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Scored<K>(usize, f32, K);
+
+impl<K: Ord> Eq for Scored<K> {}
+
+impl<K: Ord> PartialOrd for Scored<K> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<K: Ord> Ord for Scored<K> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.1
+            .partial_cmp(&other.1)
+            .unwrap_or(Ordering::Equal)
+            .then_with(|| other.2.cmp(&self.2))
+            .then_with(|| other.0.cmp(&self.0))
+    }
+}
+
+/// The `k` highest-scoring `(index, score)` pairs, best first, with ties going to the
+/// smaller key.
+///
+/// Equivalent to a stable sort by descending score and ascending key followed by
+/// `truncate(k)`, but keeps only `k` items in a heap, so ranking a large corpus is
+/// `O(n log k)` with no allocation proportional to `n`.
+pub(crate) fn top_k<K: Ord>(
+    scored: impl IntoIterator<Item = (usize, f32, K)>,
+    k: usize
+) -> Vec<(usize, f32)> {
+    if k == 0 {
+        return Vec::new();
+    }
+    let mut heap: BinaryHeap<Reverse<Scored<K>>> = BinaryHeap::with_capacity(k + 1);
+    for (index, score, key) in scored {
+        let candidate = Scored(index, score, key);
+        if heap.len() < k {
+            heap.push(Reverse(candidate));
+        } else if heap.peek().is_some_and(|Reverse(worst)| candidate > *worst) {
+            heap.pop();
+            heap.push(Reverse(candidate));
+        }
+    }
+    heap
+        .into_sorted_vec()
+        .into_iter()
+        .map(|Reverse(Scored(index, score, _))| (index, score))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::FileRecord;
+    use proptest::prelude::*;
+
+    fn sort_and_truncate(scores: &[f32], k: usize) -> Vec<(usize, f32)> {
+        let mut scored: Vec<(usize, f32)> = scores.iter().copied().enumerate().collect();
+        scored.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(Ordering::Equal));
+        scored.truncate(k);
+        scored
+    }
+
+    proptest! {
+        #[test]
+        fn top_k_matches_sorted_truncation(
+            // Few distinct values, so ties are common
+            scores in prop::collection::vec((-4i8..4).prop_map(|x| f32::from(x) / 4.0), 0..200),
+            k in 0usize..20
+        ) {
+            let expected = sort_and_truncate(&scores, k);
+            let scored = scores.iter().copied().enumerate().map(|(i, score)| (i, score, ()));
+            prop_assert_eq!(top_k(scored, k), expected);
+        }
+    }
+
+    #[test]
+    fn test_top_k_large_input() {
+        let mut state: u64 = 42;
+        let scores: Vec<f32> = (0..50_000)
+            .map(|_| {
+                state = state.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+                ((state >> 33) as f32) / (u32::MAX as f32)
+            })
+            .collect();
+
+        let heap = top_k(scores.iter().enumerate().map(|(i, score)| (i, *score, ())), 10);
+        assert_eq!(heap, sort_and_truncate(&scores, 10));
+    }
+
+    #[test]
+    fn test_auto_threshold_cuts_at_gap() {
+        let scores = [0.91, 0.89, 0.86, 0.52, 0.5, 0.47];
+        assert_eq!(AutoThreshold::default().cutoff(&scores), 3);
+
+        // The widest gap is too small to be a real boundary
+        let smooth = [0.8, 0.76, 0.72, 0.68];
+        assert_eq!(AutoThreshold::default().cutoff(&smooth), 4);
+
+        let min_results = AutoThreshold {
+            min_results: 4,
+            ..Default::default()
+        };
+        assert_eq!(min_results.cutoff(&scores), 6);
+        assert_eq!(AutoThreshold::default().cutoff(&[0.4]), 1);
+        assert_eq!(AutoThreshold::default().cutoff(&[]), 0);
+    }
+
+    #[test]
+    fn test_round_score() {
+        assert_eq!(round_score(0.48213562, 4), 0.4821);
+        assert_eq!(round_score(0.48216, 4), 0.4822);
+        assert_eq!(round_score(-0.125, 2), -0.13);
+        assert_eq!(round_score(0.7, 0), 1.0);
+    }
+
+    fn result(path: &str, extension: Option<&str>, score: f32) -> SearchResult {
+        SearchResult {
+            file: FileRecord {
+                path: path.to_string(),
+                extension: extension.map(str::to_string),
+                ..Default::default()
+            },
+            score,
+            low_confidence: false,
+            snippet: None,
+        }
+    }
+
+    #[test]
+    fn test_group_by_extension() {
+        let results = vec![
+            result("a.rs", Some("rs"), 0.9),
+            result("b.md", Some("md"), 0.8),
+            result("c.rs", Some("rs"), 0.7),
+            result("Makefile", None, 0.6),
+            result("d.md", Some("md"), 0.5),
+        ];
+
+        let groups = group_by_extension(results);
+        let summary: Vec<(&str, Vec<&str>)> = groups
+            .iter()
+            .map(|(ext, group)| {
+                (ext.as_str(), group.iter().map(|r| r.file.path.as_str()).collect())
+            })
+            .collect();
+        assert_eq!(summary, vec![
+            ("rs", vec!["a.rs", "c.rs"]),
+            ("md", vec!["b.md", "d.md"]),
+            (NO_EXTENSION_GROUP, vec!["Makefile"]),
+        ]);
+    }
+
+    #[test]
+    fn test_metric_orderings() {
+        let query = [1.0, 0.0];
+        let corpus: [(&str, [f32; 2]); 3] = [
+            ("far", [0.0, 1.0]),
+            ("near", [0.9, 0.1]),
+            ("same", [1.0, 0.0]),
+        ];
+
+        for metric in [Metric::Cosine, Metric::DotProduct, Metric::Euclidean] {
+            let mut ranked: Vec<(&str, f32)> = corpus
+                .iter()
+                .map(|(name, v)| (*name, metric.score(&query, v)))
+                .collect();
+            ranked.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+            let names: Vec<&str> = ranked
+                .iter()
+                .map(|(name, _)| *name)
+                .collect();
+            assert_eq!(names, vec!["same", "near", "far"], "Unexpected ordering for {:?}", metric);
+        }
+
+        assert!((Metric::Euclidean.score(&query, &query) - 1.0).abs() < 1e-6);
+    }
+}
@@ -0,0 +1,155 @@
+//! Vector math used to score embeddings.
+
+/// Cosine similarity in `[-1, 1]`.
+///
+/// Returns 0.0 when the lengths differ, either vector is all zeros, or a component is
+/// NaN or infinite.
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    let dot_product: f32 = a
+        .iter()
+        .zip(b.iter())
+        .map(|(x, y)| x * y)
+        .sum();
+    let magnitude_a: f32 = a
+        .iter()
+        .map(|x| x * x)
+        .sum::<f32>()
+        .sqrt();
+    let magnitude_b: f32 = b
+        .iter()
+        .map(|x| x * x)
+        .sum::<f32>()
+        .sqrt();
+
+    if magnitude_a == 0.0 || magnitude_b == 0.0 {
+        return 0.0;
+    }
+
+    finite_or_zero(dot_product / (magnitude_a * magnitude_b))
+}
+
+/// Dot product, or 0.0 when the lengths differ or the result isn't finite.
+pub fn dot_product(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return 0.0;
+    }
+
+    finite_or_zero(
+        a
+            .iter()
+            .zip(b.iter())
+            .map(|(x, y)| x * y)
+            .sum()
+    )
+}
+
+/// Maps a NaN or infinite score (from a NaN/inf vector component) to 0.0 so sorting stays
+/// deterministic.
+pub(crate) fn finite_or_zero(score: f32) -> f32 {
+    if score.is_finite() {
+        score
+    } else {
+        log::warn!("Non-finite similarity score {}, treating it as 0.0", score);
+        0.0
+    }
+}
+
+/// Euclidean distance, or infinity when the lengths differ or a component is NaN.
+pub fn euclidean_distance(a: &[f32], b: &[f32]) -> f32 {
+    if a.len() != b.len() {
+        return f32::INFINITY;
+    }
+
+    let distance = a
+        .iter()
+        .zip(b.iter())
+        .map(|(x, y)| (x - y) * (x - y))
+        .sum::<f32>()
+        .sqrt();
+    // NaN distances would otherwise become NaN scores; infinity scores 0.0
+    if distance.is_nan() {
+        log::warn!("NaN distance, treating it as infinitely far");
+        return f32::INFINITY;
+    }
+    distance
+}
+
+/// Scales `v` to unit length, leaving all-zero vectors unchanged.
+///
+/// Cosine similarity of normalized vectors equals their dot product.
+pub fn normalize(v: &[f32]) -> Vec<f32> {
+    let magnitude = v
+        .iter()
+        .map(|x| x * x)
+        .sum::<f32>()
+        .sqrt();
+    if magnitude == 0.0 {
+        return v.to_vec();
+    }
+    v.iter()
+        .map(|x| x / magnitude)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const EPSILON: f32 = 1e-6;
+
+    #[test]
+    fn test_cosine_similarity() {
+        assert!((cosine_similarity(&[1.0, 0.0], &[0.0, 1.0])).abs() < EPSILON);
+        assert!((cosine_similarity(&[0.3, 0.4], &[0.3, 0.4]) - 1.0).abs() < EPSILON);
+        assert!((cosine_similarity(&[0.3, 0.4], &[-0.3, -0.4]) + 1.0).abs() < EPSILON);
+        // Magnitude doesn't matter
+        assert!((cosine_similarity(&[1.0, 1.0], &[5.0, 5.0]) - 1.0).abs() < EPSILON);
+
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[1.0, 0.0], &[1.0, 0.0, 0.0]), 0.0);
+        assert_eq!(cosine_similarity(&[], &[]), 0.0);
+    }
+
+    #[test]
+    fn test_dot_product_and_distance() {
+        assert_eq!(dot_product(&[1.0, 2.0, 3.0], &[4.0, 5.0, 6.0]), 32.0);
+        assert_eq!(dot_product(&[1.0], &[1.0, 2.0]), 0.0);
+
+        assert_eq!(euclidean_distance(&[0.0, 0.0], &[3.0, 4.0]), 5.0);
+        assert_eq!(euclidean_distance(&[1.0, 1.0], &[1.0, 1.0]), 0.0);
+        assert_eq!(euclidean_distance(&[1.0], &[1.0, 2.0]), f32::INFINITY);
+    }
+
+    #[test]
+    fn test_normalize() {
+        assert_eq!(normalize(&[3.0, 4.0]), vec![0.6, 0.8]);
+        assert_eq!(normalize(&[0.0, 0.0]), vec![0.0, 0.0]);
+
+        let (a, b) = (normalize(&[1.0, 2.0, 2.0]), normalize(&[2.0, 0.0, 1.0]));
+        assert!((dot_product(&a, &b) - cosine_similarity(&a, &b)).abs() < EPSILON);
+    }
+
+    fn finite_vectors() -> impl Strategy<Value = (Vec<f32>, Vec<f32>)> {
+        (1usize..64).prop_flat_map(|len| {
+            (prop::collection::vec(-1e3f32..1e3, len), prop::collection::vec(-1e3f32..1e3, len))
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_cosine_in_range((a, b) in finite_vectors()) {
+            let score = cosine_similarity(&a, &b);
+            prop_assert!((-1.0 - EPSILON..=1.0 + EPSILON).contains(&score), "score {}", score);
+        }
+
+        #[test]
+        fn prop_cosine_symmetric((a, b) in finite_vectors()) {
+            prop_assert_eq!(cosine_similarity(&a, &b), cosine_similarity(&b, &a));
+        }
+    }
+}
@@ -0,0 +1,50 @@
+//! Content sniffing for files whose extension doesn't say what they are.
+
+use std::fs::File;
+use std::io::{ self, Read };
+use std::path::Path;
+
+/// How much of a file is inspected when deciding whether it's text.
+const SNIFF_BYTES: u64 = 8192;
+
+/// MIME type stored for files that were indexed because they sniffed as text.
+pub const TEXT_MIME_TYPE: &str = "text/plain";
+
+/// Whether the first bytes of the file at `path` look like UTF-8 text.
+pub fn looks_like_text(path: &Path) -> io::Result<bool> {
+    let mut head = Vec::new();
+    File::open(path)?.take(SNIFF_BYTES).read_to_end(&mut head)?;
+    Ok(is_text(&head))
+}
+
+/// Whether `bytes` is non-empty UTF-8 without NUL bytes.
+///
+/// A multi-byte character cut off at the end of the buffer still counts as text, since
+/// the buffer is usually only the head of a file.
+pub fn is_text(bytes: &[u8]) -> bool {
+    if bytes.is_empty() || bytes.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_text() {
+        assert!(is_text(b"# README\n\nPlain text without an extension.\n"));
+        assert!(is_text("naïve café".as_bytes()));
+        // "é" cut in half by the sniff window
+        assert!(is_text(&"café".as_bytes()[..4]));
+
+        assert!(!is_text(b""));
+        assert!(!is_text(b"\x7fELF\x02\x01\x01\x00\x00"));
+        assert!(!is_text(&[0x89, b'P', b'N', b'G', 0x0d, 0x0a, 0x1a, 0x0a]));
+        assert!(!is_text(&[0xff, 0xfe, b'h', b'i']));
+    }
+}
@@ -0,0 +1,64 @@
+//! Stopword lists for the keyword side of search.
+//!
+//! Embeddings already discount words like "the" and "with", but filename matching,
+//! "did you mean" suggestions and snippet highlighting compare words literally. With
+//! [`FileEmbeddingConfig::language`](crate::FileEmbeddingConfig::language) set, query words
+//! on this list are dropped from those paths. Embedding input is never filtered.
+//!
+//! Only words of three or more characters are listed, since shorter ones are already
+//! ignored by [`fallback::words`](crate::fallback::words).
+
+const ENGLISH: &[&str] = &[
+    "about", "after", "all", "and", "any", "are", "but", "can", "could", "did", "does", "for",
+    "from", "had", "has", "have", "her", "his", "how", "into", "its", "not", "now", "off",
+    "our", "out", "she", "than", "that", "the", "their", "them", "then", "there", "these",
+    "they", "this", "those", "too", "was", "were", "what", "when", "where", "which", "who",
+    "why", "will", "with", "would", "you", "your",
+];
+
+const GERMAN: &[&str] = &[
+    "aber", "als", "auch", "auf", "aus", "bei", "bin", "bis", "das", "dass", "dem", "den",
+    "der", "des", "die", "ein", "eine", "einem", "einen", "einer", "für", "hat", "ich", "ist",
+    "mit", "nach", "nicht", "noch", "oder", "sich", "sie", "sind", "und", "von", "war", "wie",
+    "wir", "zum", "zur",
+];
+
+const FRENCH: &[&str] = &[
+    "aux", "avec", "ces", "dans", "des", "elle", "est", "ils", "les", "leur", "mais", "nous",
+    "par", "pas", "pour", "qui", "que", "sans", "ses", "son", "sont", "sur", "une", "vous",
+];
+
+const SPANISH: &[&str] = &[
+    "como", "con", "del", "ella", "era", "esta", "este", "hay", "las", "los", "más", "para",
+    "pero", "por", "que", "sin", "sobre", "son", "una", "uno",
+];
+
+/// The stopword list for an ISO 639-1 code or English language name, case-insensitively.
+pub fn for_language(language: &str) -> Option<&'static [&'static str]> {
+    match language.to_lowercase().as_str() {
+        "en" | "english" => Some(ENGLISH),
+        "de" | "german" => Some(GERMAN),
+        "fr" | "french" => Some(FRENCH),
+        "es" | "spanish" => Some(SPANISH),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_for_language() {
+        assert!(for_language("en").unwrap().contains(&"the"));
+        assert_eq!(for_language("English"), for_language("en"));
+        assert!(for_language("de").unwrap().contains(&"und"));
+        assert_eq!(for_language("klingon"), None);
+
+        for list in ["en", "de", "fr", "es"].iter().filter_map(|code| for_language(code)) {
+            assert!(
+                list.iter().all(|word| word.chars().count() >= 3 && *word == word.to_lowercase())
+            );
+        }
+    }
+}
@@ -0,0 +1,168 @@
+//! How the underlying SurrealDB/RocksDB database is opened.
+//!
+//! SurrealDB 1.5 doesn't expose RocksDB's options at runtime. Cache, write buffer and
+//! compaction settings are read from `SURREAL_ROCKSDB_*` environment variables when
+//! `surrealdb-core` is *compiled*, so for a multi-GB index set them in `.cargo/config.toml`:
+//!
+//! ```toml
+//! [env]
+//! SURREAL_ROCKSDB_WRITE_BUFFER_SIZE = "67108864"      # default 256 MiB
+//! SURREAL_ROCKSDB_MAX_WRITE_BUFFER_NUMBER = "8"       # default 32
+//! SURREAL_ROCKSDB_TARGET_FILE_SIZE_BASE = "134217728" # default 512 MiB
+//! SURREAL_ROCKSDB_THREAD_COUNT = "4"                  # default: number of CPUs
+//! SURREAL_ROCKSDB_ENABLE_BLOB_FILES = "true"
+//! SURREAL_ROCKSDB_MIN_BLOB_SIZE = "4096"
+//! ```
+//!
+//! and rebuild with `cargo clean -p surrealdb-core`. Compaction style is fixed to level
+//! compaction and there is no block cache size knob. [`StorageConfig`] covers what can be
+//! set per process.
+//!
+//! # Durability
+//!
+//! SurrealDB commits every write to RocksDB's write-ahead log but doesn't fsync it, and
+//! offers no flush or sync API. A committed write survives the process crashing, since the
+//! log is already in the OS page cache, but not a power loss or kernel panic.
+//! [`FileEmbeddingSystem::flush`](crate::FileEmbeddingSystem::flush) closes that gap by
+//! fsyncing the database files directly.
+//!
+//! # Compaction
+//!
+//! RocksDB removes deleted and overwritten keys when it compacts, which it schedules in the
+//! background as files accumulate; SurrealDB 1.5 offers no way to trigger it manually.
+//! [`FileEmbeddingSystem::compact`](crate::FileEmbeddingSystem::compact) rebuilds the
+//! table's indexes from the current records and syncs the files, and reports how the
+//! directory size changed. The space freed by deletes shows up once RocksDB's own
+//! compaction has run, so a single call can report little or nothing reclaimed.
+
+use std::fs;
+use std::io;
+use std::path::{ Path, PathBuf };
+use std::time::{ Duration, SystemTime, UNIX_EPOCH };
+
+use surrealdb::error::Db;
+use surrealdb::opt::Config;
+
+/// Per-process database options.
+#[derive(Debug, Clone, Default)]
+pub struct StorageConfig {
+    /// Open an existing index as-is, without resetting or (re)defining the schema, and
+    /// reject every write with [`FileEmbeddingError::ReadOnly`](crate::FileEmbeddingError).
+    ///
+    /// For search-only deployments of a precomputed index. RocksDB itself is still opened
+    /// read-write, as SurrealDB doesn't expose its read-only mode.
+    pub read_only: bool,
+    /// Open an existing index as-is, without resetting or (re)defining the schema, failing
+    /// with [`FileEmbeddingError::SchemaMissing`](crate::FileEmbeddingError) if it was never
+    /// initialized. Unlike `read_only`, writes are allowed.
+    pub open_existing: bool,
+    /// Abort queries that run longer than this.
+    pub query_timeout: Option<Duration>,
+    /// Abort transactions that run longer than this.
+    pub transaction_timeout: Option<Duration>,
+    /// When the database directory is corrupt, move it aside to `<path>.corrupt-<unix time>`
+    /// and start a fresh, empty index instead of failing with
+    /// [`FileEmbeddingError::DatabaseCorrupt`](crate::FileEmbeddingError).
+    ///
+    /// Ignored when `read_only` is set.
+    pub reset_if_corrupt: bool,
+}
+
+impl StorageConfig {
+    pub(crate) fn surreal_config(&self) -> Config {
+        Config::new()
+            .query_timeout(self.query_timeout)
+            .transaction_timeout(self.transaction_timeout)
+    }
+}
+
+/// Whether a connection error means the RocksDB files themselves are damaged or unreadable.
+///
+/// RocksDB reports these as `Corruption:` or `IO error:` messages, which SurrealDB wraps in
+/// [`Db::Tx`]. A lock held by another process is also an I/O error but leaves the data
+/// intact, so it isn't counted.
+pub(crate) fn is_corruption(error: &surrealdb::Error) -> bool {
+    let surrealdb::Error::Db(Db::Tx(message) | Db::Ds(message)) = error else {
+        return false;
+    };
+    message.contains("Corruption:") ||
+        (message.contains("IO error:") && !message.contains("lock file"))
+}
+
+/// Fsyncs every file in the database directory (and on Unix the directory itself), returning
+/// how many files were synced.
+pub(crate) fn sync_directory(db_path: &Path) -> io::Result<usize> {
+    let mut synced = 0;
+    for entry in fs::read_dir(db_path)? {
+        let entry = entry?;
+        if entry.file_type()?.is_file() {
+            fs::File::open(entry.path())?.sync_all()?;
+            synced += 1;
+        }
+    }
+    // Persists renames and newly created files; directories can't be opened on Windows
+    #[cfg(unix)]
+    fs::File::open(db_path)?.sync_all()?;
+
+    Ok(synced)
+}
+
+/// Total size of the files in the database directory.
+pub(crate) fn directory_size(db_path: &Path) -> io::Result<u64> {
+    let mut size = 0;
+    for entry in fs::read_dir(db_path)? {
+        let metadata = entry?.metadata()?;
+        if metadata.is_file() {
+            size += metadata.len();
+        }
+    }
+    Ok(size)
+}
+
+/// What [`FileEmbeddingSystem::compact`](crate::FileEmbeddingSystem::compact) did.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompactionReport {
+    /// Size of the database directory before compacting.
+    pub bytes_before: u64,
+    /// Size of the database directory afterwards.
+    pub bytes_after: u64,
+    /// Indexes dropped and rebuilt from the current records.
+    pub indexes_rebuilt: usize,
+}
+
+impl CompactionReport {
+    /// Bytes freed, or 0 if the directory grew (e.g. while rebuilt indexes await RocksDB's
+    /// own compaction).
+    pub fn reclaimed_bytes(&self) -> u64 {
+        self.bytes_before.saturating_sub(self.bytes_after)
+    }
+}
+
+/// Renames a corrupt database directory out of the way and returns where it went.
+pub(crate) fn move_aside(db_path: &Path) -> io::Result<PathBuf> {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or_default();
+    let mut backup = db_path.as_os_str().to_owned();
+    backup.push(format!(".corrupt-{}", timestamp));
+
+    let backup = PathBuf::from(backup);
+    fs::rename(db_path, &backup)?;
+    Ok(backup)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_corruption() {
+        let error = |message: &str| surrealdb::Error::Db(Db::Tx(message.to_string()));
+
+        assert!(is_corruption(&error("Corruption: CURRENT file does not end with newline")));
+        assert!(is_corruption(&error("IO error: No such file or directory: 000012.sst")));
+        assert!(!is_corruption(&error("IO error: While lock file: db/LOCK: Resource busy")));
+        assert!(!is_corruption(&surrealdb::Error::Db(Db::QueryTimedout)));
+    }
+}
@@ -0,0 +1,99 @@
+//! Flattening JSON, YAML and TOML into `key: value` lines before embedding.
+//!
+//! Embedded raw, a config file is mostly braces, quotes and indentation. Flattened, each
+//! leaf becomes one line like `server.ports[0]: 8080`, so both keys and values carry
+//! meaning for the model and the preview stays readable.
+
+use serde_json::Value;
+
+/// Extensions whose content is parsed and flattened.
+pub const STRUCTURED_EXTENSIONS: &[&str] = &["json", "yaml", "yml", "toml"];
+
+/// A parsed document's flattened text and its top-level keys.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Flattened {
+    pub text: String,
+    pub top_level_keys: Vec<String>,
+}
+
+/// Parses `text` as the format of `extension` and flattens it, or returns `None` if the
+/// extension isn't structured or the text doesn't parse (e.g. it was cut by
+/// `max_embed_chars`), in which case the raw text should be embedded instead.
+pub fn flatten(extension: &str, text: &str) -> Option<Flattened> {
+    let value: Value = match extension {
+        "json" => serde_json::from_str(text).ok()?,
+        "yaml" | "yml" => serde_yaml::from_str(text).ok()?,
+        "toml" => toml::from_str(text).ok()?,
+        _ => {
+            return None;
+        }
+    };
+
+    let top_level_keys = match &value {
+        Value::Object(map) => map.keys().cloned().collect(),
+        _ => Vec::new(),
+    };
+    let mut lines = Vec::new();
+    flatten_into(&value, String::new(), &mut lines);
+    Some(Flattened {
+        text: lines.join("\n"),
+        top_level_keys,
+    })
+}
+
+fn flatten_into(value: &Value, key: String, lines: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (name, child) in map {
+                let child_key = if key.is_empty() { name.clone() } else { format!("{}.{}", key, name) };
+                flatten_into(child, child_key, lines);
+            }
+        }
+        Value::Array(items) => {
+            for (i, child) in items.iter().enumerate() {
+                flatten_into(child, format!("{}[{}]", key, i), lines);
+            }
+        }
+        Value::String(s) => lines.push(leaf(&key, s)),
+        other => lines.push(leaf(&key, &other.to_string())),
+    }
+}
+
+/// A `key: value` line, or the bare value for a document that is a single scalar.
+fn leaf(key: &str, value: &str) -> String {
+    if key.is_empty() { value.to_string() } else { format!("{}: {}", key, value) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flatten_formats() {
+        let json = r#"{"server": {"host": "db.internal", "ports": [8080, 8443]}, "debug": true}"#;
+        let yaml = "server:\n  host: db.internal\n  ports:\n    - 8080\n    - 8443\ndebug: true\n";
+        let toml = "debug = true\n[server]\nhost = \"db.internal\"\nports = [8080, 8443]\n";
+
+        for (extension, text) in [("json", json), ("yaml", yaml), ("toml", toml)] {
+            let flattened = flatten(extension, text).unwrap();
+            let mut lines: Vec<&str> = flattened.text.lines().collect();
+            lines.sort();
+            assert_eq!(lines, vec![
+                "debug: true",
+                "server.host: db.internal",
+                "server.ports[0]: 8080",
+                "server.ports[1]: 8443",
+            ], "Unexpected flattening of {}", extension);
+            let mut keys = flattened.top_level_keys;
+            keys.sort();
+            assert_eq!(keys, vec!["debug", "server"]);
+        }
+    }
+
+    #[test]
+    fn test_flatten_rejects_invalid() {
+        assert_eq!(flatten("json", r#"{"truncated": "#), None);
+        assert_eq!(flatten("txt", "plain text"), None);
+        assert_eq!(flatten("json", "[1, 2]").unwrap().top_level_keys, Vec::<String>::new());
+    }
+}
@@ -0,0 +1,189 @@
+//! Consistency checks over stored records, for use after a model change or a crash.
+//!
+//! [`FileEmbeddingSystem::verify_index`](crate::FileEmbeddingSystem::verify_index) only
+//! reads; [`FileEmbeddingSystem::repair_index`](crate::FileEmbeddingSystem::repair_index)
+//! re-embeds or drops what it flags.
+
+use std::collections::HashMap;
+
+use crate::models::FileRecord;
+
+/// A problem with one stored record.
+#[derive(Debug, Clone, PartialEq)]
+pub enum IndexIssue {
+    /// `content_embedding` doesn't have the model's dimension, e.g. it was stored by a
+    /// different model.
+    WrongDimension { expected: usize, actual: usize },
+    /// A stored vector contains NaN or infinity.
+    NonFiniteEmbedding,
+    /// The record is marked embedded but has no vector.
+    EmptyEmbedding,
+    /// The file (or mbox archive) the record came from no longer exists.
+    MissingFile,
+    /// The record has exactly the same embedding as `original`, so it almost certainly has
+    /// the same content.
+    DuplicateContent { original: String },
+}
+
+impl IndexIssue {
+    /// Whether re-embedding the file would fix the issue.
+    pub fn is_embedding_issue(&self) -> bool {
+        matches!(
+            self,
+            IndexIssue::WrongDimension { .. } |
+                IndexIssue::NonFiniteEmbedding |
+                IndexIssue::EmptyEmbedding
+        )
+    }
+}
+
+/// Issues found by `verify_index`, in the order records were scanned.
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    /// Number of records checked.
+    pub checked: usize,
+    /// Each flagged record's path with one of its issues; a record can appear more than once.
+    pub issues: Vec<(String, IndexIssue)>,
+}
+
+impl VerifyReport {
+    pub fn is_clean(&self) -> bool {
+        self.issues.is_empty()
+    }
+
+    /// The issues found for `path`.
+    pub fn issues_for(&self, path: &str) -> Vec<&IndexIssue> {
+        self.issues
+            .iter()
+            .filter(|(issue_path, _)| issue_path == path)
+            .map(|(_, issue)| issue)
+            .collect()
+    }
+}
+
+/// What `repair_index` does with flagged records.
+#[derive(Debug, Clone)]
+pub struct RepairOptions {
+    /// Re-embed records with a bad embedding from their file or stored content. When off,
+    /// or when there is nothing to re-embed from, they are dropped instead.
+    pub reembed: bool,
+    /// Drop records whose file no longer exists.
+    pub prune_missing: bool,
+    /// Drop duplicates, keeping the first record of each group.
+    pub drop_duplicates: bool,
+}
+
+impl Default for RepairOptions {
+    fn default() -> Self {
+        Self {
+            reembed: true,
+            prune_missing: true,
+            drop_duplicates: false,
+        }
+    }
+}
+
+/// Which records `repair_index` re-embedded and dropped.
+#[derive(Debug, Clone, Default)]
+pub struct RepairReport {
+    pub reembedded: Vec<String>,
+    pub dropped: Vec<String>,
+}
+
+/// Checks `record`'s stored vectors against the model's `dimension`, with `content_embedding`
+/// already dequantized.
+pub(crate) fn embedding_issues(record: &FileRecord, dimension: usize) -> Vec<IndexIssue> {
+    let mut issues = Vec::new();
+    if !record.embedded {
+        return issues;
+    }
+    if record.content_embedding.is_empty() {
+        issues.push(IndexIssue::EmptyEmbedding);
+    } else if record.content_embedding.len() != dimension {
+        issues.push(IndexIssue::WrongDimension {
+            expected: dimension,
+            actual: record.content_embedding.len(),
+        });
+    }
+    let non_finite = record.content_embedding
+        .iter()
+        .chain(record.embeddings.values().flatten())
+        .chain(record.chunk_embeddings.iter().flatten())
+        .chain(record.sentence_embeddings.iter().flatten())
+        .any(|x| !x.is_finite());
+    if non_finite {
+        issues.push(IndexIssue::NonFiniteEmbedding);
+    }
+    issues
+}
+
+/// Flags every record whose `content_embedding` is bit-for-bit identical to an earlier one.
+pub(crate) fn duplicate_issues(records: &[FileRecord]) -> Vec<(String, IndexIssue)> {
+    let mut first_seen: HashMap<Vec<u32>, &str> = HashMap::new();
+    let mut issues = Vec::new();
+    for record in records {
+        if record.content_embedding.is_empty() {
+            continue;
+        }
+        let key: Vec<u32> = record.content_embedding.iter().map(|x| x.to_bits()).collect();
+        match first_seen.get(&key) {
+            Some(original) => {
+                issues.push((
+                    record.path.clone(),
+                    IndexIssue::DuplicateContent { original: original.to_string() },
+                ));
+            }
+            None => {
+                first_seen.insert(key, &record.path);
+            }
+        }
+    }
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(path: &str, embedding: Vec<f32>) -> FileRecord {
+        FileRecord {
+            path: path.to_string(),
+            content_embedding: embedding,
+            embedded: true,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_embedding_issues() {
+        assert!(embedding_issues(&record("ok.txt", vec![0.6, 0.8]), 2).is_empty());
+        assert_eq!(embedding_issues(&record("short.txt", vec![1.0]), 2), vec![
+            IndexIssue::WrongDimension { expected: 2, actual: 1 },
+        ]);
+        assert_eq!(embedding_issues(&record("nan.txt", vec![f32::NAN, 0.0]), 2), vec![
+            IndexIssue::NonFiniteEmbedding,
+        ]);
+        assert_eq!(embedding_issues(&record("empty.txt", Vec::new()), 2), vec![
+            IndexIssue::EmptyEmbedding,
+        ]);
+
+        let mut pending = record("pending.txt", Vec::new());
+        pending.embedded = false;
+        assert!(embedding_issues(&pending, 2).is_empty(), "Unembedded records are expected");
+    }
+
+    #[test]
+    fn test_duplicate_issues() {
+        let records = vec![
+            record("a.txt", vec![0.6, 0.8]),
+            record("b.txt", vec![0.8, 0.6]),
+            record("copy_of_a.txt", vec![0.6, 0.8]),
+        ];
+        assert_eq!(duplicate_issues(&records), vec![
+            (
+                "copy_of_a.txt".to_string(),
+                IndexIssue::DuplicateContent { original: "a.txt".to_string() },
+            ),
+        ]);
+    }
+}
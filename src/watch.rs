@@ -0,0 +1,154 @@
+//! Batching of file-change events for
+//! [`FileEmbeddingSystem::watch_directory`](crate::FileEmbeddingSystem::watch_directory).
+
+use std::collections::BTreeSet;
+use std::future::Future;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::time::{ interval_at, Instant, MissedTickBehavior };
+
+/// How file changes seen by the watcher are committed to the index.
+///
+/// Changes are accumulated and committed in batches, so a build that rewrites hundreds of
+/// files, or saves each of them several times, costs a handful of commits rather than one
+/// per event.
+#[derive(Debug, Clone)]
+pub struct WatchConfig {
+    /// How often pending changes are committed.
+    pub commit_interval: Duration,
+    /// Commit as soon as this many distinct paths are pending, without waiting for the
+    /// interval.
+    pub max_pending_changes: usize,
+}
+
+impl Default for WatchConfig {
+    fn default() -> Self {
+        Self {
+            commit_interval: Duration::from_secs(2),
+            max_pending_changes: 500,
+        }
+    }
+}
+
+/// Collects paths from `changes` and passes them to `commit` in batches, until the channel
+/// closes. Returns the number of batches committed.
+///
+/// A path changed several times before a commit appears in the batch once, and batches are
+/// sorted. Whatever is pending when the channel closes is committed before returning.
+pub(crate) async fn batch_changes<E, F, Fut>(
+    mut changes: UnboundedReceiver<PathBuf>,
+    config: &WatchConfig,
+    mut commit: F
+) -> Result<usize, E>
+    where F: FnMut(Vec<PathBuf>) -> Fut, Fut: Future<Output = Result<(), E>>
+{
+    let max_pending = config.max_pending_changes.max(1);
+    let mut pending = BTreeSet::new();
+    let mut commits = 0;
+    // An `interval` ticks immediately; the first commit should wait a full interval
+    let mut ticker = interval_at(Instant::now() + config.commit_interval, config.commit_interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
+    loop {
+        tokio::select! {
+            change = changes.recv() => {
+                let Some(path) = change else {
+                    break;
+                };
+                pending.insert(path);
+                if pending.len() < max_pending {
+                    continue;
+                }
+            }
+            _ = ticker.tick() => {
+                if pending.is_empty() {
+                    continue;
+                }
+            }
+        }
+        commit(std::mem::take(&mut pending).into_iter().collect()).await?;
+        commits += 1;
+    }
+
+    if !pending.is_empty() {
+        commit(pending.into_iter().collect()).await?;
+        commits += 1;
+    }
+
+    Ok(commits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::sync::mpsc::unbounded_channel;
+
+    async fn run(config: &WatchConfig, paths: &[&str]) -> (usize, Vec<Vec<PathBuf>>) {
+        let (sender, receiver) = unbounded_channel();
+        for path in paths {
+            sender.send(PathBuf::from(path)).unwrap();
+        }
+        drop(sender);
+
+        let mut batches = Vec::new();
+        let commits = batch_changes(receiver, config, |batch| {
+            batches.push(batch);
+            async { Ok::<(), ()>(()) }
+        }).await.unwrap();
+        (commits, batches)
+    }
+
+    #[tokio::test]
+    async fn test_quick_changes_are_one_batch() {
+        let config = WatchConfig {
+            commit_interval: Duration::from_secs(60),
+            ..Default::default()
+        };
+        let (commits, batches) = run(&config, &["b.txt", "a.txt", "b.txt", "c.txt"]).await;
+        assert_eq!(commits, 1);
+        assert_eq!(batches, vec![["a.txt", "b.txt", "c.txt"].map(PathBuf::from).to_vec()]);
+    }
+
+    #[tokio::test]
+    async fn test_max_pending_changes_commits_early() {
+        let config = WatchConfig {
+            commit_interval: Duration::from_secs(60),
+            max_pending_changes: 2,
+        };
+        let (commits, batches) = run(&config, &["a.txt", "b.txt", "c.txt", "d.txt", "e.txt"]).await;
+        assert_eq!(commits, 3);
+        assert_eq!(
+            batches
+                .iter()
+                .map(Vec::len)
+                .collect::<Vec<_>>(),
+            vec![2, 2, 1]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_commits_on_interval() {
+        let config = WatchConfig {
+            commit_interval: Duration::from_millis(50),
+            ..Default::default()
+        };
+        let (sender, receiver) = unbounded_channel();
+        let feeder = tokio::spawn(async move {
+            sender.send(PathBuf::from("a.txt")).unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            sender.send(PathBuf::from("b.txt")).unwrap();
+        });
+
+        let mut batches = Vec::new();
+        let commits = batch_changes(receiver, &config, |batch| {
+            batches.push(batch);
+            async { Ok::<(), ()>(()) }
+        }).await.unwrap();
+        feeder.await.unwrap();
+
+        assert_eq!(commits, 2);
+        assert_eq!(batches, vec![vec![PathBuf::from("a.txt")], vec![PathBuf::from("b.txt")]]);
+    }
+}